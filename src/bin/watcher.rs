@@ -2,9 +2,9 @@ use std::{
     time::{Duration},
 };
 use clap::Parser;
-use watch_rs::{utils::OpenResult, models::watcher::Watcher};
+use watch_rs::{utils::{edit_file, OpenResult}, models::{watcher::Watcher, watcher_loop::{LoopEvent, WatcherLoop, WatcherSchedule}}};
 use std::{
-    io::Read, thread, time::{Instant}
+    io::Read, thread
 };
 use signal_hook::{consts::SIGINT, iterator::Signals};
 use log::{debug, trace, LevelFilter};
@@ -64,10 +64,7 @@ fn query_and_fetch_file_input() -> OpenResult<String> {
     let temp_filepath = temp_file.into_temp_path();
     trace!("Created temporary file : {temp_filepath:?}");
 
-    std::process::Command::new("vim")
-        .args([&temp_filepath])
-        .spawn()?
-        .wait()?;
+    edit_file(&temp_filepath)?;
 
     let mut cmds = String::new();
     std::fs::File::open(&temp_filepath)?.read_to_string(&mut cmds)?;
@@ -116,7 +113,7 @@ fn main() -> OpenResult<()> {
     let interval = Duration::from_millis((args.interval * 1000.0).floor() as u64);
     let watch_duration = args.watch_duration.map(|d| Duration::from_millis(d * 1000));
 
-    let mut watcher = Watcher::new(command_timeout)?;
+    let mut watcher = Watcher::new(command_timeout, None)?;
 
     // If set, add the setup commands in the shell
     if let Some(setup_cmds) = optional_setup_cmds {
@@ -124,29 +121,26 @@ fn main() -> OpenResult<()> {
         let _setup_captured_stdout = watcher.exec_cmd_and_fetch_output(&setup_cmds)?;
     }
 
-    let watcher_start_checkpoint = Instant::now();
+    let mut schedule = WatcherSchedule::every(interval);
+    schedule.watch_duration = watch_duration;
 
     // Execute the watcher command in the shell in a loop
-    loop {
-        let captured_stdout = watcher.exec_cmd_and_fetch_output(&command)?;
-
-        trace!("STDIN  > {}", command);
-        trace!("STDOUT = {}", captured_stdout);
-
-        // Break if an interrupt signal was received
-        if interrupt_event_receiver.try_recv().is_ok() {
-            debug!("Received interrupt event, teminating the watcher.");
-            break;
-        }
+    WatcherLoop::new(schedule).run(
+        |_| watcher.exec_cmd_and_fetch_output(&command),
+        |event| {
+            if let LoopEvent::Ran { result, .. } = event {
+                trace!("STDIN  > {}", command);
+                trace!("STDOUT = {}", result.output);
+            }
 
-        // Break if a we have exceeded a 'watch duration' specified
-        if let Some(duration) = &watch_duration {
-            if duration < &watcher_start_checkpoint.elapsed() {
-                break;
+            // Break if an interrupt signal was received
+            if interrupt_event_receiver.try_recv().is_ok() {
+                debug!("Received interrupt event, teminating the watcher.");
+                return false;
             }
-        }
-        thread::sleep(interval);
-    }
+            true
+        },
+    ).map_err(|(_, err)| err)?;
 
     watcher.kill()?;
 
@@ -0,0 +1,198 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use flate2::{write::GzEncoder, Compression};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use watch_rs::utils::OpenResult;
+
+use crate::{
+    envs::WATCHER_LOGS_DIR,
+    redact,
+    tui::{query::QueryState, WatcherIterationOutput},
+};
+
+/// Iterations of each pane kept for the debug bundles.
+pub const BUNDLED_ITERATIONS: usize = 5;
+/// Lines of the end of the log file included in the debug bundles.
+const LOG_EXCERPT_LINES: usize = 200;
+
+/// Values of assignments which look like credentials, like `password=...` or `API_TOKEN: ...`.
+static SECRET_ASSIGNMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?i)([a-z0-9_.-]*(?:password|passwd|secret|token|api[_-]?key|auth)[a-z0-9_.-]*)(\s*[=:]\s*)("[^"]*"|'[^']*'|[^\s;&|]+)"#,
+    )
+    .unwrap()
+});
+const REDACTED: &str = "<redacted>";
+
+/// How the running watch got started, recorded for the debug bundles.
+#[derive(Clone)]
+pub struct DebugSession {
+    /// Command line of the watch.
+    pub argv: Vec<String>,
+    /// The `.watchrc.toml` presets in use.
+    pub watchrc_path: Option<PathBuf>,
+    /// Commands of the watch, as last submitted.
+    pub query_state: QueryState,
+}
+
+/// Recent iterations of one of the watchers.
+pub struct PaneIterations<'a> {
+    pub title: Option<&'a str>,
+    pub iterations: Vec<&'a WatcherIterationOutput>,
+}
+
+#[derive(Serialize)]
+struct DebugBundle {
+    version: &'static str,
+    created_at: DateTime<Local>,
+    os: &'static str,
+    arch: &'static str,
+    term: Option<String>,
+    argv: Vec<String>,
+    watchrc: Option<BundledFile>,
+    env_vars: Vec<(String, String)>,
+    setup_commands: String,
+    main_commands: String,
+    teardown_commands: String,
+    transform: String,
+    before_each: String,
+    after_each: String,
+    log_excerpt: Vec<String>,
+    panes: Vec<BundledPane>,
+}
+
+#[derive(Serialize)]
+struct BundledFile {
+    path: PathBuf,
+    contents: String,
+}
+
+#[derive(Serialize)]
+struct BundledPane {
+    title: Option<String>,
+    iterations: Vec<BundledIteration>,
+}
+
+#[derive(Serialize)]
+struct BundledIteration {
+    iteration: usize,
+    started_at: DateTime<Local>,
+    finished_at: DateTime<Local>,
+    status: String,
+    exit_code: Option<i32>,
+    run_duration_ms: u128,
+    output_bytes: usize,
+    truncated_at: Option<usize>,
+    has_changed: bool,
+    output: String,
+}
+
+/// Mask the values of the assignments looking like credentials.
+fn redact_secrets(text: &str) -> String {
+    SECRET_ASSIGNMENT
+        .replace_all(text, format!("${{1}}${{2}}{REDACTED}"))
+        .into_owned()
+}
+
+/// Last lines of the log file of this process.
+fn log_excerpt() -> Vec<String> {
+    let log = fs::read_to_string(WATCHER_LOGS_DIR.path().join("watcher.log")).unwrap_or_default();
+    let lines = log.lines().collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(LOG_EXCERPT_LINES)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Package the configuration, commands, log excerpt and recent iterations of the watch into a
+/// gzip-compressed JSON file at `path`, to be attached to bug reports. With `redact`, the
+/// environment variable values and the credentials found in the texts are masked.
+pub fn write_debug_bundle(
+    path: &Path,
+    session: &DebugSession,
+    panes: &[PaneIterations<'_>],
+    redact: bool,
+) -> OpenResult<()> {
+    // The `--redact` matches are masked in any case
+    let scrub = |text: &str| match redact {
+        true => redact_secrets(&redact::redact(text)),
+        false => redact::redact_owned(text.to_string()),
+    };
+    let query_state = &session.query_state;
+
+    let watchrc = session
+        .watchrc_path
+        .as_ref()
+        .map(|watchrc_path| BundledFile {
+            path: watchrc_path.clone(),
+            contents: scrub(&fs::read_to_string(watchrc_path).unwrap_or_default()),
+        });
+    let panes = panes
+        .iter()
+        .map(|pane| BundledPane {
+            title: pane.title.map(str::to_string),
+            iterations: pane
+                .iterations
+                .iter()
+                .map(|event| BundledIteration {
+                    iteration: event.iteration,
+                    started_at: event.started_at,
+                    finished_at: event.finished_at,
+                    status: event.status.to_string(),
+                    exit_code: event.exit_code,
+                    run_duration_ms: event.run_duration.as_millis(),
+                    output_bytes: event.output_bytes,
+                    truncated_at: event.truncated_at,
+                    has_changed: event.has_changed,
+                    output: scrub(&event.output),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let bundle = DebugBundle {
+        version: env!("CARGO_PKG_VERSION"),
+        created_at: Local::now(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        term: std::env::var("TERM").ok(),
+        argv: session.argv.iter().map(|arg| scrub(arg)).collect(),
+        watchrc,
+        env_vars: query_state
+            .env_vars
+            .iter()
+            .map(|(key, value)| match redact {
+                true => (key.clone(), REDACTED.to_string()),
+                false => (key.clone(), value.clone()),
+            })
+            .collect(),
+        setup_commands: scrub(&query_state.setup_commands),
+        main_commands: scrub(&query_state.main_commands),
+        teardown_commands: scrub(&query_state.teardown_commands),
+        transform: scrub(&query_state.transform),
+        before_each: scrub(&query_state.before_each),
+        after_each: scrub(&query_state.after_each),
+        log_excerpt: log_excerpt().iter().map(|line| scrub(line)).collect(),
+        panes,
+    };
+
+    let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    encoder.write_all(serde_json::to_string_pretty(&bundle)?.as_bytes())?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Name of a new debug bundle file.
+pub fn default_bundle_name() -> String {
+    format!(
+        "watch-rs-debug-{}.json.gz",
+        Local::now().format("%Y%m%d-%H%M%S")
+    )
+}
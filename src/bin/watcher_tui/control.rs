@@ -0,0 +1,119 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use crossbeam_channel::{unbounded, Receiver};
+use log::{debug, warn};
+use watch_rs::utils::OpenResult;
+
+/// Socket on which a running watch accepts control requests, when no other path is given.
+pub fn default_socket_path() -> PathBuf {
+    std::env::temp_dir().join("watch-rs.sock")
+}
+
+/// Request sent to a running watch through its control socket, one per line.
+pub enum ControlRequest {
+    /// Label the current iteration, to correlate changes with external actions afterwards.
+    Mark(String),
+    /// Write a debug bundle of the watch to the path, with the secrets masked when `redact` is set.
+    DebugBundle { path: PathBuf, redact: bool },
+}
+
+impl ControlRequest {
+    fn parse(line: &str) -> Result<Self, String> {
+        match line.trim().split_once(' ') {
+            Some(("mark", label)) if !label.trim().is_empty() => {
+                Ok(Self::Mark(label.trim().to_string()))
+            }
+            Some(("bundle", path)) => {
+                let (path, redact) = match path.trim().strip_prefix("--redact ") {
+                    Some(path) => (path, true),
+                    None => (path, false),
+                };
+                Ok(Self::DebugBundle {
+                    path: PathBuf::from(path.trim()),
+                    redact,
+                })
+            }
+            _ => Err(format!("unknown control request `{}`", line.trim())),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        match self {
+            Self::Mark(label) => format!("mark {label}"),
+            Self::DebugBundle { path, redact: true } => {
+                format!("bundle --redact {}", path.display())
+            }
+            Self::DebugBundle {
+                path,
+                redact: false,
+            } => format!("bundle {}", path.display()),
+        }
+    }
+}
+
+/// A labeled point of the watch timeline.
+pub struct IterationMark {
+    pub label: String,
+    /// Iteration which was the latest one when the mark got added.
+    pub iteration: usize,
+    pub marked_at: DateTime<Local>,
+}
+
+/// Listen for control requests on the `socket_path`, forwarded to the returned receiver.
+/// Fails when another running watch already listens on the socket.
+pub fn serve(socket_path: &Path) -> OpenResult<Receiver<ControlRequest>> {
+    // A socket left behind by a watch which did not exit cleanly can be taken over
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(format!("{} is in use by another watch", socket_path.display()).into());
+        }
+        std::fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    debug!("Listening for control requests on {socket_path:?}");
+
+    let (request_sender, request_receiver) = unbounded();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reply_stream = match stream.try_clone() {
+                Ok(reply_stream) => reply_stream,
+                Err(err) => {
+                    warn!("Dropping control connection : {err}");
+                    continue;
+                }
+            };
+
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                let reply = match ControlRequest::parse(&line) {
+                    Ok(request) => match request_sender.send(request) {
+                        Ok(()) => "ok".to_string(),
+                        Err(_) => return,
+                    },
+                    Err(err) => err,
+                };
+                writeln!(reply_stream, "{reply}").ok();
+            }
+        }
+    });
+
+    Ok(request_receiver)
+}
+
+/// Send the `request` to the watch listening on the `socket_path`, returning its reply.
+pub fn send(socket_path: &Path, request: &ControlRequest) -> OpenResult<String> {
+    let mut stream = UnixStream::connect(socket_path)
+        .map_err(|err| format!("no watch listening on {} : {err}", socket_path.display()))?;
+    writeln!(stream, "{}", request.to_line())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
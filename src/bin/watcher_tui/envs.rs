@@ -31,7 +31,7 @@ pub static WATCHER_LOGS_DIR: Lazy<DumpDir> =
             let dump_dir = Path::new(&logs_dir).join(format!("watcher_{timestamp}"));
             std::fs::create_dir_all(&dump_dir).unwrap();
 
-            return DumpDir::SetDir(dump_dir);
+            DumpDir::SetDir(dump_dir)
         }
         None => DumpDir::TempDir(TempDir::with_prefix("watcher").unwrap()),
     });
@@ -0,0 +1,344 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use crossbeam_channel::{Receiver, Select};
+use log::{info, warn};
+use regex::Regex;
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use watch_rs::{
+    models::{
+        json_diff::JsonPath,
+        shell,
+        watcher::ExecStatus,
+        watcher_loop::{IterationResult, WatcherSchedule},
+    },
+    utils::OpenResult,
+};
+
+use crate::bundle::{write_debug_bundle, DebugSession, PaneIterations, BUNDLED_ITERATIONS};
+use crate::control::ControlRequest;
+use crate::redact::redact;
+use crate::report::{write_report, PaneHistory, SessionHistory};
+use crate::tui::{
+    self,
+    format::{describe_json_change, filter_lines, json_output_diff, Locale, OutputFormat},
+    query::QueryState,
+    DropPolicy, OutputPipeline, TitledWatchers, WatcherOutputEvent,
+};
+use crate::{hooks::Hooks, output_log::OutputLog};
+
+/// Output options of the headless mode, set from the command line.
+pub struct HeadlessOptions {
+    pub format: OutputFormat,
+    /// JSON paths left out of the reported JSON changes.
+    pub json_ignore: Vec<JsonPath>,
+    /// Only the output lines matching the filter are printed.
+    pub filter: Option<Regex>,
+    /// Control requests sent with `ctl`, marks get printed in between the iterations.
+    pub control_receiver: Option<Receiver<ControlRequest>>,
+    /// Conventions for rendering the numbers and the timestamps.
+    pub locale: Locale,
+    /// How the watch got started, packaged into the debug bundles requested with `debug-bundle`.
+    pub debug_session: DebugSession,
+    /// File the raw output of every iteration is appended to.
+    pub output_log: Option<OutputLog>,
+    pub hooks: Hooks,
+    /// HTML report of the session written when the watch ends.
+    pub report_path: Option<PathBuf>,
+    /// Memory budget of the recorded outputs of each watcher, for the report.
+    pub max_history_bytes: Option<usize>,
+    /// Cron expression of `--schedule`, in the separators instead of the interval.
+    pub cron: Option<String>,
+    /// Named in the separators of the iterations, instead of the command.
+    pub title: Option<String>,
+    /// Only the outputs are printed, without the separators of the iterations.
+    pub no_title: bool,
+    /// Events dropped once the queue of a watcher is full.
+    pub event_overflow: DropPolicy,
+}
+
+/// Run the watchers without a TUI, printing every iteration to stdout with a timestamp header.
+/// Each of the watched commands comes with its watchers, one per host in fan-out mode.
+pub fn run_headless_app(
+    watches: Vec<(QueryState, TitledWatchers)>,
+    schedule: WatcherSchedule,
+    pipeline: OutputPipeline,
+    options: HeadlessOptions,
+) -> OpenResult<()> {
+    use WatcherOutputEvent::*;
+
+    let should_close_watcher = Arc::new(AtomicBool::new(false));
+
+    // First SIGINT, SIGTERM or SIGHUP gracefully closes the watcher, a second one terminates
+    // right away along with the shells and their commands
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    let should_close_on_signal = Arc::clone(&should_close_watcher);
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if should_close_on_signal.swap(true, Ordering::AcqRel) {
+                shell::kill_all_shells();
+                std::process::exit(128 + signal);
+            }
+        }
+    });
+
+    // Commands of the panes, named in the headers of their iterations
+    let mut command_summaries = Vec::new();
+    let mut commands = Vec::new();
+    let mut receivers = Vec::new();
+    let query_state = watches[0].0.clone();
+    for (watch_query_state, watchers) in watches {
+        let command_summary = match &options.title {
+            Some(title) => title.clone(),
+            None => redact(watch_query_state.main_commands.trim()).replace('\n', "; "),
+        };
+        for (title, watcher) in watchers {
+            let (event_sender, event_receiver) = tui::event_channel(options.event_overflow);
+            tui::run_watcher_thread(
+                watcher,
+                watch_query_state.clone(),
+                schedule.clone(),
+                pipeline.clone(),
+                options
+                    .output_log
+                    .as_ref()
+                    .map(|output_log| output_log.for_pane(title.clone())),
+                options.hooks.for_pane(title.clone()),
+                event_sender,
+                Arc::clone(&should_close_watcher),
+                None,
+                false,
+            );
+            command_summaries.push(command_summary.clone());
+            commands.push(watch_query_state.main_commands.clone());
+            receivers.push((title, event_receiver));
+        }
+    }
+
+    // Print the events of all the watchers in their order of arrival
+    let mut select = Select::new();
+    receivers.iter().for_each(|(_, receiver)| {
+        select.recv(receiver);
+    });
+    let control_index = options
+        .control_receiver
+        .as_ref()
+        .map(|control_receiver| select.recv(control_receiver));
+    let mut latest_iteration = 0;
+
+    let mut previous_outputs: Vec<Option<String>> = vec![None; receivers.len()];
+    let mut recent_events = vec![VecDeque::with_capacity(BUNDLED_ITERATIONS); receivers.len()];
+    let mut histories = (0..receivers.len())
+        .map(|_| SessionHistory::new(options.max_history_bytes))
+        .collect::<Vec<_>>();
+    let mut running_watchers = receivers.len();
+    let mut stdout = io::stdout().lock();
+    while running_watchers > 0 {
+        let operation = select.select();
+        let index = operation.index();
+
+        if Some(index) == control_index {
+            match operation.recv(options.control_receiver.as_ref().unwrap()) {
+                Ok(ControlRequest::Mark(label)) => {
+                    let header = format!(
+                        "⚑ {label} | Itr: {}",
+                        options.locale.format_integer(latest_iteration)
+                    );
+                    write_output_block(&mut stdout, &header, "", Local::now(), &options)?
+                }
+                Ok(ControlRequest::DebugBundle { path, redact }) => {
+                    let panes = receivers
+                        .iter()
+                        .zip(&recent_events)
+                        .map(|((title, _), events)| PaneIterations {
+                            title: title.as_deref(),
+                            iterations: events.iter().collect(),
+                        })
+                        .collect::<Vec<_>>();
+                    match write_debug_bundle(&path, &options.debug_session, &panes, redact) {
+                        Ok(()) => info!("Debug bundle written to {path:?}"),
+                        Err(err) => warn!("Debug bundle failed : {err}"),
+                    }
+                }
+                Err(_) => {
+                    select.remove(index);
+                }
+            }
+            continue;
+        }
+
+        let (title, receiver) = &receivers[index];
+        let previous_output = &mut previous_outputs[index];
+        let title_prefix = title
+            .as_ref()
+            .map(|title| format!("{title} | "))
+            .unwrap_or_default();
+
+        match operation.recv(receiver) {
+            Ok(SetupResult(res)) if !res.is_success() => write_output_block(
+                &mut stdout,
+                &format!(
+                    "{title_prefix}Setup failed | {}",
+                    match res.exit_code {
+                        Some(exit_code) => format!("exit {exit_code}"),
+                        None => res.status.to_string(),
+                    }
+                ),
+                &res.output,
+                res.finished_at,
+                &options,
+            )?,
+            Ok(SetupResult(res)) if !res.output.is_empty() => write_output_block(
+                &mut stdout,
+                &format!("{title_prefix}Setup"),
+                &res.output,
+                res.finished_at,
+                &options,
+            )?,
+            Ok(SetupResult(_)) => {}
+            Ok(Error(error)) => write_output_block(
+                &mut stdout,
+                &format!("{title_prefix}Watch failed"),
+                &error,
+                Local::now(),
+                &options,
+            )?,
+            Ok(ShellRestarted(res, _)) => write_output_block(
+                &mut stdout,
+                &format!("{title_prefix}Shell restarted"),
+                &res.output,
+                res.finished_at,
+                &options,
+            )?,
+            Ok(IterationResult(mut res)) => {
+                latest_iteration = latest_iteration.max(res.iteration);
+                let events = &mut recent_events[index];
+                if events.len() == BUNDLED_ITERATIONS {
+                    events.pop_front();
+                }
+                events.push_back(res.clone());
+                histories[index].record(&res);
+                if let Some(filter) = &options.filter {
+                    res.output = filter_lines(&res.output, filter);
+                }
+
+                let cadence = match &options.cron {
+                    Some(cron) => format!("At {cron}"),
+                    None => format!(
+                        "Every {}s",
+                        options.locale.format_decimal(res.interval.as_secs_f64(), 1)
+                    ),
+                };
+                let mut header = format!(
+                    "{cadence}: {} | {title_prefix}Itr: {}",
+                    command_summaries[index],
+                    options.locale.format_integer(res.iteration)
+                );
+                if res.status != ExecStatus::Completed {
+                    header.push_str(&format!(" | {}", res.status));
+                }
+                if let Some(truncated_at) = res.truncated_at {
+                    header.push_str(&format!(
+                        " | output truncated at {} bytes",
+                        options.locale.format_integer(truncated_at)
+                    ));
+                }
+
+                // Past the first iteration, JSON outputs are reported as their changes
+                let json_changes = previous_output
+                    .as_deref()
+                    .filter(|_| options.format == OutputFormat::Json)
+                    .and_then(|previous| {
+                        json_output_diff(previous, &res.output, &options.json_ignore)
+                    })
+                    .map(|diff| {
+                        diff.changes()
+                            .iter()
+                            .map(|change| describe_json_change(change) + "\n")
+                            .collect::<String>()
+                    });
+                match json_changes {
+                    Some(changes) if changes.is_empty() => write_output_block(
+                        &mut stdout,
+                        &header,
+                        "(no changes)",
+                        res.finished_at,
+                        &options,
+                    )?,
+                    Some(changes) => write_output_block(
+                        &mut stdout,
+                        &header,
+                        &changes,
+                        res.finished_at,
+                        &options,
+                    )?,
+                    None => write_output_block(
+                        &mut stdout,
+                        &header,
+                        &res.output,
+                        res.finished_at,
+                        &options,
+                    )?,
+                }
+                *previous_output = Some(res.output);
+            }
+            // Iterations are only printed once complete
+            Ok(PartialOutput(_) | RunStarted(_) | Sleeping(_) | WaitingForChange) => {}
+            Ok(End) | Err(_) => {
+                select.remove(index);
+                running_watchers -= 1;
+            }
+        }
+    }
+
+    if let Some(report_path) = &options.report_path {
+        let panes = receivers
+            .iter()
+            .zip(&commands)
+            .zip(&histories)
+            .map(|(((title, _), command), history)| PaneHistory {
+                title: title.as_deref(),
+                command,
+                history,
+            })
+            .collect::<Vec<_>>();
+        write_report(report_path, &query_state, &panes)?;
+        info!("Session report written to {report_path:?}");
+    }
+
+    Ok(())
+}
+
+fn write_output_block(
+    out: &mut impl Write,
+    header: &str,
+    output: &str,
+    taken_at: DateTime<Local>,
+    options: &HeadlessOptions,
+) -> io::Result<()> {
+    if !options.no_title {
+        let timestamp = options.locale.format_clock(&taken_at, false);
+        writeln!(out, "==> {header} | {timestamp} <==")?;
+    }
+    write!(out, "{output}")?;
+    if !output.is_empty() && !output.ends_with('\n') {
+        writeln!(out)?;
+    }
+    if !options.no_title {
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
@@ -0,0 +1,184 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use watch_rs::models::{json_diff::JsonPath, watcher::ExecStatus};
+
+use crate::{
+    redact::redact,
+    tui::{format::json_output_diff, WatcherIterationOutput},
+};
+
+/// Local commands run by the watcher threads in reaction to the iterations, like sending a
+/// message once the output changed. Each watcher thread runs the hooks of its pane.
+#[derive(Clone)]
+pub struct Hooks {
+    /// Run whenever the output differs from the one of the previous iteration.
+    on_change: Option<String>,
+    /// Run whenever the command fails, at most once per `failure_throttle`.
+    on_failure: Option<String>,
+    failure_throttle: Duration,
+    /// When the failure hook last ran.
+    failure_hooked_at: Option<Instant>,
+    /// Host of the pane in fan-out mode, exported to the hooks.
+    title: Option<String>,
+    /// Paths left out of the JSON diff exporting the changed paths, when the outputs are
+    /// compared or shown as JSON.
+    json_ignore: Option<Vec<JsonPath>>,
+}
+
+impl Hooks {
+    pub fn new(on_change: Option<String>) -> Self {
+        Self {
+            on_change,
+            on_failure: None,
+            failure_throttle: Duration::ZERO,
+            failure_hooked_at: None,
+            title: None,
+            json_ignore: None,
+        }
+    }
+
+    /// Run the `on_failure` hook on the failed iterations, at most once per `throttle` not to
+    /// fire it every iteration while the command keeps failing.
+    pub fn with_on_failure(self, on_failure: Option<String>, throttle: Duration) -> Self {
+        Self {
+            on_failure,
+            failure_throttle: throttle,
+            ..self
+        }
+    }
+
+    /// Export the paths changed in the JSON outputs, leaving out the `json_ignore` paths.
+    pub fn with_json_changes(self, json_ignore: Option<Vec<JsonPath>>) -> Self {
+        Self {
+            json_ignore,
+            ..self
+        }
+    }
+
+    /// The same hooks, exporting the host of the pane.
+    pub fn for_pane(&self, title: Option<String>) -> Self {
+        Self {
+            title,
+            ..self.clone()
+        }
+    }
+
+    /// Run the hooks following the `iteration` of the `command`, `changed_from` the output of the
+    /// previous iteration when it differs.
+    pub fn after_iteration(
+        &mut self,
+        command: &str,
+        iteration: &WatcherIterationOutput,
+        changed_from: Option<&str>,
+    ) {
+        if let (Some(on_change), true) = (&self.on_change, iteration.has_changed) {
+            self.run("on-change", on_change, command, iteration, changed_from);
+        }
+
+        // Cancelled runs were stopped on purpose, their exit code is not a failure of the command
+        let has_failed = match iteration.status {
+            ExecStatus::Completed => iteration.exit_code != Some(0),
+            ExecStatus::TimedOut(_) => true,
+            ExecStatus::Cancelled => false,
+        };
+        if let (Some(on_failure), true) = (&self.on_failure, has_failed) {
+            match self.failure_hooked_at {
+                Some(hooked_at) if hooked_at.elapsed() < self.failure_throttle => {
+                    debug!(
+                        "Throttled the on-failure hook of iteration {}",
+                        iteration.iteration
+                    )
+                }
+                _ => {
+                    self.run("on-failure", on_failure, command, iteration, changed_from);
+                    self.failure_hooked_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Run the `hook` in the background, not to delay the next iteration, with the output of
+    /// the `iteration` on its stdin and its metadata in `WATCH_RS_*` variables.
+    /// Failures of the hook are only logged not to stop the watch.
+    fn run(
+        &self,
+        name: &'static str,
+        hook: &str,
+        command: &str,
+        iteration: &WatcherIterationOutput,
+        changed_from: Option<&str>,
+    ) {
+        debug!(
+            "Running the {name} hook of iteration {}",
+            iteration.iteration
+        );
+        let mut hook_command = Command::new("/bin/bash");
+        hook_command
+            .args(["-c", hook])
+            .env("WATCH_RS_COMMAND", redact(command).as_ref())
+            .env("WATCH_RS_ITERATION", iteration.iteration.to_string())
+            .env("WATCH_RS_STATUS", iteration.status.to_string())
+            .env(
+                "WATCH_RS_DURATION_MS",
+                iteration.run_duration.as_millis().to_string(),
+            )
+            .env("WATCH_RS_STARTED_AT", iteration.started_at.to_rfc3339())
+            .env("WATCH_RS_FINISHED_AT", iteration.finished_at.to_rfc3339())
+            .env("WATCH_RS_OUTPUT_BYTES", iteration.output_bytes.to_string())
+            .env(
+                "WATCH_RS_CHANGED",
+                (iteration.has_changed as u8).to_string(),
+            )
+            .env("WATCH_RS_HOST", self.title.as_deref().unwrap_or_default())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        if let Some(exit_code) = iteration.exit_code {
+            hook_command.env("WATCH_RS_EXIT_CODE", exit_code.to_string());
+        }
+        // One changed path per line, like `.items[2].name`
+        if let Some(diff) =
+            changed_from
+                .zip(self.json_ignore.as_deref())
+                .and_then(|(previous, json_ignore)| {
+                    json_output_diff(previous, &iteration.output, json_ignore)
+                })
+        {
+            let changed_paths = diff
+                .changes()
+                .iter()
+                .map(|change| change.path())
+                .collect::<Vec<_>>()
+                .join("\n");
+            hook_command.env("WATCH_RS_CHANGED_PATHS", changed_paths);
+        }
+
+        let output = iteration.output.clone();
+        let iteration = iteration.iteration;
+        thread::spawn(move || {
+            let result = hook_command.spawn().and_then(|mut child| {
+                // Written from another thread, as the hook may output before reading all of its input
+                let mut stdin = child.stdin.take().unwrap();
+                let writer = thread::spawn(move || stdin.write_all(output.as_bytes()));
+                let result = child.wait_with_output()?;
+                writer.join().ok();
+                Ok(result)
+            });
+            match result {
+                Ok(result) if result.status.success() => {}
+                Ok(result) => warn!(
+                    "The {name} hook of iteration {iteration} failed with {} : {}",
+                    result.status,
+                    String::from_utf8_lossy(&result.stderr).trim()
+                ),
+                Err(err) => warn!("The {name} hook of iteration {iteration} failed to run : {err}"),
+            }
+        });
+    }
+}
@@ -1,57 +1,540 @@
+mod bundle;
+mod control;
 mod envs;
+mod headless;
+mod hooks;
+mod output_log;
+mod redact;
+mod report;
+mod snapshot;
+mod trigger;
 mod tui;
+mod watchfile;
+mod watchrc;
 
-use clap::Parser;
-use crossbeam_channel::unbounded;
+use bundle::DebugSession;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use control::ControlRequest;
+use crossterm::{
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
+    execute,
+};
 use envs::WATCHER_LOGS_DIR;
-use log::{trace, LevelFilter};
+use headless::HeadlessOptions;
+use hooks::Hooks;
+use log::{info, trace, warn, LevelFilter};
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::Config;
+use output_log::{ExportFormat, OutputLog};
 use ratatui::DefaultTerminal;
-use std::time::Duration;
+use redact::RedactingEncoder;
+use regex::Regex;
+use signal_hook::{
+    consts::{SIGHUP, SIGTERM},
+    iterator::Signals,
+};
+use snapshot::SnapshotReader;
 use std::{
-    io::Read,
-    sync::{atomic::AtomicBool, Arc},
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use trigger::{FileTrigger, PathGlob};
+use tui::{
+    alert::AlertRule,
+    format::{DiffGranularity, HourCycle, Locale, OutputFormat, TimeFormat, Timezone},
+    highlight::HighlightRule,
+    plot::MetricExtractor,
+    query::{QueryState, QueryTui},
+    record::Recorder,
+    summary::SummaryExtractor,
+    watcher::{
+        summarize_command, SplitDirection, Theme, UnfocusedMode, WatchTab, WatcherPane, WatcherTui,
+        WatcherTuiExit, WatcherTuiOptions,
+    },
+    DropPolicy, OutputPipeline, ShellRestart, TitledWatchers,
 };
-use tui::query::QueryTui;
-use watch_rs::{models::watcher::Watcher, utils::OpenResult};
+use watch_rs::{
+    models::{
+        comparator::{CompareMode, SharedComparator},
+        encoding::OutputEncoding,
+        json_diff::JsonPath,
+        shell,
+        watcher::{self, Watcher},
+        watcher_loop::{Backoff, CronSchedule, Trigger, WatcherSchedule},
+    },
+    utils::{edit_file, find_editor, parse_duration, parse_env_var, OpenResult},
+};
+use watchfile::{confirm_watchfile, WatchFile};
+use watchrc::WatchRc;
 
-const DEFAULT_COMMAND_TIMEOUT: u64 = 30 * 1000;
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+/// Bytes of each output kept by default, 4 MiB.
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+/// Bytes of the recorded outputs of each pane kept by default, 64 MiB.
+const DEFAULT_MAX_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+/// Time given to the watchers to close on SIGTERM or SIGHUP, before tearing them down.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// Characters of the commands naming the panes of `--split`.
+const PANE_TITLE_WIDTH: usize = 32;
 
 /// Short help message
 #[derive(Parser)]
 #[command(version, about, long_about=None)]
 struct Args {
     /// Individual command run timeout.
-    /// Unit in seconds.
-    #[arg(short = 't', long)]
-    timeout: Option<u64>,
+    /// Accepts seconds or a human-friendly duration like `500ms`, `2.5s` or `5m`.
+    #[arg(short = 't', long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
 
     /// Call interval between two command invocations.
-    /// Defaults to 1 second. Unit in seconds.
-    #[arg(short = 'n', long, default_value_t = 1.0)]
-    interval: f64,
+    /// Defaults to 1 second. Accepts seconds or a human-friendly duration like `500ms` or `5m`.
+    #[arg(short = 'n', long, default_value = "1s", value_parser = parse_duration)]
+    interval: Duration,
+
+    /// Run the command on a fixed cadence of `interval` from the start, instead of sleeping
+    /// `interval` after each run. Runs overrunning their slot skip the missed slots.
+    #[arg(short = 'p', long, default_value_t = false)]
+    precise: bool,
+
+    /// Run the command at the times of a cron expression, like `*/5 * * * *` for every 5
+    /// minutes on the minute, instead of every `interval`. Takes the 5 fields of crontab, or 6
+    /// and 7 fields starting with the seconds and ending with the years. The first iteration
+    /// runs right away, the next ones on the schedule.
+    #[arg(long, value_name = "CRON", conflicts_with_all = ["interval", "precise"])]
+    schedule: Option<CronSchedule>,
 
     /// Main command to execute and watch on.
     /// Optional to pass as a command argument, as we would query user for command(s) if not provided.
+    /// Can be repeated to watch several commands, each in its own tab, or sharing the screen
+    /// with `--split`.
     #[arg(short = 'c', long)]
-    command: Option<String>,
+    command: Vec<String>,
+
+    /// Character encoding of the command output, like `latin-1` or `shift-jis` for legacy tools.
+    /// Defaults to UTF-8, with the invalid byte sequences replaced rather than garbling the TUI.
+    #[arg(long, default_value_t = OutputEncoding::Utf8)]
+    encoding: OutputEncoding,
+
+    /// Only keep the first bytes of each output, for commands flooding their output not to
+    /// exhaust the memory. The iterations cut short are flagged as truncated. 0 keeps them whole.
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_OUTPUT_BYTES)]
+    max_output_bytes: usize,
+
+    /// Bash compatible shell to run the command in, like a Bash installed elsewhere.
+    /// Remote commands of the fan-out mode run in the `/bin/bash` of the hosts.
+    #[arg(long, value_name = "PATH", default_value = watcher::DEFAULT_SHELL)]
+    shell: PathBuf,
+
+    /// Directory to run the command in, instead of the one watch-rs was launched from.
+    /// In fan-out mode, the directory is looked up on each host.
+    #[arg(long, value_name = "DIR")]
+    cwd: Option<PathBuf>,
+
+    /// Environment variable exported in the watcher shell before the setup commands run.
+    /// Can be repeated to export several variables, which can then be edited in the Env tab.
+    #[arg(short = 'e', long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    envs: Vec<(String, String)>,
 
     /// Total duration for the watcher process.
     /// If a provided duration is smaller than interval (+ timeout), then we would exit after the first run.
-    /// Defaults to None for infinite runs. Unit in seconds.
-    #[arg(short = 'w', long)]
-    watch_duration: Option<u64>,
+    /// Defaults to None for infinite runs. Accepts seconds or a human-friendly duration like `1h`.
+    #[arg(short = 'w', long, value_parser = parse_duration)]
+    watch_duration: Option<Duration>,
+
+    /// Also run an iteration whenever a file matching this glob changes, like `src/**/*.rs`,
+    /// looked up from the `--cwd` directory. Can be repeated to watch several globs.
+    /// `**` matches across the directories, `*` and `?` within a path component, and `{a,b}`
+    /// any of the alternatives.
+    #[arg(long, value_name = "GLOB")]
+    watch_path: Vec<PathGlob>,
+
+    /// Only run an iteration when the `--watch-path` files change, rather than every interval.
+    #[arg(long, default_value_t = false, requires = "watch_path")]
+    watch_path_only: bool,
+
+    /// Only run an iteration when the git repository of the `--cwd` directory changes: a commit
+    /// or a checkout moving HEAD, a staged change, or a change of a tracked file or of an
+    /// untracked one not ignored by `.gitignore`. Combines with `--watch-path`, for the files
+    /// out of the repository.
+    #[arg(long, default_value_t = false)]
+    watch_git: bool,
+
+    /// Quiet time after a change of the `--watch-path` files before running, for the burst of
+    /// writes of a save or of a checkout to trigger a single iteration.
+    #[arg(long, value_name = "DURATION", default_value = "200ms", value_parser = parse_duration)]
+    watch_debounce: Duration,
+
+    /// Detect the changes of the `--watch-path` and `--watch-git` files by scanning them a few
+    /// times a second, rather than from the file events of the OS. For the file systems without
+    /// file events, like the network ones or the mounts of some containers.
+    #[arg(long, default_value_t = false)]
+    watch_poll: bool,
+
+    /// Interval used while the command fails (non-zero exit or timeout), switching back to
+    /// `interval` on the next success. Accepts the same formats as `interval`.
+    #[arg(long, value_parser = parse_duration, conflicts_with = "backoff")]
+    interval_on_error: Option<Duration>,
+
+    /// Back off on failures: after a non-zero exit or a timeout, the interval gets multiplied
+    /// by this factor (up to `--backoff-max`), and resets to normal on the next success.
+    #[arg(long, value_name = "FACTOR")]
+    backoff: Option<f64>,
+
+    /// Cap of the backed off interval. Defaults to 5 minutes.
+    #[arg(long, default_value = "5m", value_parser = parse_duration)]
+    backoff_max: Duration,
+
+    /// Exit when the output of the command changes from the previous iteration.
+    #[arg(short = 'g', long, default_value_t = false)]
+    chgexit: bool,
+
+    /// How outputs are compared for change detection and diff highlighting:
+    /// `exact`, `normalized`, `numeric[:TOLERANCE]`, `json` or `image-hash[:MAX_DISTANCE]`.
+    #[arg(long, default_value_t = CompareMode::Exact)]
+    compare_mode: CompareMode,
+
+    /// Format of the command output. With `json`, iterations are diffed structurally
+    /// and rendered as a tree marking the added, removed and changed nodes. With `auto`, the
+    /// outputs detected as JSON are pretty-printed with sorted keys and otherwise handled as text.
+    /// With `table`, tab, comma or whitespace separated outputs are rendered as a table with the
+    /// changed cells highlighted, its rows get sorted by a column with `o` and reversed with `O`.
+    /// With `csv`, the outputs are parsed as CSV and rendered as such a table.
+    /// With `markdown`, the headings, lists, quotes and code fences are styled.
+    #[arg(long, visible_alias = "render", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Start in diff view, showing the changes from the previous iteration.
+    /// Toggled at runtime with `d`.
+    #[arg(short = 'd', long, default_value_t = false)]
+    diff: bool,
+
+    /// Lines of context kept around the changes in diff view, the farther unchanged lines get
+    /// collapsed into separators. The collapsed lines can be expanded at runtime with `x`.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    diff_context: usize,
+
+    /// Unit of the changes highlighted in diff view: whole lines, or the words or characters
+    /// which changed within the lines. Cycled at runtime with `g`.
+    #[arg(long, value_enum, default_value_t = DiffGranularity::Line)]
+    diff_granularity: DiffGranularity,
+
+    /// Only show the output lines matching this regex, the full output is still logged.
+    /// Toggled at runtime with `f`.
+    #[arg(long, value_name = "REGEX")]
+    filter: Option<Regex>,
+
+    /// Extract a number from each iteration's output, with a regex matching it or capturing it
+    /// in its first group, and chart it over time below the output, like `--plot 'Mem: +\d+ +\d+ +\d+ +\d+ +\d+ +(\d+)'`
+    /// to follow the available memory reported by `free -m`.
+    #[arg(long, value_name = "REGEX")]
+    plot: Option<MetricExtractor>,
+
+    /// Alert when the plotted value satisfies this rule, like `value > 90`, turning the borders
+    /// red, ringing the bell and logging the alert. Can be repeated, the comparisons are
+    /// `>`, `>=`, `<`, `<=`, `==` and `!=`.
+    #[arg(long, value_name = "RULE", requires = "plot")]
+    alert: Vec<AlertRule>,
+
+    /// Style the output lines matching a regex, written `REGEX=STYLE` like `ERROR=red` or
+    /// `WARN=yellow,bold`. The style lists a color, `bg:<color>` and modifiers among `bold`, `dim`,
+    /// `italic`, `underlined` and `reversed`. Can be repeated, the first matching rule applies.
+    #[arg(long, value_name = "REGEX=STYLE")]
+    highlight: Vec<HighlightRule>,
+
+    /// Mask the matches of this regex, like `ghp_\w+` or `password=\S+`, in the rendered outputs,
+    /// the snapshots and bundles, and in `watcher.log`. Can be repeated.
+    #[arg(long, value_name = "REGEX")]
+    redact: Vec<Regex>,
+
+    /// Prefix each output line with its line number. Toggled at runtime with `l`.
+    #[arg(short = 'l', long, default_value_t = false)]
+    line_numbers: bool,
+
+    /// Truncate the long lines instead of soft-wrapping them, for table-formatted outputs.
+    /// The lines are panned with `Left` / `Right`, and wrapping is toggled at runtime with `w`.
+    #[arg(long, default_value_t = false)]
+    no_wrap: bool,
+
+    /// Keep the view pinned to the bottom of the output as new iterations arrive, like `tail -f`.
+    /// Scrolling up unpins the view, and following is toggled at runtime with `F`.
+    #[arg(long, default_value_t = false)]
+    follow: bool,
+
+    /// Render the TUI without colors, keeping the bold and reversed emphasis.
+    /// Also enabled by a non-empty `NO_COLOR` environment variable.
+    #[arg(long, default_value_t = false)]
+    no_color: bool,
+
+    /// Title of the header, instead of the directory the command runs in.
+    /// In headless mode, it names the iterations instead of the command.
+    #[arg(long, conflicts_with = "no_title")]
+    title: Option<String>,
+
+    /// Leave out the header, the status bar and the titles of the panes, like `watch -t`,
+    /// for only the outputs to show, toggled at runtime with `z`. In headless mode, only the
+    /// outputs are printed.
+    #[arg(long, default_value_t = false)]
+    no_title: bool,
+
+    /// Render the outputs of the commands side by side (`vertical`) or one above the other
+    /// (`horizontal`) in the same frame, instead of in tabs. Needs several `-c` commands.
+    #[arg(long, value_enum)]
+    split: Option<SplitDirection>,
+
+    /// A/B mode of two `-c` commands, like a staging and a production endpoint: both run every
+    /// interval, and the diff view renders the changes from the output of the first to the one
+    /// of the second. The diff view is toggled with `d`, back to the outputs side by side.
+    #[arg(long, default_value_t = false)]
+    ab: bool,
+
+    /// Ignore the iterations which exit like the previous one with a byte-identical output:
+    /// they are neither rendered nor kept in the history, a counter of the unchanged runs is
+    /// shown instead. Keeps the history of rarely changing commands meaningful.
+    #[arg(long, default_value_t = false)]
+    only_changes: bool,
+
+    /// Accent colors of the TUI.
+    #[arg(long, value_enum, default_value_t = Theme::Lime)]
+    theme: Theme,
+
+    /// Locale used to render the numbers and the clock, like `de_DE` or `fr`.
+    /// Defaults to the locale of the environment, from `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    #[arg(long)]
+    locale: Option<Locale>,
+
+    /// Whether the clock counts the hours from 1 to 12 with AM / PM, or from 0 to 23.
+    #[arg(long, value_enum, default_value_t = HourCycle::H24)]
+    hour_cycle: HourCycle,
+
+    /// `strftime` format of the clock in the header and the headless output, like `%H:%M` or
+    /// `%a %I:%M:%S %p`, replacing the date and time of the day of the locale.
+    #[arg(long, value_name = "STRFTIME")]
+    time_format: Option<TimeFormat>,
+
+    /// IANA time zone the clock and the timestamps of the logs are displayed in, like
+    /// `America/New_York`, instead of the local one. The watched commands run in it as well.
+    #[arg(long, value_name = "IANA NAME")]
+    timezone: Option<Timezone>,
+
+    /// What to do while the terminal is unfocused: `keep` running as usual, `pause-render` to
+    /// stop redrawing, or `pause` to suspend the command execution as well.
+    #[arg(long, value_enum, default_value_t = UnfocusedMode::Keep)]
+    unfocused: UnfocusedMode,
+
+    /// Events of the watchers to drop once their queue is full, like while the terminal is
+    /// suspended: `drop-oldest` to catch up with the latest ones, or `drop-newest` to keep the
+    /// queued ones. The dropped events are counted in the status bar.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = DropPolicy::DropOldest)]
+    event_overflow: DropPolicy,
+
+    /// Only render and diff this subtree of the JSON outputs, selected with a jq-like path like
+    /// `.items[].status` or `.data.result[0]`, `.` being the whole document. The paths of
+    /// `--json-ignore` are relative to it.
+    #[arg(long, value_name = "PATH")]
+    json_path: Option<JsonPath>,
+
+    /// JSON paths left out of the structural comparison and diff display, like
+    /// `.metadata.resourceVersion` or `.items[].status.lastProbeTime`, for fields changing on every poll.
+    #[arg(long, value_name = "PATH", num_args = 1..)]
+    json_ignore: Vec<JsonPath>,
+
+    /// Terminate the watch once the output has been identical for N consecutive iterations.
+    /// Useful for waiting on convergence of builds, syncs or migrations.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    until_stable: Option<u64>,
+
+    /// Terminate the watch after N iterations, whichever of it and `--watch-duration` comes
+    /// first. Useful for bounded sampling runs, like `--no-tui --count 10`.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    count: Option<u64>,
+
+    /// Run the command a single time, keeping its output displayed in the TUI to be scrolled,
+    /// searched or copied until quitting with Ctrl+C.
+    #[arg(long, default_value_t = false, conflicts_with = "count")]
+    once: bool,
+
+    /// Fan-out mode: run the same command on each of these hosts through SSH,
+    /// rendering one column per host with the lines differing across hosts highlighted.
+    #[arg(long, value_delimiter = ',')]
+    hosts: Vec<String>,
+
+    /// Start on the summary table, reducing each watcher's output into one row with this extractor:
+    /// `exit-code`, `first-line` or `metric:<regex>`. Unhealthy watchers are listed first.
+    /// The table can be toggled at runtime with `t`, using the `exit-code` extractor by default.
+    #[arg(long, value_name = "EXTRACTOR")]
+    summary: Option<SummaryExtractor>,
 
     /// Flag to specify the presence of setup commands.
     /// We can query user for the setup commands if there are setup commands.
     #[arg(short = 's', long, default_value_t = false)]
     has_setup: bool,
+
+    /// Flag to specify the presence of teardown commands, run once in the shell when the watch
+    /// ends, like deleting the temporary resources of the setup commands.
+    /// We can query user for the teardown commands if there are teardown commands.
+    #[arg(long, default_value_t = false)]
+    has_teardown: bool,
+
+    /// Commands run in the same shell before the main commands of every iteration, unlike the
+    /// setup commands run once. Like busting a cache, their output is left out of the display.
+    #[arg(long, value_name = "CMD")]
+    before_each: Option<String>,
+
+    /// Commands run in the same shell after the main commands of every iteration, like cleaning
+    /// up the files they left. Their output is left out of the display.
+    #[arg(long, value_name = "CMD")]
+    after_each: Option<String>,
+
+    /// Run without the TUI, printing each iteration to stdout with a timestamp header.
+    /// Suitable for piping into other tools or running under CI.
+    #[arg(long, default_value_t = false)]
+    no_tui: bool,
+
+    /// Socket accepting control requests sent with `ctl`.
+    /// Defaults to `watch-rs.sock` in the temporary directory.
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// Append the raw output of every iteration to this file, each under a separator line with
+    /// its timestamp, iteration and exit code, to grep through after the watch.
+    #[arg(long, value_name = "PATH")]
+    output_log: Option<PathBuf>,
+
+    /// Run this shell command locally whenever the output differs from the previous iteration,
+    /// with the new output on its stdin and the iteration in the `WATCH_RS_ITERATION`,
+    /// `WATCH_RS_EXIT_CODE`, `WATCH_RS_STATUS`, `WATCH_RS_DURATION_MS`, `WATCH_RS_STARTED_AT`,
+    /// `WATCH_RS_FINISHED_AT`, `WATCH_RS_OUTPUT_BYTES`, `WATCH_RS_CHANGED`, `WATCH_RS_COMMAND`
+    /// and `WATCH_RS_HOST` variables. Like `curl` to a chat webhook. With the `json` compare mode
+    /// or format, the changed paths are in `WATCH_RS_CHANGED_PATHS`, one per line.
+    #[arg(long, value_name = "CMD")]
+    on_change: Option<String>,
+
+    /// Run this shell command locally whenever the command exits with a non-zero code or times
+    /// out, with its output on stdin and its exit code in `WATCH_RS_EXIT_CODE`, along with the
+    /// variables of `--on-change`. Cancelled runs are not failures.
+    #[arg(long, value_name = "CMD")]
+    on_failure: Option<String>,
+
+    /// Minimum delay between two runs of the `--on-failure` hook, for a command failing every
+    /// iteration not to fire it every iteration.
+    #[arg(long, value_name = "DURATION", default_value = "5m", value_parser = parse_duration)]
+    on_failure_throttle: Duration,
+
+    /// Write an HTML report of the session to this file when the watch ends, with the commands
+    /// and the durations, exit codes, outputs and diffs of the iterations. Written at any time with `R`.
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// Memory budget of the recorded outputs of each pane, browsed with the iteration picker
+    /// and written to the report. Past it, the outputs of the oldest iterations are evicted,
+    /// their durations and exit codes staying recorded. 0 keeps them all.
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_HISTORY_BYTES)]
+    max_history_bytes: usize,
+
+    /// Record the TUI session into this asciicast v2 file, to be replayed with `asciinema play`.
+    #[arg(long, value_name = "FILE", conflicts_with = "no_tui")]
+    record: Option<PathBuf>,
+
+    /// Directory the snapshots exported with `s` are written to, `S` prompts for another one.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    export_dir: PathBuf,
+
+    /// Outputs larger than this many megabytes are exported as gzip-compressed chunks,
+    /// indexed by a manifest, instead of a single plain text file.
+    #[arg(long, value_name = "MB", default_value_t = snapshot::DEFAULT_CHUNK_SIZE_MB,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    export_chunk_mb: u64,
+
+    /// Profile of the `.watchrc.toml` or of the user config to use, laid over their top level presets.
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Ignore the `.watchrc.toml` of the project.
+    #[arg(long, default_value_t = false)]
+    no_watchrc: bool,
+
+    /// Ignore the user config, `~/.config/watch-rs/config.toml`.
+    #[arg(long, default_value_t = false)]
+    no_config: bool,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Run the watch described by a watchfile, fetched from a local path or an HTTP(S) URL.
+    /// Remote watchfiles are shown for confirmation before anything is executed.
+    Run {
+        /// Path or HTTP(S) URL of the watchfile.
+        source: String,
+
+        /// Skip the confirmation prompt for remote watchfiles.
+        #[arg(short = 'y', long, default_value_t = false)]
+        yes: bool,
+    },
+    /// Print a snapshot exported with `s`, decompressing its chunks one at a time.
+    Show {
+        /// Plain text snapshot file, or directory of a chunked snapshot.
+        snapshot: PathBuf,
+    },
+    /// List the profiles of the `.watchrc.toml` and of the user config, selected with `--profile`.
+    Profiles,
+    /// Convert an output log written with `--output-log` into JSON or CSV, with the iteration,
+    /// timestamp, duration, exit code and output of each iteration, printed to stdout.
+    Export {
+        /// Output log of a watch.
+        history: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+    },
+    /// Send a control request to a running watch, through its control socket.
+    Ctl {
+        /// Control socket of the watch, defaults to `watch-rs.sock` in the temporary directory.
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        request: CtlRequest,
+    },
+    /// Have a running watch package its config, commands, log excerpt and recent iterations
+    /// into a single gzip-compressed JSON file, to attach to a bug report.
+    DebugBundle {
+        /// Control socket of the watch, defaults to `watch-rs.sock` in the temporary directory.
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        /// Path of the bundle, defaults to a timestamped file in the working directory.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Mask the environment variable values and the credentials found in the texts.
+        #[arg(long, default_value_t = false)]
+        redact: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CtlRequest {
+    /// Label the current iteration of the watch, like `ctl mark "deployed v2"`.
+    Mark { label: String },
 }
 
 fn init() -> OpenResult<()> {
-    let stdout = FileAppender::builder().build(WATCHER_LOGS_DIR.path().join("watcher.log"))?;
+    let stdout = FileAppender::builder()
+        .encoder(Box::new(RedactingEncoder::default()))
+        .build(WATCHER_LOGS_DIR.path().join("watcher.log"))?;
 
     let config = Config::builder()
         .appender(Appender::builder().build("stdout", Box::new(stdout)))
@@ -62,14 +545,18 @@ fn init() -> OpenResult<()> {
     Ok(())
 }
 
-fn query_and_fetch_file_input(file_title: &str) -> OpenResult<String> {
+/// Query the commands in the user's editor. Without any editor available, the commands
+/// are left empty to be typed in the `QueryTui` instead, when `tui_fallback` is set.
+fn query_and_fetch_file_input(file_title: &str, tui_fallback: bool) -> OpenResult<String> {
+    if tui_fallback && find_editor().is_none() {
+        warn!("No editor found, falling back on the TUI editor");
+        return Ok(String::new());
+    }
+
     let filepath = WATCHER_LOGS_DIR.path().join(file_title);
     trace!("Created a temporary file : {filepath:?}");
 
-    std::process::Command::new("vim")
-        .args([&filepath])
-        .spawn()?
-        .wait()?;
+    edit_file(&filepath)?;
 
     let mut cmds = String::new();
     std::fs::File::open(&filepath)?.read_to_string(&mut cmds)?;
@@ -79,61 +566,600 @@ fn query_and_fetch_file_input(file_title: &str) -> OpenResult<String> {
 fn run_app_in_terminal_instance(
     app: impl FnOnce(DefaultTerminal) -> OpenResult<()>,
 ) -> OpenResult<()> {
-    // Setup terminal for TUI start
+    // Setup terminal for TUI start, reporting the focus changes and the mouse events
     let terminal = ratatui::init();
+    execute!(io::stdout(), EnableFocusChange, EnableMouseCapture)?;
+    handle_termination_signals()?;
 
     let result = app(terminal);
 
-    // Restore terminal after finish
-    ratatui::restore();
+    // Restore terminal after finish, even when the window closed with it
+    restore_terminal();
 
     result
 }
 
+fn restore_terminal() {
+    execute!(io::stdout(), DisableFocusChange, DisableMouseCapture).ok();
+    ratatui::restore();
+}
+
+/// Shut the TUI down on SIGTERM or SIGHUP the way it does on Ctrl+C. Watchers still running
+/// after the grace period get torn down, with the terminal restored right away.
+fn handle_termination_signals() -> OpenResult<()> {
+    let mut signals = Signals::new([SIGTERM, SIGHUP])?;
+    thread::spawn(move || {
+        let Some(signal) = signals.forever().next() else {
+            return;
+        };
+        info!("Received signal {signal}, shutting down the watch");
+        tui::TERMINATION_REQUESTED.store(true, Ordering::Release);
+
+        thread::sleep(SHUTDOWN_GRACE_PERIOD);
+        warn!("Watchers still running after the grace period, tearing them down");
+        shell::kill_all_shells();
+        restore_terminal();
+        std::process::exit(128 + signal);
+    });
+    Ok(())
+}
+
+fn send_control_request(socket: Option<PathBuf>, request: CtlRequest) -> OpenResult<()> {
+    let socket_path = socket.unwrap_or_else(control::default_socket_path);
+    let reply = match request {
+        CtlRequest::Mark { label } => control::send(&socket_path, &ControlRequest::Mark(label))?,
+    };
+    println!("{reply}");
+    Ok(())
+}
+
+fn request_debug_bundle(
+    socket: Option<PathBuf>,
+    output: Option<PathBuf>,
+    redact: bool,
+) -> OpenResult<()> {
+    let socket_path = socket.unwrap_or_else(control::default_socket_path);
+    // The watch may run in another directory, it gets the absolute path
+    let path = std::path::absolute(output.unwrap_or_else(|| bundle::default_bundle_name().into()))?;
+
+    let reply = control::send(
+        &socket_path,
+        &ControlRequest::DebugBundle {
+            path: path.clone(),
+            redact,
+        },
+    )?;
+    match reply.as_str() {
+        "ok" => println!("Debug bundle requested, written to {}", path.display()),
+        _ => println!("{reply}"),
+    }
+    Ok(())
+}
+
+fn show_snapshot(path: &Path) -> OpenResult<()> {
+    let snapshot = SnapshotReader::open(path)?;
+    let mut stdout = io::stdout().lock();
+    for index in 0..snapshot.chunk_count() {
+        stdout.write_all(snapshot.load_chunk(index)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn list_profiles(presets: Option<&WatchRc>) {
+    let profiles = presets.map(|presets| &presets.profiles);
+    if profiles.is_none_or(|profiles| profiles.is_empty()) {
+        println!("No profiles, they are defined under [profiles.<name>] in a .watchrc.toml or the user config");
+    }
+    for (name, profile) in profiles.into_iter().flatten() {
+        let mut details = Vec::new();
+        if let Some(command) = &profile.command {
+            details.push(command.trim().replace('\n', "; "));
+        }
+        if let Some(interval) = profile.interval {
+            details.push(format!("every {}", humantime::format_duration(interval)));
+        }
+        println!("{name}\t{}", details.join(" | "));
+    }
+}
+
+/// Lay the `watchrc` presets under the flags given on the command line.
+/// Returns the setup commands of the presets.
+fn apply_watchrc(args: &mut Args, matches: &ArgMatches, watchrc: WatchRc) -> Option<String> {
+    let is_flag_given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if args.command.is_empty() {
+        args.command.extend(watchrc.command);
+    }
+    args.timeout = args.timeout.or(watchrc.timeout);
+    args.interval_on_error = args.interval_on_error.or(watchrc.interval_on_error);
+    args.watch_duration = args.watch_duration.or(watchrc.watch_duration);
+    args.filter = args.filter.take().or(watchrc.filter);
+    args.time_format = args.time_format.take().or(watchrc.time_format);
+    args.timezone = args.timezone.take().or(watchrc.timezone);
+    if let (Some(interval), false) = (watchrc.interval, is_flag_given("interval")) {
+        args.interval = interval;
+    }
+    if let (Some(compare_mode), false) = (watchrc.compare_mode, is_flag_given("compare_mode")) {
+        args.compare_mode = compare_mode;
+    }
+    if let (Some(format), false) = (watchrc.format, is_flag_given("format")) {
+        args.format = format;
+    }
+    if let (Some(theme), false) = (watchrc.theme, is_flag_given("theme")) {
+        args.theme = theme;
+    }
+    if let (Some(hour_cycle), false) = (watchrc.hour_cycle, is_flag_given("hour_cycle")) {
+        args.hour_cycle = hour_cycle;
+    }
+    if let (Some(shell), false) = (watchrc.shell, is_flag_given("shell")) {
+        args.shell = shell;
+    }
+    if args.json_ignore.is_empty() {
+        args.json_ignore = watchrc.json_ignore;
+    }
+    if args.highlight.is_empty() {
+        args.highlight = watchrc.highlight;
+    }
+
+    // Variables given on the command line are exported last, overriding the presets
+    let mut envs = watchrc.env.into_iter().collect::<Vec<_>>();
+    envs.append(&mut args.envs);
+    args.envs = envs;
+
+    watchrc.setup.filter(|_| !args.has_setup)
+}
+
 pub fn run_tui_app() -> OpenResult<()> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    redact::set_redactions(args.redact.clone());
+
+    let action = match args.action.take() {
+        Some(Action::Ctl { socket, request }) => return send_control_request(socket, request),
+        Some(Action::Show { snapshot }) => return show_snapshot(&snapshot),
+        Some(Action::Export { history, format }) => {
+            let iterations = output_log::read_output_log(&history)?;
+            return output_log::export_iterations(&mut io::stdout().lock(), &iterations, format);
+        }
+        Some(Action::DebugBundle {
+            socket,
+            output,
+            redact,
+        }) => return request_debug_bundle(socket, output, redact),
+        action => action,
+    };
+
+    // Presets of the project, from the closest `.watchrc.toml` up the directory tree,
+    // laid over the ones of the user config
+    let watchrc_dir = match (&args.cwd, args.hosts.is_empty()) {
+        (Some(cwd), true) => cwd.clone(),
+        _ => std::env::current_dir()?,
+    };
+    let user_config_path = WatchRc::user_config().filter(|_| !args.no_config);
+    let watchrc_path = WatchRc::find(&watchrc_dir).filter(|_| !args.no_watchrc);
+    let mut presets: Option<WatchRc> = None;
+    for path in [&user_config_path, &watchrc_path].into_iter().flatten() {
+        let loaded = WatchRc::load(path)?;
+        presets = Some(match presets {
+            Some(base) => loaded.laid_over(base),
+            None => loaded,
+        });
+    }
+
+    if let Some(Action::Profiles) = action {
+        list_profiles(presets.as_ref());
+        return Ok(());
+    }
+
+    let mut preset_setup_commands = None;
+    let mut preset_teardown_commands = None;
+    let mut keybindings = BTreeMap::new();
+    match presets {
+        Some(mut presets) => {
+            if let Some(profile) = &args.profile {
+                presets = presets.with_profile(profile)?;
+            }
+            keybindings = std::mem::take(&mut presets.keybindings);
+            preset_setup_commands = apply_watchrc(&mut args, &matches, presets);
+        }
+        None if args.profile.is_some() => {
+            return Err(format!(
+                "--profile needs a {} file or a user config",
+                watchrc::WATCHRC_FILE_NAME
+            )
+            .into());
+        }
+        None => {}
+    }
+    // Before the shells and the threads get spawned
+    if let Some(timezone) = &args.timezone {
+        timezone.set_local();
+    }
+
+    // Load the watch from a watchfile, its fields take precedence over the flags
+    if let Some(Action::Run { source, yes }) = action {
+        let contents = WatchFile::fetch(&source)?;
+        if WatchFile::is_remote(&source) && !yes && !confirm_watchfile(&source, &contents)? {
+            return Ok(());
+        }
 
-    // // Fetch and initialize the setup commands if Watcher `has_setup`
-    // let mut optional_setup_cmds: Option<String> = None;
-    // if args.has_setup {
-    //     let setup_cmds = query_and_fetch_file_input("setup_commands.bash")?;
-    //     optional_setup_cmds = Some(setup_cmds);
-    // }
+        let watchfile = WatchFile::parse(&contents)?;
+        args.command = vec![watchfile.command];
+        args.interval = watchfile.interval.unwrap_or(args.interval);
+        args.timeout = watchfile.timeout.or(args.timeout);
+        args.watch_duration = watchfile.watch_duration.or(args.watch_duration);
+        preset_setup_commands = watchfile.setup;
+        preset_teardown_commands = watchfile.teardown;
+    }
+
+    // Fetch and initialize the setup commands if Watcher `has_setup`
+    let setup_commands = match preset_setup_commands {
+        Some(setup_commands) => setup_commands,
+        None if args.has_setup => query_and_fetch_file_input("setup_commands.bash", !args.no_tui)?,
+        None => String::new(),
+    };
+    let teardown_commands = match preset_teardown_commands {
+        Some(teardown_commands) => teardown_commands,
+        None if args.has_teardown => {
+            query_and_fetch_file_input("teardown_commands.bash", !args.no_tui)?
+        }
+        None => String::new(),
+    };
 
     // Fetch or query the Watcher `command`
-    let command: String = args
-        .command
-        .unwrap_or_else(|| query_and_fetch_file_input("run_commands.bash").unwrap());
-
-    let command_timeout = args.timeout.map_or(DEFAULT_COMMAND_TIMEOUT, |t| t * 1000);
-
-    let interval = Duration::from_millis((args.interval * 1000.0).floor() as u64);
-    let watch_duration = args.watch_duration.map(|d| Duration::from_millis(d * 1000));
-
-    let watcher = Watcher::new(command_timeout)?;
-
-    run_app_in_terminal_instance(move |mut terminal| {
-        if let Some(query_state) = QueryTui::new(Some(command.clone())).run_app(&mut terminal)? {
-            let (event_sender, event_receiver) = unbounded();
-            let should_close_watcher = Arc::new(AtomicBool::new(false));
-
-            // Create and start the watcher thread, with the event sender channel
-            tui::run_watcher_thread(
-                watcher,
-                query_state,
-                interval,
-                watch_duration,
-                event_sender,
-                Arc::clone(&should_close_watcher),
-            );
-
-            // Create the TUI app and run it, with the event receiver channel
-            let mut watcher_tui =
-                tui::watcher::WatcherTui::new(event_receiver, Arc::clone(&should_close_watcher));
-            return watcher_tui.run_app(&mut terminal);
+    let commands = match args.command.is_empty() {
+        true => vec![query_and_fetch_file_input(
+            "run_commands.bash",
+            !args.no_tui,
+        )?],
+        false => std::mem::take(&mut args.command),
+    };
+    if args.split.is_some() && commands.len() < 2 {
+        return Err("--split needs at least two commands, given with -c".into());
+    }
+    if args.ab && commands.len() != 2 {
+        return Err("--ab needs exactly two commands, given with -c".into());
+    }
+    // The commands of the A/B mode are split, their diff is rendered instead of their outputs
+    let split = match args.ab {
+        true => Some(args.split.unwrap_or_default()),
+        false => args.split,
+    };
+
+    let command_timeout = args.timeout.unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+
+    // Swapped from the settings of the TUI, for the watcher threads as well
+    let comparator = SharedComparator::new(args.compare_mode.comparator(&args.json_ignore));
+    let mut schedule = WatcherSchedule {
+        interval: args.interval,
+        cron: args.schedule.clone(),
+        interval_on_error: args.interval_on_error,
+        watch_duration: args.watch_duration,
+        precise: args.precise,
+        until_stable: args.until_stable.map(|n| n as usize),
+        max_iterations: match args.once {
+            true => Some(1),
+            false => args.count.map(|n| n as usize),
+        },
+        backoff: args.backoff.map(|multiplier| Backoff {
+            multiplier,
+            max_interval: args.backoff_max,
+        }),
+        exit_on_change: args.chgexit,
+        comparator: Arc::new(comparator.clone()),
+        paused: Arc::new(AtomicBool::new(false)),
+        refresh: Arc::new(AtomicBool::new(false)),
+        skip_next: Arc::new(AtomicBool::new(false)),
+        live_interval: Arc::new(Mutex::new(args.interval)),
+        triggers: Vec::new(),
+        triggers_only: args.watch_path_only || args.watch_git,
+    };
+    if !args.watch_path.is_empty() || args.watch_git {
+        let file_trigger = FileTrigger::new(
+            std::mem::take(&mut args.watch_path),
+            args.cwd.clone().unwrap_or_else(|| PathBuf::from(".")),
+            args.watch_debounce,
+        );
+        let file_trigger = match args.watch_git {
+            true => file_trigger.with_git()?,
+            false => file_trigger,
+        };
+        let file_trigger: Box<dyn Trigger> = match args.watch_poll {
+            true => Box::new(file_trigger),
+            false => Box::new(file_trigger.with_file_events()),
+        };
+        schedule.triggers.push(file_trigger);
+    }
+
+    // A local watcher, or one remote watcher per host in fan-out mode
+    let command_timeout = command_timeout.as_millis() as u64;
+    let cwd = args.cwd.clone();
+    let hosts = args.hosts.clone();
+    let encoding = args.encoding;
+    let max_output_bytes =
+        Some(args.max_output_bytes).filter(|&max_output_bytes| max_output_bytes > 0);
+    let shell = args.shell.clone();
+    let spawn_watcher = move |host: Option<&str>| -> OpenResult<Watcher> {
+        let watcher = match host {
+            None => Watcher::in_shell(&shell, command_timeout, cwd.as_deref())?,
+            Some(host) => Watcher::over_ssh(host, command_timeout, cwd.as_deref())?,
+        };
+        Ok(watcher
+            .with_encoding(encoding)
+            .with_max_output_bytes(max_output_bytes))
+    };
+    let create_watchers = {
+        let spawn_watcher = spawn_watcher.clone();
+        move || -> OpenResult<TitledWatchers> {
+            match hosts.is_empty() {
+                true => Ok(vec![(None, spawn_watcher(None)?)]),
+                false => hosts
+                    .iter()
+                    .map(|host| Ok((Some(host.clone()), spawn_watcher(Some(host))?)))
+                    .collect(),
+            }
         }
-        Ok(())
-    })
+    };
+    // The commands share their environment, setup and transform
+    let query_states = commands
+        .into_iter()
+        .map(|command| QueryState {
+            env_vars: args.envs.clone(),
+            setup_commands: setup_commands.clone(),
+            main_commands: command,
+            teardown_commands: teardown_commands.clone(),
+            transform: String::new(),
+            before_each: args.before_each.clone().unwrap_or_default(),
+            after_each: args.after_each.clone().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+    let watchers = query_states
+        .iter()
+        .map(|_| create_watchers())
+        .collect::<OpenResult<Vec<_>>>()?;
+
+    // Directory the command runs in, remote shells start in the home directory of the user
+    let effective_cwd = match (&args.cwd, args.hosts.is_empty()) {
+        (Some(cwd), true) => std::fs::canonicalize(cwd)?.display().to_string(),
+        (Some(cwd), false) => cwd.display().to_string(),
+        (None, true) => std::env::current_dir()?.display().to_string(),
+        (None, false) => "~".to_string(),
+    };
+
+    // Marks and other control requests sent with `ctl`, while the watch runs
+    let socket_path = args
+        .control_socket
+        .clone()
+        .unwrap_or_else(control::default_socket_path);
+    let control_receiver = match control::serve(&socket_path) {
+        Ok(control_receiver) => Some(control_receiver),
+        Err(err) => {
+            warn!("Control socket unavailable : {err}");
+            None
+        }
+    };
+
+    let is_control_served = control_receiver.is_some();
+    let pipeline = OutputPipeline {
+        json_path: args.json_path,
+        pretty_json: args.format.pretty_prints_json(),
+    };
+    let debug_session = DebugSession {
+        argv: std::env::args().collect(),
+        watchrc_path,
+        query_state: query_states[0].clone(),
+    };
+    let output_log = args
+        .output_log
+        .as_deref()
+        .map(OutputLog::open)
+        .transpose()?;
+    let hooks = Hooks::new(args.on_change.take())
+        .with_on_failure(args.on_failure.take(), args.on_failure_throttle)
+        .with_json_changes(
+            (args.compare_mode == CompareMode::Json || args.format == OutputFormat::Json)
+                .then(|| args.json_ignore.clone()),
+        );
+    let locale = args
+        .locale
+        .take()
+        .unwrap_or_else(Locale::from_env)
+        .with_clock(args.hour_cycle, args.time_format.take());
+    let max_history_bytes =
+        Some(args.max_history_bytes).filter(|&max_history_bytes| max_history_bytes > 0);
+    let monochrome =
+        args.no_color || std::env::var_os("NO_COLOR").is_some_and(|no_color| !no_color.is_empty());
+    let result = if args.no_tui {
+        headless::run_headless_app(
+            query_states.into_iter().zip(watchers).collect(),
+            schedule,
+            pipeline,
+            HeadlessOptions {
+                format: args.format,
+                json_ignore: args.json_ignore,
+                filter: args.filter,
+                control_receiver,
+                locale,
+                debug_session,
+                output_log,
+                hooks,
+                report_path: args.report,
+                max_history_bytes,
+                cron: args.schedule.as_ref().map(ToString::to_string),
+                title: args.title,
+                no_title: args.no_title,
+                event_overflow: args.event_overflow,
+            },
+        )
+    } else {
+        run_app_in_terminal_instance(move |mut terminal| {
+            let mut query_states = query_states;
+            let mut watchers = watchers;
+            let mut control_receiver = control_receiver;
+            let mut watcher_tui: Option<WatcherTui> = None;
+            let mut edited_tab = 0;
+
+            // Each edit of the commands restarts the watch, with fresh shells
+            loop {
+                let Some(edited_state) = QueryTui::new(query_states[edited_tab].clone())
+                    .with_monochrome(monochrome)
+                    .run_app(&mut terminal)?
+                else {
+                    return Ok(());
+                };
+                // Only the main commands differ across the tabs
+                for query_state in &mut query_states {
+                    query_state.env_vars = edited_state.env_vars.clone();
+                    query_state.setup_commands = edited_state.setup_commands.clone();
+                    query_state.teardown_commands = edited_state.teardown_commands.clone();
+                    query_state.transform = edited_state.transform.clone();
+                }
+                query_states[edited_tab] = edited_state;
+
+                let should_close_watcher = Arc::new(AtomicBool::new(false));
+
+                let mut tabs = Vec::new();
+                for (query_state, tab_watchers) in query_states.iter().zip(watchers) {
+                    let mut panes = Vec::new();
+                    for (title, watcher) in tab_watchers {
+                        let (event_sender, event_receiver) =
+                            tui::event_channel(args.event_overflow);
+                        let dropped_events = event_sender.dropped_events();
+                        let cancel_handle = watcher.cancel_handle();
+                        // Restarted shells are spawned on the host of the pane
+                        let restart_requested = Arc::new(AtomicBool::new(false));
+                        let shell_restart = {
+                            let spawn_watcher = spawn_watcher.clone();
+                            let host = title.clone();
+                            ShellRestart {
+                                spawn: Box::new(move || spawn_watcher(host.as_deref())),
+                                requested: Arc::clone(&restart_requested),
+                            }
+                        };
+                        // Split panes are named after their command
+                        let title = match (split, title) {
+                            (None, title) => title,
+                            (Some(_), title) => {
+                                let command =
+                                    summarize_command(&query_state.main_commands, PANE_TITLE_WIDTH);
+                                Some(match title {
+                                    Some(host) => format!("{host} │ {command}"),
+                                    None => command,
+                                })
+                            }
+                        };
+
+                        // Create and start the watcher thread, with the event sender channel
+                        tui::run_watcher_thread(
+                            watcher,
+                            query_state.clone(),
+                            schedule.clone(),
+                            pipeline.clone(),
+                            output_log
+                                .as_ref()
+                                .map(|output_log| output_log.for_pane(title.clone())),
+                            hooks.for_pane(title.clone()),
+                            event_sender,
+                            Arc::clone(&should_close_watcher),
+                            Some(shell_restart),
+                            true,
+                        );
+                        panes.push(
+                            WatcherPane::new(title, event_receiver)
+                                .with_dropped_events(dropped_events)
+                                .with_history_budget(max_history_bytes)
+                                .with_cancel_handle(cancel_handle)
+                                .with_shell_restart(restart_requested),
+                        );
+                    }
+                    tabs.push(WatchTab {
+                        query_state: query_state.clone(),
+                        panes,
+                    });
+                }
+                // Split commands share a single tab, the one of the first command
+                if split.is_some() {
+                    let panes = tabs
+                        .iter_mut()
+                        .flat_map(|tab| std::mem::take(&mut tab.panes))
+                        .collect();
+                    tabs.truncate(1);
+                    tabs[0].panes = panes;
+                }
+
+                // Create the TUI app and run it, with the event receiver channels
+                let watcher_tui = match watcher_tui.as_mut() {
+                    Some(watcher_tui) => {
+                        watcher_tui.restart(tabs, Arc::clone(&should_close_watcher));
+                        watcher_tui
+                    }
+                    None => watcher_tui.insert(WatcherTui::new(
+                        tabs,
+                        Arc::clone(&should_close_watcher),
+                        WatcherTuiOptions {
+                            summary_extractor: args.summary.clone(),
+                            compare_mode: args.compare_mode,
+                            comparator: comparator.clone(),
+                            format: args.format,
+                            json_ignore: args.json_ignore.clone(),
+                            diff_view: args.diff || args.ab,
+                            diff_context: args.diff_context,
+                            diff_granularity: args.diff_granularity,
+                            unfocused_mode: args.unfocused,
+                            filter: args.filter.clone(),
+                            plot: args.plot.clone(),
+                            alerts: args.alert.clone(),
+                            highlights: args.highlight.clone(),
+                            line_numbers: args.line_numbers,
+                            wrap: !args.no_wrap,
+                            follow: args.follow,
+                            theme: args.theme,
+                            locale: locale.clone(),
+                            export_dir: args.export_dir.clone(),
+                            export_chunk_size: args.export_chunk_mb as usize * 1024 * 1024,
+                            paused: Arc::clone(&schedule.paused),
+                            refresh: Arc::clone(&schedule.refresh),
+                            skip_next: Arc::clone(&schedule.skip_next),
+                            live_interval: Arc::clone(&schedule.live_interval),
+                            cron: args.schedule.as_ref().map(ToString::to_string),
+                            keep_open: args.once,
+                            cwd: effective_cwd.clone(),
+                            title: args.title.clone(),
+                            no_title: args.no_title,
+                            split: split.unwrap_or_default(),
+                            ab_diff: args.ab,
+                            only_changes: args.only_changes,
+                            debug_session: debug_session.clone(),
+                            report_path: args.report.clone(),
+                            keybindings: keybindings.clone(),
+                            monochrome,
+                            recorder: args.record.as_deref().map(Recorder::create).transpose()?,
+                            control_receiver: control_receiver.take(),
+                        },
+                    )),
+                };
+
+                match watcher_tui.run_app(&mut terminal)? {
+                    WatcherTuiExit::Ended => return Ok(()),
+                    WatcherTuiExit::EditQuery => {
+                        should_close_watcher.store(true, Ordering::Release);
+                        edited_tab = watcher_tui.active_tab();
+                        watchers = query_states
+                            .iter()
+                            .map(|_| create_watchers())
+                            .collect::<OpenResult<Vec<_>>>()?;
+                    }
+                }
+            }
+        })
+    };
+
+    // Watcher threads still busy on a command would otherwise leave their shells behind
+    shell::kill_all_shells();
+    if is_control_served {
+        std::fs::remove_file(&socket_path).ok();
+    }
+    result
 }
 
 fn main() -> OpenResult<()> {
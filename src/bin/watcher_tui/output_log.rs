@@ -0,0 +1,185 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use log::warn;
+use serde::Serialize;
+use watch_rs::{models::watcher::ExecStatus, utils::OpenResult};
+
+use crate::tui::WatcherIterationOutput;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// File the raw output of every iteration is appended to with `--output-log`, each under a
+/// separator line, to be grepped after the watch or converted with `export`. Shared by the watcher threads of all the panes.
+#[derive(Clone)]
+pub struct OutputLog {
+    file: Arc<Mutex<File>>,
+    /// Host of the pane in fan-out mode, written in the separators.
+    title: Option<String>,
+}
+
+impl OutputLog {
+    /// Open the log at `path` for appending, keeping the iterations of the previous watches.
+    pub fn open(path: &Path) -> OpenResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            title: None,
+        })
+    }
+
+    /// The same log, with the separators naming the pane.
+    pub fn for_pane(&self, title: Option<String>) -> Self {
+        Self {
+            file: Arc::clone(&self.file),
+            title,
+        }
+    }
+
+    /// Append the `output` of an iteration, failures are only logged not to stop the watch.
+    pub fn append(&self, iteration: &WatcherIterationOutput) {
+        let taken_at = iteration.finished_at.format(TIMESTAMP_FORMAT);
+        let mut separator = format!("==> {taken_at} | Itr: {}", iteration.iteration);
+        if let Some(title) = &self.title {
+            separator.push_str(&format!(" | host: {title}"));
+        }
+        if let Some(exit_code) = iteration.exit_code {
+            separator.push_str(&format!(" | exit: {exit_code}"));
+        }
+        if iteration.status != ExecStatus::Completed {
+            separator.push_str(&format!(" | status: {}", iteration.status));
+        }
+        if let Some(truncated_at) = iteration.truncated_at {
+            separator.push_str(&format!(" | truncated: {truncated_at} bytes"));
+        }
+        separator.push_str(&format!(
+            " | took: {}ms",
+            iteration.run_duration.as_millis()
+        ));
+
+        let mut file = self.file.lock().unwrap();
+        let mut entry = format!("{separator} <==\n{}", iteration.output);
+        if !entry.ends_with('\n') {
+            entry.push('\n');
+        }
+        if let Err(err) = file.write_all(entry.as_bytes()) {
+            warn!(
+                "Failed to append iteration {} to the output log : {err}",
+                iteration.iteration
+            );
+        }
+    }
+}
+
+/// Iteration read back from an output log, for its `export`.
+#[derive(Serialize)]
+pub struct LoggedIteration {
+    pub iteration: usize,
+    pub timestamp: String,
+    /// Host of the pane in fan-out mode.
+    pub host: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// Set when the command did not complete, like `TIMED OUT after 30s, partial output`.
+    pub status: Option<String>,
+    pub output: String,
+}
+
+/// Parse a separator line of the log, without the output of the iteration.
+fn parse_separator(line: &str) -> Option<LoggedIteration> {
+    let fields = line.strip_prefix("==> ")?.strip_suffix(" <==")?;
+    let mut fields = fields.split(" | ");
+    let timestamp = fields.next()?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+
+    let mut iteration = LoggedIteration {
+        iteration: fields.next()?.strip_prefix("Itr: ")?.parse().ok()?,
+        timestamp: timestamp.to_string(),
+        host: None,
+        duration_ms: None,
+        exit_code: None,
+        status: None,
+        output: String::new(),
+    };
+    for field in fields {
+        match field.split_once(": ")? {
+            ("host", host) => iteration.host = Some(host.to_string()),
+            ("exit", exit_code) => iteration.exit_code = exit_code.parse().ok(),
+            ("status", status) => iteration.status = Some(status.to_string()),
+            ("took", took) => iteration.duration_ms = took.strip_suffix("ms")?.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(iteration)
+}
+
+/// Read the iterations of the output log at `path`, in the order they got appended.
+pub fn read_output_log(path: &Path) -> OpenResult<Vec<LoggedIteration>> {
+    let mut iterations: Vec<LoggedIteration> = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        match (parse_separator(&line), iterations.last_mut()) {
+            (Some(iteration), _) => iterations.push(iteration),
+            (None, Some(iteration)) => {
+                iteration.output.push_str(&line);
+                iteration.output.push('\n');
+            }
+            (None, None) => return Err(format!("{} is not an output log", path.display()).into()),
+        }
+    }
+    Ok(iterations)
+}
+
+/// Machine-readable formats of the `export` of an output log.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// An array of objects, one per iteration.
+    Json,
+    /// A header line, then one row per iteration.
+    Csv,
+}
+
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Write the `iterations` to `out` in the `format`.
+pub fn export_iterations(
+    out: &mut impl Write,
+    iterations: &[LoggedIteration],
+    format: ExportFormat,
+) -> OpenResult<()> {
+    match format {
+        ExportFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(iterations)?)?,
+        ExportFormat::Csv => {
+            writeln!(
+                out,
+                "iteration,timestamp,host,duration_ms,exit_code,status,output"
+            )?;
+            for iteration in iterations {
+                let optional = |value: Option<String>| value.unwrap_or_default();
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    iteration.iteration,
+                    iteration.timestamp,
+                    csv_field(iteration.host.as_deref().unwrap_or_default()),
+                    optional(iteration.duration_ms.map(|ms| ms.to_string())),
+                    optional(iteration.exit_code.map(|code| code.to_string())),
+                    csv_field(iteration.status.as_deref().unwrap_or_default()),
+                    csv_field(&iteration.output),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
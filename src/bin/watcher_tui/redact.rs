@@ -0,0 +1,82 @@
+use std::{borrow::Cow, io};
+
+use log::Record;
+use log4rs::encode::{pattern::PatternEncoder, Encode};
+use once_cell::sync::OnceCell;
+use regex::{bytes, Regex};
+
+/// Replacement of the secrets matched by the `--redact` rules.
+pub const MASK: &str = "••••••";
+
+/// Rules of `--redact`, set once the arguments are parsed. Kept process-wide for the log
+/// encoder, which is installed before the arguments are known.
+static REDACTIONS: OnceCell<Vec<Regex>> = OnceCell::new();
+/// Same rules, matching the raw bytes of the outputs.
+static BYTE_REDACTIONS: OnceCell<Vec<bytes::Regex>> = OnceCell::new();
+
+pub fn set_redactions(rules: Vec<Regex>) {
+    let byte_rules = rules
+        .iter()
+        .filter_map(|rule| bytes::Regex::new(rule.as_str()).ok())
+        .collect();
+    BYTE_REDACTIONS.set(byte_rules).ok();
+    REDACTIONS.set(rules).ok();
+}
+
+/// Mask the matches of the `--redact` rules in the `text`.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let rules = REDACTIONS.get().map(Vec::as_slice).unwrap_or_default();
+    rules.iter().fold(Cow::Borrowed(text), |text, rule| {
+        match rule.replace_all(&text, MASK) {
+            Cow::Borrowed(_) => text,
+            Cow::Owned(redacted) => Cow::Owned(redacted),
+        }
+    })
+}
+
+/// Owned version of [`redact`], not reallocating the `text` without matches.
+pub fn redact_owned(text: String) -> String {
+    match redact(&text) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(redacted) => redacted,
+    }
+}
+
+/// Mask the matches of the `--redact` rules in the raw `bytes` of an output.
+pub fn redact_bytes(bytes: Vec<u8>) -> Vec<u8> {
+    let rules = BYTE_REDACTIONS.get().map(Vec::as_slice).unwrap_or_default();
+    rules.iter().fold(bytes, |bytes, rule| {
+        match rule.replace_all(&bytes, MASK.as_bytes()) {
+            Cow::Borrowed(_) => bytes,
+            Cow::Owned(redacted) => redacted,
+        }
+    })
+}
+
+struct Buffer(Vec<u8>);
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl log4rs::encode::Write for Buffer {}
+
+/// Log encoder masking the `--redact` matches of the records, so that no secret reaches `watcher.log`.
+#[derive(Debug, Default)]
+pub struct RedactingEncoder(PatternEncoder);
+
+impl Encode for RedactingEncoder {
+    fn encode(&self, w: &mut dyn log4rs::encode::Write, record: &Record) -> anyhow::Result<()> {
+        let mut buffer = Buffer(Vec::new());
+        self.0.encode(&mut buffer, record)?;
+        let line = String::from_utf8_lossy(&buffer.0);
+        w.write_all(redact(&line).as_bytes())?;
+        Ok(())
+    }
+}
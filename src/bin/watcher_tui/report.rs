@@ -0,0 +1,370 @@
+use std::{collections::VecDeque, fmt::Write, fs, path::Path, time::Duration};
+
+use chrono::{DateTime, Local};
+use watch_rs::{
+    models::{
+        line_diff::{line_diff, DiffLine},
+        watcher::ExecStatus,
+    },
+    utils::OpenResult,
+};
+
+use crate::{
+    redact::redact,
+    tui::{query::QueryState, WatcherIterationOutput},
+};
+
+/// Iterations of each pane kept for the session reports, the oldest ones are dropped past it.
+pub const REPORTED_ITERATIONS: usize = 1000;
+
+const REPORT_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #1e293b; }
+pre { background: #f1f5f9; padding: 0.75em; overflow-x: auto; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #cbd5e1; padding: 0.25em 0.75em; text-align: left; }
+th { background: #e2e8f0; }
+tr.failed td { background: #fee2e2; }
+.added { background: #dcfce7; }
+.removed { background: #fee2e2; }
+";
+
+/// Iteration recorded for the session report and the iteration picker.
+pub struct ReportedIteration {
+    pub iteration: usize,
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    pub run_duration: Duration,
+    pub status: ExecStatus,
+    pub exit_code: Option<i32>,
+    pub output_bytes: usize,
+    pub truncated_at: Option<usize>,
+    /// Whether the output differed from the one of the previous iteration, even once evicted.
+    pub output_changed: bool,
+    /// Output of the iteration, `None` when identical to the one of the previous iteration or
+    /// evicted from the history.
+    pub output: Option<String>,
+    /// Bytes of the output as the command printed them, kept along with the output when they
+    /// differ from its UTF-8 bytes.
+    pub raw_output: Option<Vec<u8>>,
+}
+
+impl ReportedIteration {
+    pub fn is_success(&self) -> bool {
+        self.status == ExecStatus::Completed && self.exit_code == Some(0)
+    }
+
+    /// Bytes of memory its outputs take.
+    fn output_len(&self) -> usize {
+        self.output.as_ref().map_or(0, String::len) + self.raw_output.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Take its outputs out, returning the bytes they took.
+    fn take_outputs(&mut self) -> usize {
+        let output_len = self.output_len();
+        self.output = None;
+        self.raw_output = None;
+        output_len
+    }
+}
+
+/// Iterations of a pane since the watch started, reported with `R` or `--report`.
+/// Only the outputs which changed are kept, for long sessions of a stable output to stay small.
+/// Past the memory budget, the outputs of the oldest iterations are evicted, their metadata
+/// staying recorded.
+#[derive(Default)]
+pub struct SessionHistory {
+    iterations: VecDeque<ReportedIteration>,
+    last_output: String,
+    /// Bytes of the outputs kept, `max_output_bytes` at most.
+    output_bytes: usize,
+    max_output_bytes: Option<usize>,
+    /// Oldest iterations whose outputs got evicted.
+    evicted: usize,
+}
+
+impl SessionHistory {
+    pub fn new(max_output_bytes: Option<usize>) -> Self {
+        Self {
+            max_output_bytes,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, event: &WatcherIterationOutput) {
+        let output = match self.iterations.is_empty() || event.output != self.last_output {
+            true => {
+                self.last_output = event.output.clone();
+                Some(event.output.clone())
+            }
+            false => None,
+        };
+        if self.iterations.len() == REPORTED_ITERATIONS {
+            // The output of the dropped iteration is carried over when the next one shared it
+            let mut dropped = self.iterations.pop_front().unwrap();
+            match self.iterations.front_mut() {
+                Some(next) if next.output.is_none() && self.evicted == 0 => {
+                    next.output = dropped.output;
+                    next.raw_output = dropped.raw_output;
+                }
+                _ => self.output_bytes -= dropped.take_outputs(),
+            }
+            self.evicted = self.evicted.saturating_sub(1);
+        }
+        let iteration = ReportedIteration {
+            iteration: event.iteration,
+            started_at: event.started_at,
+            finished_at: event.finished_at,
+            run_duration: event.run_duration,
+            status: event.status,
+            exit_code: event.exit_code,
+            output_bytes: event.output_bytes,
+            truncated_at: event.truncated_at,
+            output_changed: output.is_some(),
+            raw_output: event.raw_output.clone().filter(|_| output.is_some()),
+            output,
+        };
+        self.output_bytes += iteration.output_len();
+        self.iterations.push_back(iteration);
+        self.evict_outputs();
+    }
+
+    /// Evict the outputs of the oldest iterations until they fit the memory budget, along with
+    /// the iterations sharing them. The latest output is always kept.
+    fn evict_outputs(&mut self) {
+        let Some(max_output_bytes) = self.max_output_bytes else {
+            return;
+        };
+        while self.output_bytes > max_output_bytes {
+            let Some(next_output) = self
+                .iterations
+                .range(self.evicted + 1..)
+                .position(|it| it.output.is_some())
+            else {
+                return;
+            };
+            let shared_until = self.evicted + 1 + next_output;
+            for it in self.iterations.range_mut(self.evicted..shared_until) {
+                self.output_bytes -= it.take_outputs();
+            }
+            self.evicted = shared_until;
+        }
+    }
+
+    /// Recorded iterations, the oldest first.
+    pub fn iterations(&self) -> &VecDeque<ReportedIteration> {
+        &self.iterations
+    }
+
+    /// Whether the output of the recorded iteration at `index` got evicted.
+    pub fn is_evicted(&self, index: usize) -> bool {
+        index < self.evicted
+    }
+
+    /// First and last iterations whose outputs are still kept, once some got evicted.
+    pub fn retained_range(&self) -> Option<(usize, usize)> {
+        let first = self
+            .iterations
+            .get(self.evicted)
+            .filter(|_| self.evicted > 0)?;
+        Some((first.iteration, self.iterations.back()?.iteration))
+    }
+
+    /// Index of the recorded `iteration`, unless it got dropped.
+    pub fn position(&self, iteration: usize) -> Option<usize> {
+        self.iterations
+            .iter()
+            .position(|it| it.iteration == iteration)
+    }
+
+    /// Output of the recorded iteration at `index`, carried over from the last one which changed.
+    pub fn output_at(&self, index: usize) -> Option<&str> {
+        self.changed_at(index)?.output.as_deref()
+    }
+
+    /// Bytes of the output at `index` as the command printed them, when they differ from its text.
+    pub fn raw_output_at(&self, index: usize) -> Option<&[u8]> {
+        self.changed_at(index)?.raw_output.as_deref()
+    }
+
+    /// Last recorded iteration up to `index` whose output changed, and is still kept.
+    fn changed_at(&self, index: usize) -> Option<&ReportedIteration> {
+        self.iterations
+            .range(..=index)
+            .rev()
+            .find(|it| it.output.is_some())
+    }
+}
+
+/// Recorded iterations of one of the watchers.
+pub struct PaneHistory<'a> {
+    pub title: Option<&'a str>,
+    /// Command the watcher runs, one per tab when several commands are watched.
+    pub command: &'a str,
+    pub history: &'a SessionHistory,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Line diff of two outputs, with the added and removed lines marked.
+fn diff_html(previous: &str, current: &str) -> String {
+    let mut html = String::new();
+    for line in line_diff(previous, current) {
+        match line {
+            DiffLine::Unchanged(line) => writeln!(html, "  {}", escape_html(line)),
+            DiffLine::Added(line) => {
+                writeln!(html, "<span class=\"added\">+ {}</span>", escape_html(line))
+            }
+            DiffLine::Removed(line) => {
+                writeln!(
+                    html,
+                    "<span class=\"removed\">- {}</span>",
+                    escape_html(line)
+                )
+            }
+            DiffLine::Elided(count) => writeln!(html, "  ⋯ {count} unchanged lines"),
+        }
+        .unwrap();
+    }
+    html
+}
+
+fn pane_html(html: &mut String, index: usize, pane: &PaneHistory<'_>) {
+    let iterations = &pane.history.iterations;
+    let failures = iterations.iter().filter(|it| !it.is_success()).count();
+    let changes = iterations
+        .iter()
+        .skip(1)
+        .filter(|it| it.output_changed)
+        .count();
+    writeln!(
+        html,
+        "<h2>{}</h2>\n<p>{} iterations, {failures} failed, {changes} output changes</p>",
+        escape_html(pane.title.unwrap_or("local")),
+        iterations.len(),
+    )
+    .unwrap();
+
+    html.push_str("<table>\n<tr><th>Itr</th><th>Finished</th><th>Duration</th><th>Status</th><th>Output</th></tr>\n");
+    for it in iterations {
+        let mut status = match (&it.status, it.exit_code) {
+            (ExecStatus::Completed, Some(exit_code)) => format!("exit {exit_code}"),
+            (status, _) => status.to_string(),
+        };
+        if let Some(truncated_at) = it.truncated_at {
+            write!(status, ", output truncated at {truncated_at} bytes").unwrap();
+        }
+        writeln!(
+            html,
+            "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            if it.is_success() {
+                ""
+            } else {
+                " class=\"failed\""
+            },
+            it.iteration,
+            it.finished_at.format("%Y-%m-%d %H:%M:%S"),
+            humantime::format_duration(Duration::from_millis(it.run_duration.as_millis() as u64)),
+            escape_html(&status),
+            match (&it.output, it.output_changed) {
+                (Some(_), _) => format!(
+                    "<a href=\"#{}-{}\">changed</a>",
+                    pane_anchor(index, pane),
+                    it.iteration
+                ),
+                (None, true) => "changed, evicted".to_string(),
+                (None, false) => "same".to_string(),
+            },
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+
+    // Full first output, then the diffs of the changed outputs
+    let mut previous_output: Option<&str> = None;
+    for it in iterations {
+        let Some(output) = &it.output else {
+            continue;
+        };
+        let body = match previous_output {
+            Some(previous_output) => diff_html(previous_output, output),
+            None => escape_html(output),
+        };
+        writeln!(
+            html,
+            "<details id=\"{}-{}\"{}><summary>Itr {} at {}</summary>\n<pre>{body}</pre>\n</details>",
+            pane_anchor(index, pane),
+            it.iteration,
+            if previous_output.is_none() { " open" } else { "" },
+            it.iteration,
+            it.finished_at.format("%H:%M:%S"),
+        )
+        .unwrap();
+        previous_output = Some(output);
+    }
+}
+
+/// Anchor of the outputs of the pane at `index`, unique across the tabs sharing the titles.
+fn pane_anchor(index: usize, pane: &PaneHistory<'_>) -> String {
+    let title = pane.title.unwrap_or("local");
+    let title = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    format!("{index}-{title}")
+}
+
+/// Write a standalone HTML report of the session at `path`, with the commands, and the
+/// durations, exit codes, outputs and diffs of the recorded iterations of each pane.
+pub fn write_report(
+    path: &Path,
+    query_state: &QueryState,
+    panes: &[PaneHistory<'_>],
+) -> OpenResult<()> {
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>watch-rs report</title>\n<style>{REPORT_STYLE}</style>\n</head>\n<body>"
+    )?;
+    writeln!(
+        html,
+        "<h1>watch-rs session report</h1>\n<p>Generated on {}</p>",
+        Local::now().format("%Y-%m-%d %H:%M:%S %Z")
+    )?;
+    if !query_state.setup_commands.trim().is_empty() {
+        writeln!(
+            html,
+            "<h3>Setup</h3>\n<pre>{}</pre>",
+            escape_html(&redact(&query_state.setup_commands))
+        )?;
+    }
+    // Panes of the same tab follow each other, under the command they share
+    let mut command = None;
+    for (index, pane) in panes.iter().enumerate() {
+        if command != Some(pane.command) {
+            command = Some(pane.command);
+            writeln!(
+                html,
+                "<h3>Command</h3>\n<pre>{}</pre>",
+                escape_html(&redact(pane.command))
+            )?;
+        }
+        pane_html(&mut html, index, pane);
+    }
+    html.push_str("</body>\n</html>\n");
+
+    fs::write(path, html)?;
+    Ok(())
+}
+
+/// Name of a new session report file.
+pub fn default_report_name() -> String {
+    format!(
+        "watch-rs-report-{}.html",
+        Local::now().format("%Y%m%d-%H%M%S")
+    )
+}
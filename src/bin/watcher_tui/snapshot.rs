@@ -0,0 +1,162 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use watch_rs::utils::OpenResult;
+
+/// Outputs larger than this are exported as gzip-compressed chunks, unless configured otherwise.
+pub const DEFAULT_CHUNK_SIZE_MB: u64 = 8;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Index of a snapshot exported in chunks, stored next to them.
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub iteration: usize,
+    pub taken_at: DateTime<Local>,
+    /// Size of the whole uncompressed output.
+    pub total_bytes: usize,
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    /// Name of the chunk file, within the snapshot directory.
+    pub file: String,
+    /// Position of the chunk within the uncompressed output.
+    pub offset: usize,
+    pub bytes: usize,
+}
+
+/// Split the `output` in pieces of at most `chunk_size` bytes, cut after a newline when possible.
+fn split_chunks(output: &str, chunk_size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = output;
+    while rest.len() > chunk_size {
+        let mut end = chunk_size;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = rest[..end].rfind('\n').map_or(end, |newline| newline + 1);
+
+        let (chunk, remaining) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+    chunks.push(rest);
+    chunks
+}
+
+/// Export the `output` of an iteration as `name` within `dir`, returning the path written.
+/// Outputs up to `chunk_size` bytes are written as a plain `.txt` file, larger ones as a
+/// directory of gzip-compressed chunks indexed by a manifest.
+pub fn export_snapshot(
+    dir: &Path,
+    name: &str,
+    iteration: usize,
+    output: &str,
+    chunk_size: usize,
+) -> OpenResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    if output.len() <= chunk_size {
+        let path = dir.join(format!("{name}.txt"));
+        fs::write(&path, output)?;
+        return Ok(path);
+    }
+
+    let snapshot_dir = dir.join(name);
+    fs::create_dir_all(&snapshot_dir)?;
+
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    for (index, chunk) in split_chunks(output, chunk_size).into_iter().enumerate() {
+        let file = format!("chunk-{index:04}.gz");
+        let mut encoder = GzEncoder::new(
+            File::create(snapshot_dir.join(&file))?,
+            Compression::default(),
+        );
+        encoder.write_all(chunk.as_bytes())?;
+        encoder.finish()?;
+
+        chunks.push(SnapshotChunk {
+            file,
+            offset,
+            bytes: chunk.len(),
+        });
+        offset += chunk.len();
+    }
+    debug!("Exported {} chunks into {snapshot_dir:?}", chunks.len());
+
+    let manifest = SnapshotManifest {
+        iteration,
+        taken_at: Local::now(),
+        total_bytes: output.len(),
+        chunks,
+    };
+    fs::write(
+        snapshot_dir.join(MANIFEST_FILE_NAME),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(snapshot_dir)
+}
+
+/// An exported snapshot, of which the chunks are only decompressed when loaded.
+pub enum SnapshotReader {
+    Plain(PathBuf),
+    Chunked {
+        dir: PathBuf,
+        manifest: SnapshotManifest,
+    },
+}
+
+impl SnapshotReader {
+    /// Open the snapshot at `path`, either a plain file or a directory with a manifest.
+    pub fn open(path: &Path) -> OpenResult<Self> {
+        if path.is_file() {
+            return Ok(Self::Plain(path.to_path_buf()));
+        }
+
+        let manifest_path = path.join(MANIFEST_FILE_NAME);
+        let manifest = fs::read_to_string(&manifest_path).map_err(|err| {
+            format!(
+                "no snapshot manifest at {} : {err}",
+                manifest_path.display()
+            )
+        })?;
+        Ok(Self::Chunked {
+            dir: path.to_path_buf(),
+            manifest: serde_json::from_str(&manifest)?,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        match self {
+            Self::Plain(_) => 1,
+            Self::Chunked { manifest, .. } => manifest.chunks.len(),
+        }
+    }
+
+    /// Read and decompress the chunk at `index`.
+    pub fn load_chunk(&self, index: usize) -> OpenResult<String> {
+        match self {
+            Self::Plain(path) => Ok(fs::read_to_string(path)?),
+            Self::Chunked { dir, manifest } => {
+                let chunk = manifest
+                    .chunks
+                    .get(index)
+                    .ok_or_else(|| format!("no chunk {index} in the snapshot"))?;
+
+                let mut contents = String::with_capacity(chunk.bytes);
+                GzDecoder::new(File::open(dir.join(&chunk.file))?).read_to_string(&mut contents)?;
+                Ok(contents)
+            }
+        }
+    }
+}
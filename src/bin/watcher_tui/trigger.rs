@@ -0,0 +1,460 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{debug, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use regex::Regex;
+use watch_rs::{models::watcher_loop::Trigger, utils::OpenResult};
+
+/// How often the watched files are scanned for changes, with `--watch-poll`.
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+/// Files scanned per glob at most, not to walk a whole disk on a too broad glob.
+const MAX_SCANNED_FILES: usize = 20_000;
+/// Directories never walked into, their churn is rarely a change of the watched sources.
+const SKIPPED_DIRS: [&str; 3] = [".git", "target", "node_modules"];
+
+/// Glob of the files watched with `--watch-path`, like `src/**/*.rs`. `**` matches across the
+/// directories, `*` and `?` within a path component, and `{a,b}` any of the alternatives.
+/// The files below a matched directory are matched as well.
+#[derive(Clone)]
+pub struct PathGlob {
+    /// Directory the walk starts from, the components of the glob before the first wildcard.
+    base: PathBuf,
+    regex: Regex,
+}
+
+impl FromStr for PathGlob {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if pattern.trim().is_empty() {
+            return Err("empty glob".to_string());
+        }
+        let is_wildcard = |component: &&str| component.contains(['*', '?', '{']);
+        let base = pattern
+            .split('/')
+            .take_while(|component| !is_wildcard(component))
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        let mut in_alternatives = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    // `**/` matches no directory at all as well
+                    match chars.next_if_eq(&'/') {
+                        Some(_) => regex.push_str("(?:.*/)?"),
+                        None => regex.push_str(".*"),
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '{' if !in_alternatives => {
+                    in_alternatives = true;
+                    regex.push_str("(?:");
+                }
+                '}' if in_alternatives => {
+                    in_alternatives = false;
+                    regex.push(')');
+                }
+                ',' if in_alternatives => regex.push('|'),
+                c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        if in_alternatives {
+            return Err(format!("unclosed {{ in {pattern}"));
+        }
+        regex.push_str("(?:/.*)?$");
+
+        Ok(Self {
+            base: PathBuf::from(base),
+            regex: Regex::new(&regex).map_err(|err| err.to_string())?,
+        })
+    }
+}
+
+/// State of the watched files, compared across the scans.
+#[derive(Clone, Default, PartialEq)]
+struct FilesSnapshot {
+    /// Modification time and size of the files.
+    files: BTreeMap<PathBuf, (Option<SystemTime>, u64)>,
+    /// HEAD and staged entries of the git repository, with `--watch-git`.
+    repository: Option<String>,
+}
+
+impl FilesSnapshot {
+    fn insert(&mut self, file: PathBuf) {
+        if let Ok(metadata) = fs::metadata(&file) {
+            self.files
+                .insert(file, (metadata.modified().ok(), metadata.len()));
+        }
+    }
+}
+
+/// Changes reported by the file events of the OS since a trigger last polled.
+#[derive(Default)]
+struct PendingChanges {
+    /// A file matching a glob changed.
+    files: AtomicBool,
+    /// Something changed in the git repository, to be compared with its snapshot.
+    repository: AtomicBool,
+}
+
+/// File events of the OS (inotify, FSEvents, ...) on the watched directories, shared by the
+/// copies of a trigger. Each copy gets its own pending changes.
+struct FileEvents {
+    watcher: Mutex<RecommendedWatcher>,
+    subscribers: Arc<Mutex<Vec<Weak<PendingChanges>>>>,
+}
+
+impl FileEvents {
+    fn new(globs: Vec<PathGlob>, root: PathBuf, git: bool) -> notify::Result<Self> {
+        let subscribers = Arc::new(Mutex::new(Vec::<Weak<PendingChanges>>::new()));
+        let notified = Arc::clone(&subscribers);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => event,
+                Ok(_) => return,
+                Err(err) => return warn!("Failed to watch the files : {err}"),
+            };
+            let relative_paths = event
+                .paths
+                .iter()
+                .map(|path| path.strip_prefix(&root).unwrap_or(path).to_string_lossy())
+                .collect::<Vec<_>>();
+            let has_files = relative_paths
+                .iter()
+                .any(|path| globs.iter().any(|glob| glob.regex.is_match(path)));
+            let has_repository = git && relative_paths.iter().any(|path| !is_churn(path));
+            if !has_files && !has_repository {
+                return;
+            }
+
+            notified.lock().unwrap().retain(|pending| {
+                let Some(pending) = pending.upgrade() else {
+                    return false;
+                };
+                pending.files.fetch_or(has_files, Ordering::AcqRel);
+                pending
+                    .repository
+                    .fetch_or(has_repository, Ordering::AcqRel);
+                true
+            });
+        })?;
+
+        Ok(Self {
+            watcher: Mutex::new(watcher),
+            subscribers,
+        })
+    }
+
+    /// Watch the directory `path` and the ones below it, or the one of the file `path`. A missing
+    /// path is looked for from its closest existing directory.
+    fn watch(&self, path: &Path) -> notify::Result<()> {
+        let directory = path
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or(Path::new("."));
+        debug!("Watching the changes of {}", directory.display());
+        self.watcher
+            .lock()
+            .unwrap()
+            .watch(directory, RecursiveMode::Recursive)
+    }
+
+    /// Changes pending for a new copy of the trigger.
+    fn subscribe(&self) -> Arc<PendingChanges> {
+        let pending = Arc::default();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&pending));
+        pending
+    }
+}
+
+/// Whether the changed `path`, relative to the root, is churn of the build tools or of the git
+/// objects rather than a change of the sources or of the repository.
+fn is_churn(path: &str) -> bool {
+    let mut components = path.split('/');
+    match components.next() {
+        Some(".git") => matches!(components.next(), Some("objects" | "logs") | None),
+        Some(first) => SKIPPED_DIRS.contains(&first),
+        None => false,
+    }
+}
+
+/// How the changes of the watched files get detected.
+enum Detection {
+    /// From the file events of the OS, only scanning the git repository once it got changed.
+    Events {
+        events: Arc<FileEvents>,
+        pending: Arc<PendingChanges>,
+    },
+    /// By scanning the modification times and sizes of the files a few times a second, for the
+    /// file systems without file events, like the network ones.
+    Polling { scanned_at: Instant },
+}
+
+impl Clone for Detection {
+    fn clone(&self) -> Self {
+        match self {
+            Detection::Events { events, .. } => Detection::Events {
+                events: Arc::clone(events),
+                pending: events.subscribe(),
+            },
+            Detection::Polling { scanned_at } => Detection::Polling {
+                scanned_at: *scanned_at,
+            },
+        }
+    }
+}
+
+/// Trigger of the iterations on the changes of the files matching the globs of `--watch-path`,
+/// detected from the file events of the OS, or by scanning the files with `--watch-poll`.
+/// Each watcher thread polls its own copy.
+#[derive(Clone)]
+pub struct FileTrigger {
+    globs: Vec<PathGlob>,
+    /// Directory the relative globs are looked up from.
+    root: PathBuf,
+    /// Quiet time after a change before running, for the burst of writes of a save or of a
+    /// checkout to trigger a single iteration.
+    debounce: Duration,
+    /// Watch the git repository of the root as well, its HEAD, index and working tree.
+    git: bool,
+    detection: Detection,
+    snapshot: FilesSnapshot,
+    /// Time of the latest change not triggered on yet, as the files may still be changing.
+    changed_at: Option<Instant>,
+}
+
+impl FileTrigger {
+    pub fn new(globs: Vec<PathGlob>, root: PathBuf, debounce: Duration) -> Self {
+        // The file events report the paths from the watched directories
+        let root = fs::canonicalize(&root).unwrap_or(root);
+        let mut trigger = Self {
+            globs,
+            root,
+            debounce,
+            git: false,
+            detection: Detection::Polling {
+                scanned_at: Instant::now(),
+            },
+            snapshot: FilesSnapshot::default(),
+            changed_at: None,
+        };
+        trigger.snapshot = trigger.snapshot();
+        trigger
+    }
+
+    /// The same trigger, detecting the changes from the file events of the OS rather than by
+    /// scanning the files. Falls back on scanning them when the events are not available.
+    pub fn with_file_events(mut self) -> Self {
+        let events =
+            FileEvents::new(self.globs.clone(), self.root.clone(), self.git).and_then(|events| {
+                let watched = self.globs.iter().map(|glob| self.root.join(&glob.base));
+                for path in watched.chain(self.git.then(|| self.root.clone())) {
+                    events.watch(&path)?;
+                }
+                Ok(events)
+            });
+        match events {
+            Ok(events) => {
+                let pending = events.subscribe();
+                self.detection = Detection::Events {
+                    events: Arc::new(events),
+                    pending,
+                };
+                self.snapshot = self.snapshot();
+            }
+            Err(err) => {
+                warn!("Scanning the watched files, as their events are not available : {err}")
+            }
+        }
+        self
+    }
+
+    /// The same trigger, watching the git repository of the root as well: the commits and
+    /// checkouts moving HEAD, the staged changes, and the changes of the tracked files and of
+    /// the untracked ones not ignored by `.gitignore`.
+    pub fn with_git(mut self) -> OpenResult<Self> {
+        if git_output(&self.root, &["rev-parse", "--git-dir"]).is_none() {
+            return Err(format!(
+                "--watch-git needs {} to be in a git repository",
+                self.root.display()
+            )
+            .into());
+        }
+        self.git = true;
+        self.snapshot = self.snapshot();
+        Ok(self)
+    }
+
+    fn scan_globs(&self, snapshot: &mut FilesSnapshot) {
+        for glob in &self.globs {
+            let mut files = Vec::new();
+            walk(&self.root.join(&glob.base), &mut files);
+            for file in files {
+                // Relative globs are matched against the paths relative to the root
+                let relative = file.strip_prefix(&self.root).unwrap_or(&file);
+                if !glob.regex.is_match(&relative.to_string_lossy()) {
+                    continue;
+                }
+                snapshot.insert(file);
+            }
+        }
+    }
+
+    fn scan_repository(&self, snapshot: &mut FilesSnapshot) {
+        if self.git {
+            let head = git_output(&self.root, &["rev-parse", "--verify", "-q", "HEAD"]);
+            let index = git_output(&self.root, &["ls-files", "-z", "--stage"]);
+            snapshot.repository = Some(format!(
+                "{}\n{}",
+                head.unwrap_or_default(),
+                index.unwrap_or_default()
+            ));
+
+            let listed = [
+                "ls-files",
+                "-z",
+                "--cached",
+                "--others",
+                "--exclude-standard",
+            ];
+            let listed = git_output(&self.root, &listed).unwrap_or_default();
+            // A deleted tracked file is still listed, its missing metadata is the change
+            for file in listed
+                .split('\0')
+                .filter(|file| !file.is_empty())
+                .take(MAX_SCANNED_FILES)
+            {
+                snapshot.insert(self.root.join(file));
+            }
+        }
+    }
+
+    /// Whether the watched files changed since the previous poll.
+    fn has_changed(&mut self) -> bool {
+        let has_files = match &mut self.detection {
+            Detection::Events { pending, .. } => {
+                let has_files = pending.files.swap(false, Ordering::AcqRel);
+                if !pending.repository.swap(false, Ordering::AcqRel) {
+                    return has_files;
+                }
+                has_files
+            }
+            Detection::Polling { scanned_at } if scanned_at.elapsed() >= SCAN_INTERVAL => {
+                *scanned_at = Instant::now();
+                false
+            }
+            Detection::Polling { .. } => return false,
+        };
+
+        // Most changes of the repository leave its state as is, like the ones of the ignored files
+        let snapshot = self.snapshot();
+        let has_changed = snapshot != self.snapshot;
+        self.snapshot = snapshot;
+        has_files || has_changed
+    }
+
+    /// Snapshot of the files compared across the polls: the repository only with the file
+    /// events, which tell the changes of the globs apart already.
+    fn snapshot(&self) -> FilesSnapshot {
+        let mut snapshot = FilesSnapshot::default();
+        if let Detection::Polling { .. } = self.detection {
+            self.scan_globs(&mut snapshot);
+        }
+        self.scan_repository(&mut snapshot);
+        snapshot
+    }
+}
+
+impl Trigger for FileTrigger {
+    fn name(&self) -> &'static str {
+        "files"
+    }
+
+    fn poll(&mut self) -> bool {
+        if self.has_changed() {
+            self.changed_at = Some(Instant::now());
+        }
+
+        // Fires once the files stayed unchanged for the debounce duration
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= self.debounce => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        let next_scan = match &self.detection {
+            Detection::Events { .. } => Duration::MAX,
+            Detection::Polling { scanned_at } => SCAN_INTERVAL.saturating_sub(scanned_at.elapsed()),
+        };
+        match self.changed_at {
+            Some(changed_at) => next_scan.min(self.debounce.saturating_sub(changed_at.elapsed())),
+            None => next_scan,
+        }
+    }
+}
+
+/// Output of a git command run in `root`, None when it fails.
+fn git_output(root: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(root)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Collect the files below `path`, or `path` itself when it is a file.
+fn walk(path: &Path, files: &mut Vec<PathBuf>) {
+    if files.len() >= MAX_SCANNED_FILES {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        if path.is_file() {
+            files.push(path.to_path_buf());
+        }
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                let is_skipped = SKIPPED_DIRS
+                    .iter()
+                    .any(|skipped| entry.file_name() == *skipped);
+                if !is_skipped {
+                    walk(&path, files);
+                }
+            }
+            Ok(_) if files.len() < MAX_SCANNED_FILES => files.push(path),
+            _ => {}
+        }
+    }
+}
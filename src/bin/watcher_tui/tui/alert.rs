@@ -0,0 +1,83 @@
+use std::{fmt, str::FromStr};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static ALERT_RULE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*value\s*(>=|<=|==|!=|>|<)\s*(\S+)\s*$").unwrap());
+
+#[derive(Clone, Copy, PartialEq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// Threshold on the value extracted with `--plot`, like `value > 90`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct AlertRule {
+    comparison: Comparison,
+    threshold: f64,
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let captures = ALERT_RULE.captures(rule).ok_or_else(|| {
+            format!("invalid alert `{rule}`, expected `value <op> <number>` with an op among > >= < <= == !=")
+        })?;
+
+        let comparison = match &captures[1] {
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            "==" => Comparison::Equal,
+            _ => Comparison::NotEqual,
+        };
+        let threshold = captures[2]
+            .parse()
+            .map_err(|_| format!("invalid alert threshold `{}`", &captures[2]))?;
+        Ok(Self {
+            comparison,
+            threshold,
+        })
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.comparison {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        };
+        write!(f, "value {op} {}", self.threshold)
+    }
+}
+
+impl AlertRule {
+    pub fn is_satisfied(&self, value: f64) -> bool {
+        match self.comparison {
+            Comparison::Greater => value > self.threshold,
+            Comparison::GreaterOrEqual => value >= self.threshold,
+            Comparison::Less => value < self.threshold,
+            Comparison::LessOrEqual => value <= self.threshold,
+            Comparison::Equal => value == self.threshold,
+            Comparison::NotEqual => value != self.threshold,
+        }
+    }
+}
+
+/// Alert raised on a pane, for as long as its latest value satisfies the rule.
+pub struct ActiveAlert {
+    pub rule: AlertRule,
+    pub value: f64,
+}
@@ -0,0 +1,569 @@
+use std::{env, path::Path, str::FromStr, time::Duration};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use clap::ValueEnum;
+use ratatui::{
+    style::{palette::tailwind, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use watch_rs::models::{
+    json_diff::{self, JsonChange, JsonDiffNode, JsonPath},
+    line_diff::{split_chars, split_words, token_diff, DiffLine, DiffSpan},
+};
+
+/// How the output of the watched command is interpreted for rendering and diffing.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// Outputs detected as JSON are pretty-printed with sorted keys, then rendered and
+    /// diffed line by line like text.
+    Auto,
+    /// Columnar outputs are rendered as a table, with the cells which changed highlighted.
+    Table,
+    /// CSV outputs are rendered as a table, like the columnar ones.
+    Csv,
+    /// Markdown outputs are rendered with styled headings, lists, quotes and code.
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Format following this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Text => Self::Json,
+            Self::Json => Self::Auto,
+            Self::Auto => Self::Table,
+            Self::Table => Self::Csv,
+            Self::Csv => Self::Markdown,
+            Self::Markdown => Self::Text,
+        }
+    }
+
+    /// Whether the outputs are rendered as a table.
+    pub fn is_tabular(self) -> bool {
+        matches!(self, Self::Table | Self::Csv)
+    }
+
+    /// Whether the JSON outputs get pretty-printed by the watcher threads.
+    pub fn pretty_prints_json(self) -> bool {
+        matches!(self, Self::Json | Self::Auto)
+    }
+}
+
+/// Unit of the changes highlighted in the diff view.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DiffGranularity {
+    /// Changed lines are removed then added whole, like `diff`.
+    #[default]
+    Line,
+    /// Changed words are highlighted within the lines, like `git diff --word-diff`.
+    Word,
+    /// Changed characters are highlighted within the lines, like `watch -d`.
+    Char,
+}
+
+impl DiffGranularity {
+    /// Granularity following this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Line => Self::Word,
+            Self::Word => Self::Char,
+            Self::Char => Self::Line,
+        }
+    }
+}
+
+/// How the hours of the clock are counted.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+pub enum HourCycle {
+    /// From 1 to 12, followed by AM or PM.
+    #[value(name = "12h")]
+    #[serde(rename = "12h")]
+    H12,
+    #[default]
+    #[value(name = "24h")]
+    #[serde(rename = "24h")]
+    H24,
+}
+
+/// `strftime` format of the clock, like `%H:%M` or `%a %d %b %I:%M:%S %p`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TimeFormat(String);
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match StrftimeItems::new(value).any(|item| item == Item::Error) {
+            true => Err(format!("invalid strftime format `{value}`")),
+            false => Ok(Self(value.to_string())),
+        }
+    }
+}
+
+/// Database of the IANA time zones, the one `chrono` reads the zone of `TZ` from.
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// IANA time zone the times are displayed in, like `Europe/Paris` or `UTC`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Timezone(String);
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let is_known = Path::new(value).is_relative()
+            && !value.split('/').any(|part| part == "..")
+            && Path::new(ZONEINFO_DIR).join(value).is_file();
+        match is_known {
+            true => Ok(Self(value.to_string())),
+            false => Err(format!("unknown time zone `{value}`")),
+        }
+    }
+}
+
+impl Timezone {
+    /// Make this zone the local one of the process, through `TZ`, for the clock and the
+    /// timestamps of the logs. The shells of the watchers inherit it as well.
+    pub fn set_local(&self) {
+        env::set_var("TZ", &self.0);
+    }
+}
+
+/// Name of the local time zone, the one of `TZ` when set.
+pub fn local_timezone_name() -> Option<String> {
+    match env::var("TZ") {
+        Ok(timezone) if !timezone.is_empty() => Some(timezone.trim_start_matches(':').to_string()),
+        _ => iana_time_zone::get_timezone().ok(),
+    }
+}
+
+/// Conventions of the user's locale for rendering numbers and dates.
+#[derive(Clone)]
+pub struct Locale {
+    decimal_separator: char,
+    group_separator: char,
+    /// `strftime` format of the dates, followed by the time of the day.
+    date_format: &'static str,
+    hour_cycle: HourCycle,
+    /// Format of the clock replacing the date and time of the day of the locale.
+    time_format: Option<TimeFormat>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            group_separator: ',',
+            date_format: "%b %d",
+            hour_cycle: HourCycle::H24,
+            time_format: None,
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parse a POSIX locale name like `de_DE.UTF-8`, or a language tag like `fr-CA`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let name = value.split(['.', '@']).next().unwrap_or_default();
+        let (language, region) = name.split_once(['_', '-']).unwrap_or((name, ""));
+        let region = region.to_ascii_uppercase();
+
+        let (decimal_separator, group_separator, date_format) =
+            match language.to_ascii_lowercase().as_str() {
+                "c" | "posix" => return Ok(Self::default()),
+                "en" if matches!(region.as_str(), "" | "US" | "CA" | "PH") => {
+                    return Ok(Self::default())
+                }
+                "en" => ('.', ',', "%d %b"),
+                "de" | "da" | "nb" | "no" | "fi" | "cs" | "sk" | "pl" | "ru" | "uk" | "tr" => {
+                    (',', '.', "%d.%m.")
+                }
+                "fr" | "sv" => (',', '\u{202f}', "%d/%m"),
+                "es" | "it" | "pt" | "ro" | "el" => (',', '.', "%d/%m"),
+                "nl" => (',', '.', "%d-%m"),
+                "ja" | "zh" | "ko" | "hu" | "lt" => ('.', ',', "%m/%d"),
+                _ => return Err(format!("unsupported locale `{value}`")),
+            };
+        Ok(Self {
+            decimal_separator,
+            group_separator,
+            date_format,
+            ..Self::default()
+        })
+    }
+}
+
+impl Locale {
+    /// Locale of the environment, from `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    /// Unset and unsupported locales fall back on the US English conventions.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// The same locale, with its clock counting the hours with the `hour_cycle`, or formatted
+    /// with the `time_format` when given.
+    pub fn with_clock(self, hour_cycle: HourCycle, time_format: Option<TimeFormat>) -> Self {
+        Self {
+            hour_cycle,
+            time_format,
+            ..self
+        }
+    }
+
+    /// Integer with its digits grouped by thousands.
+    pub fn format_integer(&self, value: usize) -> String {
+        let digits = value.to_string();
+        let mut grouped = String::with_capacity(digits.len() * 4 / 3);
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push(self.group_separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// Decimal number rounded to `precision` fractional digits.
+    pub fn format_decimal(&self, value: f64, precision: usize) -> String {
+        let formatted = format!("{value:.precision$}");
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let integer = match integer.parse::<usize>() {
+            Ok(integer) => self.format_integer(integer),
+            Err(_) => integer.to_string(),
+        };
+        match fraction.is_empty() {
+            true => integer,
+            false => format!("{integer}{}{fraction}", self.decimal_separator),
+        }
+    }
+
+    /// Short duration, in milliseconds under a second and else in seconds.
+    pub fn format_duration(&self, duration: Duration) -> String {
+        match duration.as_millis() {
+            millis @ 0..1000 => format!("{millis}ms"),
+            _ => format!("{}s", self.format_decimal(duration.as_secs_f64(), 2)),
+        }
+    }
+
+    /// Date and time of the day, with hundredths of seconds when `precise`.
+    /// A custom time format is rendered as is, its precision is up to its own specifiers.
+    pub fn format_clock(&self, time: &DateTime<Local>, precise: bool) -> String {
+        if let Some(TimeFormat(time_format)) = &self.time_format {
+            return time.format(time_format).to_string();
+        }
+
+        let hours = match self.hour_cycle {
+            HourCycle::H12 => "%I",
+            HourCycle::H24 => "%H",
+        };
+        let mut clock = time
+            .format(&format!("{} {hours}:%M:%S", self.date_format))
+            .to_string();
+        if precise {
+            clock.push_str(&format!(
+                "{}{:02}",
+                self.decimal_separator,
+                time.timestamp_subsec_millis() / 10
+            ));
+        }
+        if self.hour_cycle == HourCycle::H12 {
+            clock.push_str(&time.format(" %p").to_string());
+        }
+        clock
+    }
+}
+
+/// Whether the `output` bytes look like binary data rather than text: they have a NUL byte, or
+/// more than a tenth of their characters are neither printable nor whitespace, invalid UTF-8
+/// sequences included.
+pub fn is_binary(output: &[u8]) -> bool {
+    if output.contains(&0) {
+        return true;
+    }
+    let mut char_count = 0;
+    let mut non_printable_count = 0;
+    for c in String::from_utf8_lossy(output).chars() {
+        char_count += 1;
+        // Escape sequences of colored outputs are text as well
+        if (c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x1b'))
+            || c == char::REPLACEMENT_CHARACTER
+        {
+            non_printable_count += 1;
+        }
+    }
+    non_printable_count * 10 > char_count
+}
+
+/// Hexdump of the `output` bytes, 16 per line with their offset and printable characters.
+pub fn hexdump(output: &[u8]) -> String {
+    let mut dump = String::with_capacity(output.len() * 4);
+    for (line_index, bytes) in output.chunks(16).enumerate() {
+        let mut hex = String::with_capacity(50);
+        for (index, byte) in bytes.iter().enumerate() {
+            if index == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{byte:02x} "));
+        }
+        let printable = bytes
+            .iter()
+            .map(|&byte| match byte.is_ascii_graphic() || byte == b' ' {
+                true => byte as char,
+                false => '.',
+            })
+            .collect::<String>();
+        dump.push_str(&format!(
+            "{:08x}  {hex:<49} |{printable}|\n",
+            line_index * 16
+        ));
+    }
+    dump
+}
+
+/// Keep only the lines of the `output` matching the `filter`.
+pub fn filter_lines(output: &str, filter: &Regex) -> String {
+    output
+        .lines()
+        .filter(|line| filter.is_match(line))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// Prefix each line of the `text` with its line number, right aligned on the widest one.
+pub fn with_line_numbers(mut text: Text<'_>) -> Text<'_> {
+    let width = text.lines.len().to_string().len();
+    let number_style = Style::default().fg(tailwind::SLATE.c500);
+
+    for (line_index, line) in text.lines.iter_mut().enumerate() {
+        let number = format!("{:>width$} │ ", line_index + 1);
+        line.spans.insert(0, Span::styled(number, number_style));
+    }
+    text
+}
+
+/// The `output` pretty-printed with its object keys sorted, for the layout of the JSON to
+/// stay the same across iterations. `None` when it is not a JSON object or array.
+pub fn pretty_json(output: &str) -> Option<String> {
+    let trimmed = output.trim_start();
+    if !trimmed.starts_with(['{', '[']) {
+        return None;
+    }
+
+    let value = serde_json::from_str::<Value>(trimmed).ok()?;
+    let mut pretty = serde_json::to_string_pretty(&value).ok()?;
+    pretty.push('\n');
+    Some(pretty)
+}
+
+/// Structural diff of two JSON outputs without the `ignored_paths`,
+/// `None` when either of them is not valid JSON.
+pub fn json_output_diff(
+    previous: &str,
+    current: &str,
+    ignored_paths: &[JsonPath],
+) -> Option<JsonDiffNode> {
+    let previous = json_diff::parse_without(previous, ignored_paths)?;
+    let current = json_diff::parse_without(current, ignored_paths)?;
+    Some(json_diff::diff(&previous, &current))
+}
+
+pub fn describe_json_change(change: &JsonChange) -> String {
+    match change {
+        JsonChange::Added { path, value } => format!("+ {path}: {value}"),
+        JsonChange::Removed { path, value } => format!("- {path}: {value}"),
+        JsonChange::Changed { path, old, new } => format!("~ {path}: {old} → {new}"),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Marker {
+    Unchanged,
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Marker {
+    fn symbol(self) -> &'static str {
+        match self {
+            Marker::Unchanged => "  ",
+            Marker::Added => "+ ",
+            Marker::Removed => "- ",
+            Marker::Changed => "~ ",
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            Marker::Unchanged => Style::default(),
+            Marker::Added => Style::default().fg(tailwind::GREEN.c400),
+            Marker::Removed => Style::default().fg(tailwind::RED.c400),
+            Marker::Changed => Style::default().fg(tailwind::AMBER.c300),
+        }
+    }
+}
+
+/// Tree-style rendering of a structural JSON diff, marking the added, removed and changed nodes.
+pub fn json_diff_text(node: &JsonDiffNode) -> Text<'static> {
+    let mut lines = Vec::new();
+    render_diff_node(node, None, 0, &mut lines);
+    lines.into()
+}
+
+fn render_diff_node(
+    node: &JsonDiffNode,
+    key: Option<&str>,
+    depth: usize,
+    lines: &mut Vec<Line<'static>>,
+) {
+    match node {
+        JsonDiffNode::Unchanged(value) => render_value(value, key, depth, Marker::Unchanged, lines),
+        JsonDiffNode::Added(value) => render_value(value, key, depth, Marker::Added, lines),
+        JsonDiffNode::Removed(value) => render_value(value, key, depth, Marker::Removed, lines),
+        JsonDiffNode::Changed { old, new } if !is_compound(old) && !is_compound(new) => {
+            lines.push(tree_line(
+                Marker::Changed,
+                depth,
+                key,
+                &format!("{old} → {new}"),
+            ));
+        }
+        JsonDiffNode::Changed { old, new } => {
+            render_value(old, key, depth, Marker::Removed, lines);
+            render_value(new, key, depth, Marker::Added, lines);
+        }
+        JsonDiffNode::Object(entries) => {
+            lines.push(tree_line(Marker::Unchanged, depth, key, "{"));
+            for (entry_key, entry_node) in entries {
+                render_diff_node(entry_node, Some(entry_key), depth + 1, lines);
+            }
+            lines.push(tree_line(Marker::Unchanged, depth, None, "}"));
+        }
+        JsonDiffNode::Array(items) => {
+            lines.push(tree_line(Marker::Unchanged, depth, key, "["));
+            for item_node in items {
+                render_diff_node(item_node, None, depth + 1, lines);
+            }
+            lines.push(tree_line(Marker::Unchanged, depth, None, "]"));
+        }
+    }
+}
+
+fn render_value(
+    value: &Value,
+    key: Option<&str>,
+    depth: usize,
+    marker: Marker,
+    lines: &mut Vec<Line<'static>>,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            lines.push(tree_line(marker, depth, key, "{"));
+            for (entry_key, entry_value) in map {
+                render_value(entry_value, Some(entry_key), depth + 1, marker, lines);
+            }
+            lines.push(tree_line(marker, depth, None, "}"));
+        }
+        Value::Array(items) if !items.is_empty() => {
+            lines.push(tree_line(marker, depth, key, "["));
+            for item in items {
+                render_value(item, None, depth + 1, marker, lines);
+            }
+            lines.push(tree_line(marker, depth, None, "]"));
+        }
+        _ => lines.push(tree_line(marker, depth, key, &value.to_string())),
+    }
+}
+
+fn tree_line(marker: Marker, depth: usize, key: Option<&str>, content: &str) -> Line<'static> {
+    let indent = "  ".repeat(depth);
+    let line = match key {
+        Some(key) => format!("{}{indent}{key}: {content}", marker.symbol()),
+        None => format!("{}{indent}{content}", marker.symbol()),
+    };
+    Line::styled(line, marker.style())
+}
+
+fn is_compound(value: &Value) -> bool {
+    matches!(value, Value::Object(_) | Value::Array(_))
+}
+
+/// Rendering of a line diff, with the elided runs of unchanged lines as separators.
+/// Past the line `granularity`, the removed lines followed by added ones are rendered as
+/// changed lines, with their changed words or characters highlighted.
+pub fn line_diff_text<'a>(diff_lines: &[DiffLine<'a>], granularity: DiffGranularity) -> Text<'a> {
+    let mut lines = Vec::new();
+    let mut removed_lines = Vec::new();
+    for diff_line in diff_lines {
+        match (diff_line, granularity) {
+            (DiffLine::Removed(line), DiffGranularity::Word | DiffGranularity::Char) => {
+                removed_lines.push(*line)
+            }
+            (DiffLine::Added(line), _) if !removed_lines.is_empty() => {
+                let previous = removed_lines.remove(0);
+                lines.push(changed_line(previous, line, granularity));
+            }
+            _ => {
+                let removed = removed_lines.drain(..).map(DiffLine::Removed);
+                lines.extend(removed.map(|removed| diff_line_text(&removed)));
+                lines.push(diff_line_text(diff_line));
+            }
+        }
+    }
+    let removed = removed_lines.drain(..).map(DiffLine::Removed);
+    lines.extend(removed.map(|removed| diff_line_text(&removed)));
+    lines.into()
+}
+
+fn diff_line_text<'a>(diff_line: &DiffLine<'a>) -> Line<'a> {
+    match *diff_line {
+        DiffLine::Unchanged(line) => Line::raw(line),
+        DiffLine::Added(line) => Line::styled(format!("+ {line}"), Marker::Added.style()),
+        DiffLine::Removed(line) => Line::styled(format!("- {line}"), Marker::Removed.style()),
+        DiffLine::Elided(count) => Line::styled(
+            format!("⋯ {count} unchanged lines ⋯"),
+            Style::default().fg(tailwind::SLATE.c500),
+        )
+        .centered(),
+    }
+}
+
+/// Changed line of a diff, with its removed words or characters struck through before the
+/// added ones.
+fn changed_line<'a>(previous: &'a str, current: &'a str, granularity: DiffGranularity) -> Line<'a> {
+    let split: fn(&str) -> Vec<&str> = match granularity {
+        DiffGranularity::Char => split_chars,
+        _ => split_words,
+    };
+    let spans = token_diff(&split(previous), &split(current))
+        .into_iter()
+        .map(|span| match span {
+            DiffSpan::Unchanged(token) => Span::raw(token),
+            DiffSpan::Added(token) => {
+                Span::styled(token, Marker::Added.style().add_modifier(Modifier::BOLD))
+            }
+            DiffSpan::Removed(token) => Span::styled(
+                token,
+                Marker::Removed.style().add_modifier(Modifier::CROSSED_OUT),
+            ),
+        });
+    let marker = Span::styled(Marker::Changed.symbol(), Marker::Changed.style());
+    Line::from_iter(std::iter::once(marker).chain(spans))
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{palette::tailwind::Palette, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Row, Table},
+};
+
+/// Cheat sheet of the key bindings active in the current mode, opened with `?` and closed
+/// with any key.
+pub struct HelpPopup {
+    /// Current mode of the TUI, in the title of the popup.
+    mode: String,
+    bindings: Vec<(String, &'static str)>,
+}
+
+impl HelpPopup {
+    pub fn new(mode: String) -> Self {
+        Self {
+            mode,
+            bindings: Vec::new(),
+        }
+    }
+
+    pub fn bind(&mut self, keys: impl Into<String>, action: &'static str) {
+        self.bindings.push((keys.into(), action));
+    }
+
+    /// Bind the `keys`, along with the keys of the user config remapped onto them.
+    pub fn bind_remappable(
+        &mut self,
+        keys: &[char],
+        action: &'static str,
+        keybindings: &BTreeMap<char, char>,
+    ) {
+        let labels = keys
+            .iter()
+            .map(|&key| {
+                let mut label = key.to_string();
+                for (remapped_key, _) in keybindings.iter().filter(|(_, bound)| **bound == key) {
+                    label.push_str(&format!(", {remapped_key}"));
+                }
+                label
+            })
+            .collect::<Vec<_>>();
+        self.bind(labels.join(" / "), action);
+    }
+
+    pub fn widget(&self, palette: Palette) -> Table<'_> {
+        let rows = self.bindings.iter().map(|(keys, action)| {
+            Row::new([
+                Cell::from(keys.as_str().fg(palette.c400).bold()),
+                Cell::from(*action),
+            ])
+        });
+
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(palette.c500))
+            .title(format!("  Keys │ {}  ", self.mode).bold())
+            .title_bottom(" Any key to close ");
+
+        Table::new(rows, [Constraint::Length(18), Constraint::Fill(1)]).block(block)
+    }
+
+    /// Centered area of the popup within `area`.
+    pub fn area(&self, area: Rect) -> Rect {
+        let height = self.bindings.len() as u16 + 2;
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Length(64)])
+            .flex(Flex::Center)
+            .areas(area);
+        area
+    }
+}
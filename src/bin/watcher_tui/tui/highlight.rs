@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Text,
+};
+use regex::Regex;
+
+/// Style given to the output lines matching a regex, written `REGEX=STYLE` like `ERROR=red`
+/// or `^WARN=yellow,bold`. The style lists a foreground color, `bg:<color>` and modifiers
+/// among `bold`, `dim`, `italic`, `underlined` and `reversed`, separated by commas.
+#[derive(Clone)]
+pub struct HighlightRule {
+    regex: Regex,
+    style: Style,
+}
+
+fn parse_style(spec: &str) -> Result<Style, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .try_fold(Style::default(), |style, token| {
+            let parse_color = |color: &str| {
+                Color::from_str(color).map_err(|_| format!("unknown color `{color}`"))
+            };
+            Ok(match token {
+                "bold" => style.add_modifier(Modifier::BOLD),
+                "dim" => style.add_modifier(Modifier::DIM),
+                "italic" => style.add_modifier(Modifier::ITALIC),
+                "underlined" => style.add_modifier(Modifier::UNDERLINED),
+                "reversed" => style.add_modifier(Modifier::REVERSED),
+                _ => match token.strip_prefix("bg:") {
+                    Some(color) => style.bg(parse_color(color)?),
+                    None => style.fg(parse_color(token)?),
+                },
+            })
+        })
+}
+
+impl FromStr for HighlightRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        // Split on the last `=`, for the regex to contain some
+        let (pattern, style) = rule
+            .rsplit_once('=')
+            .ok_or_else(|| format!("invalid highlight `{rule}`, expected `REGEX=STYLE`"))?;
+        Ok(Self {
+            regex: Regex::new(pattern).map_err(|err| err.to_string())?,
+            style: parse_style(style)?,
+        })
+    }
+}
+
+/// Style the lines of the `text` with the first of the `rules` matching them.
+/// The styles of the spans, like the diff colors, stay on top.
+pub fn with_highlights<'a>(mut text: Text<'a>, rules: &[HighlightRule]) -> Text<'a> {
+    if rules.is_empty() {
+        return text;
+    }
+
+    for line in &mut text.lines {
+        let content = line
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect::<String>();
+        if let Some(rule) = rules.iter().find(|rule| rule.regex.is_match(&content)) {
+            line.style = line.style.patch(rule.style);
+        }
+    }
+    text
+}
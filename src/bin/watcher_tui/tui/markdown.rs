@@ -0,0 +1,142 @@
+use ratatui::{
+    style::{
+        palette::tailwind::{self, Palette},
+        Modifier, Style,
+    },
+    text::{Line, Span, Text},
+};
+
+fn code_style() -> Style {
+    Style::default()
+        .fg(tailwind::ORANGE.c300)
+        .bg(tailwind::SLATE.c900)
+}
+
+/// Spans of the inline markup of a `line`: `code`, **bold** and *italic* or _italic_.
+fn inline_spans(line: &str, base_style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        // Underscores within words, like in `snake_case`, are no delimiters
+        let is_intraword = plain.ends_with(|c: char| c.is_alphanumeric());
+        let (delimiter, style) = match c {
+            '`' => ("`", code_style()),
+            '*' if rest.starts_with("**") => ("**", base_style.add_modifier(Modifier::BOLD)),
+            '_' if is_intraword => {
+                plain.push(c);
+                rest = &rest[1..];
+                continue;
+            }
+            '_' if rest.starts_with("__") => ("__", base_style.add_modifier(Modifier::BOLD)),
+            '*' | '_' => (&rest[..1], base_style.add_modifier(Modifier::ITALIC)),
+            _ => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+
+        // Unclosed delimiters, or followed by a space, are kept as is
+        let inner = &rest[delimiter.len()..];
+        match inner.find(delimiter) {
+            Some(end) if end > 0 && !inner.starts_with(' ') => {
+                if !plain.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut plain), base_style));
+                }
+                spans.push(Span::styled(inner[..end].to_string(), style));
+                rest = &inner[end + delimiter.len()..];
+            }
+            _ => {
+                plain.push_str(delimiter);
+                rest = inner;
+            }
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span::styled(plain, base_style));
+    }
+    spans
+}
+
+/// Rendering of a markdown `output`, with styled headings, lists, quotes and code.
+pub fn markdown_text(output: &str, palette: Palette) -> Text<'static> {
+    let mut lines = Vec::new();
+    let mut is_in_code_block = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        if trimmed.starts_with("```") {
+            is_in_code_block = !is_in_code_block;
+            let language = trimmed.trim_start_matches('`').trim();
+            lines.push(Line::styled(
+                format!("{indent}╌╌╌ {language}"),
+                Style::default().fg(tailwind::SLATE.c500),
+            ));
+            continue;
+        }
+        if is_in_code_block {
+            lines.push(Line::styled(format!("  {line}"), code_style()));
+            continue;
+        }
+
+        let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            let mut style = Style::default()
+                .fg(palette.c300)
+                .add_modifier(Modifier::BOLD);
+            if heading_level == 1 {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            lines.push(Line::from(inline_spans(
+                trimmed[heading_level..].trim(),
+                style,
+            )));
+            continue;
+        }
+
+        if trimmed.len() >= 3 && trimmed.chars().all(|c| c == '-') {
+            lines.push(Line::styled(
+                "─".repeat(40),
+                Style::default().fg(tailwind::SLATE.c500),
+            ));
+            continue;
+        }
+
+        if let Some(quote) = trimmed.strip_prefix('>') {
+            let quote_style = Style::default()
+                .fg(tailwind::SLATE.c300)
+                .add_modifier(Modifier::ITALIC);
+            let mut spans = vec![Span::styled(format!("{indent}│ "), palette.c500)];
+            spans.extend(inline_spans(quote.trim_start(), quote_style));
+            lines.push(Line::from(spans));
+            continue;
+        }
+
+        // Bullets of the unordered lists, the ordered lists keep their numbers
+        let list_item = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| trimmed.strip_prefix(bullet))
+            .map(|item| ("•".to_string(), item))
+            .or_else(|| {
+                let (number, item) = trimmed.split_once(". ")?;
+                let is_number = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit());
+                is_number.then(|| (format!("{number}."), item))
+            });
+        if let Some((marker, item)) = list_item {
+            let mut spans = vec![Span::styled(
+                format!("{indent}{marker} "),
+                Style::default().fg(palette.c400),
+            )];
+            spans.extend(inline_spans(item, Style::default()));
+            lines.push(Line::from(spans));
+            continue;
+        }
+
+        lines.push(Line::from(inline_spans(line, Style::default())));
+    }
+    lines.into()
+}
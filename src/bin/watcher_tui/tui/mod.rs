@@ -1,92 +1,591 @@
 use std::{
+    io::Write,
+    process::{Command, Stdio},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
     },
     thread,
     time::{Duration, Instant},
 };
 
-use crossbeam_channel::Sender;
-use log::{debug, trace};
+use crate::{
+    hooks::Hooks,
+    output_log::OutputLog,
+    redact::{redact, redact_bytes, redact_owned},
+};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use log::{debug, info, trace, warn};
 use query::QueryState;
-use watch_rs::models::watcher::Watcher;
+use ratatui::{buffer::Buffer, style::Color};
+use watch_rs::{
+    models::{
+        json_diff::JsonPath,
+        shell::CancelHandle,
+        watcher::{ExecStatus, Watcher},
+        watcher_loop::{
+            wait_for_trigger, IterationResult, LoopEvent, Trigger, WatcherLoop, WatcherSchedule,
+        },
+    },
+    utils::OpenResult,
+};
 
+pub mod alert;
+pub mod format;
+pub mod help;
+pub mod highlight;
+pub mod markdown;
+pub mod picker;
+pub mod plot;
 pub mod query;
+pub mod record;
+pub mod search;
+pub mod selection;
+pub mod settings;
+pub mod summary;
+pub mod table;
+pub mod timeline;
 pub mod watcher;
 
 pub static TICK_RATE: Duration = Duration::from_millis(15);
 
+/// Redraws of an otherwise idle TUI, for its clocks, spinners and notices to keep moving.
+pub static IDLE_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Set on SIGTERM or SIGHUP, for the TUIs to shut down the way they do on Ctrl+C.
+pub static TERMINATION_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Events a watcher thread queues up for its pane, before dropping some of them.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Streamed outputs and run timings dropped once the queue of a watcher thread is full, like
+/// while the terminal is suspended. The results of the iterations are never dropped, the watcher
+/// thread waits for room for them instead.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DropPolicy {
+    /// Drop the oldest queued outputs and timings, for the pane to catch up with the latest ones.
+    #[default]
+    DropOldest,
+    /// Drop the new outputs and timings, keeping the queued ones.
+    DropNewest,
+}
+
+/// Events of a watcher thread, received by its pane.
+pub type EventReceiver = Arc<Receiver<WatcherOutputEvent>>;
+
+/// Sending end of the events of a watcher thread, dropping events once its queue is full.
+#[derive(Clone)]
+pub struct EventSender {
+    sender: Sender<WatcherOutputEvent>,
+    /// Receiving end of the pane, to drop the oldest events from. Held weakly for the channel
+    /// to disconnect once the pane is gone.
+    receiver: Weak<Receiver<WatcherOutputEvent>>,
+    policy: DropPolicy,
+    dropped_events: Arc<AtomicUsize>,
+}
+
+/// Bounded channel of the events of a watcher thread, following the drop `policy` once full.
+pub fn event_channel(policy: DropPolicy) -> (EventSender, EventReceiver) {
+    let (sender, receiver) = bounded(EVENT_CHANNEL_CAPACITY);
+    let receiver = Arc::new(receiver);
+    let event_sender = EventSender {
+        sender,
+        receiver: Arc::downgrade(&receiver),
+        policy,
+        dropped_events: Arc::default(),
+    };
+    (event_sender, receiver)
+}
+
+impl EventSender {
+    /// Send the `event`, dropping streamed outputs and timings per the policy when the queue is
+    /// full. The other events are never dropped, waiting for room instead. Returns whether the
+    /// receiving pane is still there.
+    pub fn send(&self, event: WatcherOutputEvent) -> bool {
+        let event = match self.sender.try_send(event) {
+            Ok(()) => return true,
+            Err(TrySendError::Disconnected(_)) => return false,
+            Err(TrySendError::Full(event)) => event,
+        };
+        if self.policy == DropPolicy::DropOldest {
+            self.drop_queued();
+        }
+
+        match event.is_droppable() {
+            true => match self.sender.try_send(event) {
+                Ok(()) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped_events.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+            },
+            false => self.sender.send(event).is_ok(),
+        }
+    }
+
+    /// Drop the streamed outputs and timings of the queue, the other events are queued back in
+    /// their order. The watcher thread is the only sender, nothing gets queued meanwhile.
+    fn drop_queued(&self) {
+        let Some(receiver) = self.receiver.upgrade() else {
+            return;
+        };
+        let (dropped, kept): (Vec<_>, Vec<_>) = receiver
+            .try_iter()
+            .partition(WatcherOutputEvent::is_droppable);
+        self.dropped_events
+            .fetch_add(dropped.len(), Ordering::Relaxed);
+        for event in kept {
+            // The pane receives the events meanwhile, making room rather than taking it
+            if self.sender.send(event).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Count of the events dropped so far, shared with the pane.
+    pub fn dropped_events(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.dropped_events)
+    }
+}
+
+/// Watchers of a command along with the titles of their panes, one per host in fan-out mode.
+pub type TitledWatchers = Vec<(Option<String>, Watcher)>;
+
+#[derive(Clone)]
 pub struct WatcherIterationOutput {
-    iteration: usize,
-    output: String,
+    pub iteration: usize,
+    pub output: String,
+    /// Bytes of the output as the command printed them, when they differ from the UTF-8 bytes
+    /// of `output`, like the binary outputs. Left out once a transform rewrote the output.
+    pub raw_output: Option<Vec<u8>>,
+    pub status: ExecStatus,
+    pub exit_code: Option<i32>,
+    /// Effective interval before the next iteration.
+    pub interval: Duration,
+    /// Time the command took to run.
+    pub run_duration: Duration,
+    /// When the command started and finished running.
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    /// Size of the output of the command in bytes, ahead of the transform and post-processing.
+    pub output_bytes: usize,
+    /// Bytes the output got cut at, past `--max-output-bytes`.
+    pub truncated_at: Option<usize>,
+    /// Whether the output changed from the one of the previous iteration, per the comparator.
+    pub has_changed: bool,
+}
+
+impl IterationResult for WatcherIterationOutput {
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    fn is_success(&self) -> bool {
+        self.status == ExecStatus::Completed && self.exit_code == Some(0)
+    }
+}
+
+/// Post-processing of the outputs in the watcher thread, ahead of their comparison and display.
+#[derive(Clone, Default)]
+pub struct OutputPipeline {
+    /// Only keep this subtree of the JSON outputs, pretty-printed.
+    pub json_path: Option<JsonPath>,
+    /// Pretty-print the JSON outputs, with their keys sorted.
+    pub pretty_json: bool,
+}
+
+impl OutputPipeline {
+    /// Whether the outputs are passed through untouched, rewriting them needs them complete.
+    fn is_identity(&self) -> bool {
+        self.json_path.is_none() && !self.pretty_json
+    }
+
+    fn process(&self, output: String) -> String {
+        // Outputs which are not JSON are left as is
+        let output = match &self.json_path {
+            Some(json_path) => match serde_json::from_str(&output) {
+                Ok(value) => serde_json::to_string_pretty(&json_path.select(&value))
+                    .map_or(output, |selected| selected + "\n"),
+                Err(_) => output,
+            },
+            None => output,
+        };
+        match self.pretty_json {
+            true => format::pretty_json(&output).unwrap_or(output),
+            false => output,
+        }
+    }
+}
+
+/// Drop the colors of the rendered `buffer`, keeping the bold, reversed and other modifiers,
+/// for the monochrome rendering of `--no-color` and `NO_COLOR`.
+pub fn strip_colors(buffer: &mut Buffer) {
+    for cell in &mut buffer.content {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
 }
 
 pub enum WatcherOutputEvent {
     SetupResult(WatcherIterationOutput),
+    /// The shell got recreated, with the result of its setup commands and the handle cancelling
+    /// its commands.
+    ShellRestarted(WatcherIterationOutput, Option<CancelHandle>),
+    /// An iteration started running at this instant.
+    RunStarted(Instant),
+    /// The watcher thread sleeps until this instant, before running the next iteration.
+    Sleeping(Instant),
+    /// The watcher thread waits for a change of the watched files to run the next iteration.
+    WaitingForChange,
+    /// New complete lines of output of the running iteration, following the ones streamed before.
+    /// Dropped ones leave a gap in the streamed output, until the result of the iteration.
+    PartialOutput(String),
     IterationResult(WatcherIterationOutput),
+    /// The watch failed, like when its shell can not be driven anymore. It waits to be retried
+    /// in a fresh shell, or to be closed.
+    Error(String),
     End,
 }
 
+impl WatcherOutputEvent {
+    /// Whether the event can be dropped once the queue is full, the next events superseding it.
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            WatcherOutputEvent::PartialOutput(_)
+                | WatcherOutputEvent::RunStarted(_)
+                | WatcherOutputEvent::Sleeping(_)
+        )
+    }
+}
+
+/// Pipe the `output` through the `transform` shell snippet, run locally.
+/// Failures of the transform are reported in place of the output.
+fn transform_output(transform: &str, output: String) -> String {
+    let transformed = Command::new("/bin/bash")
+        .args(["-c", transform])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            // Written from another thread, as the transform may output before reading all of its input
+            let mut stdin = child.stdin.take().unwrap();
+            let writer = thread::spawn(move || stdin.write_all(output.as_bytes()));
+            let transformed = child.wait_with_output()?;
+            writer.join().ok();
+            Ok(transformed)
+        });
+
+    match transformed {
+        Ok(transformed) if transformed.status.success() => {
+            String::from_utf8_lossy(&transformed.stdout).into_owned()
+        }
+        Ok(transformed) => format!(
+            "Transform failed with {}:\n{}",
+            transformed.status,
+            String::from_utf8_lossy(&transformed.stderr)
+        ),
+        Err(err) => format!("Transform failed to run: {err}"),
+    }
+}
+
+/// Recreates the shell of a watcher thread on request, for when it got into a bad state like a
+/// broken working directory or a stuck variable.
+pub struct ShellRestart {
+    /// Spawns a fresh shell, like the one the watch started with.
+    pub spawn: Box<dyn Fn() -> OpenResult<Watcher> + Send>,
+    /// Set to restart the shell before the next iteration, which then runs right away.
+    pub requested: Arc<AtomicBool>,
+}
+
+/// Fires once a restart of the shell got requested, the restart itself clearing the request.
+#[derive(Clone)]
+struct ShellRestartTrigger(Arc<AtomicBool>);
+
+impl Trigger for ShellRestartTrigger {
+    fn name(&self) -> &'static str {
+        "shell restart"
+    }
+
+    fn poll(&mut self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Export the variables and run the setup commands of the `query_state` in a fresh shell.
+fn prepare_shell(
+    watcher: &mut Watcher,
+    query_state: &QueryState,
+    interval: Duration,
+) -> OpenResult<WatcherIterationOutput> {
+    // Exported ahead of the setup commands, so that these can use the variables as well
+    for (key, value) in &query_state.env_vars {
+        watcher.export_env(key, value)?;
+    }
+
+    // If set, add the setup commands in the shell
+    debug!("Executing setup commands : {}", query_state.setup_commands);
+    let setup_start = Instant::now();
+    let started_at = Local::now();
+    let setup_result = watcher.exec_cmd_and_fetch_output(&query_state.setup_commands)?;
+    if !setup_result.is_success() {
+        warn!(
+            "The setup commands failed ({}) : {}",
+            match setup_result.exit_code {
+                Some(exit_code) => format!("exit {exit_code}"),
+                None => setup_result.status.to_string(),
+            },
+            redact(setup_result.output.trim())
+        );
+    }
+    Ok(WatcherIterationOutput {
+        iteration: 0,
+        output_bytes: setup_result.raw_output.len(),
+        truncated_at: setup_result.truncated_at,
+        raw_output: None,
+        output: redact_owned(setup_result.output),
+        status: setup_result.status,
+        exit_code: setup_result.exit_code,
+        interval,
+        run_duration: setup_start.elapsed(),
+        started_at,
+        finished_at: Local::now(),
+        has_changed: false,
+    })
+}
+
+/// Run the `commands` in the shell of the `watcher` aside from the watched ones, with their
+/// output only logged. Failures are only logged as well, not to stop the watch.
+fn run_aside_commands(watcher: &mut Watcher, description: &str, commands: &str) {
+    if commands.trim().is_empty() {
+        return;
+    }
+    trace!("Running the {description} : {commands}");
+    match watcher.exec_cmd_and_fetch_output(commands) {
+        Ok(result) if result.is_success() => {}
+        Ok(result) => warn!(
+            "The {description} failed ({}) : {}",
+            match result.exit_code {
+                Some(exit_code) => format!("exit {exit_code}"),
+                None => result.status.to_string(),
+            },
+            redact(result.output.trim())
+        ),
+        Err(err) => warn!("The {description} failed to run : {err}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_watcher_thread(
     mut watcher: Watcher,
     query_state: QueryState,
-    interval: Duration,
-    watch_duration: Option<Duration>,
-    watcher_event_sender: Sender<WatcherOutputEvent>,
+    mut schedule: WatcherSchedule,
+    pipeline: OutputPipeline,
+    output_log: Option<OutputLog>,
+    mut hooks: Hooks,
+    watcher_event_sender: EventSender,
     should_close_watcher: Arc<AtomicBool>,
+    shell_restart: Option<ShellRestart>,
+    stream_output: bool,
 ) {
     thread::spawn(move || {
-        // If set, add the setup commands in the shell
-        debug!("Executing setup commands : {}", query_state.setup_commands);
-        let captured_stdout = watcher
-            .exec_cmd_and_fetch_output(&query_state.setup_commands)
-            .unwrap();
-        watcher_event_sender
-            .send(WatcherOutputEvent::SetupResult(WatcherIterationOutput {
-                iteration: 0,
-                output: captured_stdout,
-            }))
-            .unwrap();
-
-        let watcher_start_checkpoint = Instant::now();
-        let mut iteration = 0;
-
-        // Execute the watcher command in the shell in a loop
+        let interval = schedule.interval;
+        if let Some(shell_restart) = &shell_restart {
+            schedule
+                .triggers
+                .push(Box::new(ShellRestartTrigger(Arc::clone(
+                    &shell_restart.requested,
+                ))));
+        }
+
+        // Partial outputs are only streamed when rendered as is, transforms and the
+        // post-processing need the full output
+        let is_streamed =
+            stream_output && query_state.transform.trim().is_empty() && pipeline.is_identity();
+        let mut setup_result = prepare_shell(&mut watcher, &query_state, interval);
+        if let Ok(setup_result) = &setup_result {
+            watcher_event_sender.send(WatcherOutputEvent::SetupResult(setup_result.clone()));
+        }
+        // Iterations run before the watch failed, the retried one carrying on from them
+        let mut iteration_offset = 0;
         loop {
-            iteration += 1;
-            let captured_stdout = watcher
-                .exec_cmd_and_fetch_output(&query_state.main_commands)
-                .unwrap();
-
-            trace!("STDIN  > {}", query_state.main_commands);
-            trace!("STDOUT = {}", captured_stdout);
-
-            watcher_event_sender
-                .try_send(WatcherOutputEvent::IterationResult(
-                    WatcherIterationOutput {
-                        iteration,
-                        output: captured_stdout,
-                    },
-                ))
-                .unwrap();
-
-            if should_close_watcher.load(Ordering::Acquire) {
-                break;
-            }
+            let end = match setup_result {
+                Err(err) => Err((0, err)),
+                Ok(_) => WatcherLoop::new(schedule.clone())
+                    .with_stop_handle(Arc::clone(&should_close_watcher))
+                    .run(
+                        |iteration| {
+                            let iteration = iteration_offset + iteration;
+                            let restart_requested =
+                                shell_restart.as_ref().filter(|shell_restart| {
+                                    shell_restart.requested.swap(false, Ordering::AcqRel)
+                                });
+                            if let Some(shell_restart) = restart_requested {
+                                // The previous shell might be stuck, it is killed without its teardown
+                                info!("Restarting the shell before iteration {iteration}");
+                                watcher.kill().ok();
+                                watcher = (shell_restart.spawn)()?;
+                                let setup_result =
+                                    prepare_shell(&mut watcher, &query_state, interval)?;
+                                watcher_event_sender.send(WatcherOutputEvent::ShellRestarted(
+                                    setup_result,
+                                    watcher.cancel_handle(),
+                                ));
+                            }
+
+                            run_aside_commands(
+                                &mut watcher,
+                                &format!("before-each commands of iteration {iteration}"),
+                                &query_state.before_each,
+                            );
+                            let run_start = Instant::now();
+                            let started_at = Local::now();
+                            watcher_event_sender.send(WatcherOutputEvent::RunStarted(run_start));
+
+                            let mut result = watcher.exec_cmd_streaming(
+                                &query_state.main_commands,
+                                |output| {
+                                    if is_streamed {
+                                        watcher_event_sender.send(
+                                            WatcherOutputEvent::PartialOutput(
+                                                redact(output).into_owned(),
+                                            ),
+                                        );
+                                    }
+                                },
+                            )?;
+                            let run_duration = run_start.elapsed();
+                            let finished_at = Local::now();
+                            let output_bytes = result.raw_output.len();
+                            result.output = redact_owned(result.output);
+                            let raw_output = Some(redact_bytes(result.raw_output))
+                                .filter(|raw_output| raw_output != result.output.as_bytes());
+                            run_aside_commands(
+                                &mut watcher,
+                                &format!("after-each commands of iteration {iteration}"),
+                                &query_state.after_each,
+                            );
+
+                            let mut iteration_output = WatcherIterationOutput {
+                                iteration,
+                                output: result.output,
+                                raw_output,
+                                status: result.status,
+                                exit_code: result.exit_code,
+                                interval: Duration::ZERO,
+                                run_duration,
+                                started_at,
+                                finished_at,
+                                output_bytes,
+                                truncated_at: result.truncated_at,
+                                has_changed: false,
+                            };
 
-            // Break if a we have exceeded a 'watch duration' specified
-            if let Some(duration) = &watch_duration {
-                if duration < &watcher_start_checkpoint.elapsed() {
+                            trace!("STDIN  > {}", query_state.main_commands);
+                            trace!("STDOUT = {}", iteration_output.output);
+                            if let Some(output_log) = &output_log {
+                                output_log.append(&iteration_output);
+                            }
+
+                            if !query_state.transform.trim().is_empty() || !pipeline.is_identity() {
+                                iteration_output.raw_output = None;
+                            }
+                            if !query_state.transform.trim().is_empty() {
+                                iteration_output.output = transform_output(
+                                    &query_state.transform,
+                                    iteration_output.output,
+                                );
+                            }
+                            iteration_output.output = pipeline.process(iteration_output.output);
+                            if iteration_output.status != ExecStatus::Completed {
+                                warn!("Iteration {iteration} {}", iteration_output.status);
+                            }
+                            Ok(iteration_output)
+                        },
+                        |event| match event {
+                            LoopEvent::Ran {
+                                mut result,
+                                interval,
+                                changed_from,
+                                ..
+                            } => {
+                                result.interval = interval;
+                                result.has_changed = changed_from.is_some();
+                                hooks.after_iteration(
+                                    &query_state.main_commands,
+                                    &result,
+                                    changed_from.as_deref(),
+                                );
+                                // The receiving pane is gone once the watch got restarted with edited
+                                // commands
+                                watcher_event_sender
+                                    .send(WatcherOutputEvent::IterationResult(result))
+                            }
+                            LoopEvent::Sleeping(until) => {
+                                watcher_event_sender.send(WatcherOutputEvent::Sleeping(until));
+                                true
+                            }
+                            LoopEvent::WaitingForTrigger => {
+                                watcher_event_sender.send(WatcherOutputEvent::WaitingForChange);
+                                true
+                            }
+                        },
+                    ),
+            };
+            let err = match end {
+                Ok(end) => {
+                    debug!(
+                        "Watch ended after {} iterations : {:?}",
+                        iteration_offset + end.iterations,
+                        end.reason
+                    );
                     break;
                 }
+                Err((iterations, err)) => {
+                    iteration_offset += iterations;
+                    warn!("Watch failed after {iteration_offset} iterations : {err}");
+                    err
+                }
+            };
+            watcher_event_sender.send(WatcherOutputEvent::Error(err.to_string()));
+
+            // Failed watches wait to be retried in a fresh shell, or to be closed
+            let Some(shell_restart) = &shell_restart else {
+                break;
+            };
+            let mut retry_trigger: [Box<dyn Trigger>; 1] = [Box::new(ShellRestartTrigger(
+                Arc::clone(&shell_restart.requested),
+            ))];
+            if wait_for_trigger(&mut retry_trigger, &should_close_watcher).is_none() {
+                break;
+            }
+            shell_restart.requested.store(false, Ordering::Release);
+
+            info!("Retrying the watch in a fresh shell");
+            watcher.kill().ok();
+            setup_result = (shell_restart.spawn)().and_then(|restarted| {
+                watcher = restarted;
+                prepare_shell(&mut watcher, &query_state, interval)
+            });
+            if let Ok(setup_result) = &setup_result {
+                watcher_event_sender.send(WatcherOutputEvent::ShellRestarted(
+                    setup_result.clone(),
+                    watcher.cancel_handle(),
+                ));
             }
-            thread::sleep(interval);
         }
 
-        watcher_event_sender
-            .try_send(WatcherOutputEvent::End)
-            .unwrap();
-        watcher.kill().unwrap();
+        // Teardown commands run in the shell of the setup commands, before it gets killed
+        run_aside_commands(
+            &mut watcher,
+            "teardown commands",
+            &query_state.teardown_commands,
+        );
+
+        watcher_event_sender.send(WatcherOutputEvent::End);
+        watcher.kill().ok();
     });
 }
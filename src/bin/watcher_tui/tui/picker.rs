@@ -0,0 +1,122 @@
+use std::{fmt::Write, iter};
+
+use ratatui::{
+    style::{
+        palette::tailwind::{self, Palette},
+        Style, Stylize,
+    },
+    text::{Line, Span},
+    widgets::{Block, BorderType, List, ListState},
+};
+use watch_rs::models::watcher::ExecStatus;
+
+use super::format::Locale;
+use crate::report::SessionHistory;
+
+/// Columns of the side panel of the iteration picker.
+pub const PICKER_WIDTH: u16 = 48;
+
+/// Side panel listing the live output, then the recorded iterations of the first pane newest
+/// first, toggled with `i`. The selected iteration is loaded into the panes with `Enter`.
+#[derive(Default)]
+pub struct IterationPicker {
+    /// Iteration of the selected entry, `None` for the live output. Kept by number rather
+    /// than by position, for the selection to stay put as the new iterations come in.
+    pub selected: Option<usize>,
+}
+
+impl IterationPicker {
+    fn entries(history: &SessionHistory) -> Vec<Option<usize>> {
+        iter::once(None)
+            .chain(
+                history
+                    .iterations()
+                    .iter()
+                    .rev()
+                    .map(|it| Some(it.iteration)),
+            )
+            .collect()
+    }
+
+    /// Move the selection by `offset` entries, towards the older iterations when positive.
+    pub fn move_selection(&mut self, history: &SessionHistory, offset: isize) {
+        let entries = Self::entries(history);
+        let index = entries
+            .iter()
+            .position(|entry| *entry == self.selected)
+            .unwrap_or(0);
+        let index = index.saturating_add_signed(offset).min(entries.len() - 1);
+        self.selected = entries[index];
+    }
+
+    /// List of the entries, with the state selecting the current one.
+    pub fn widget(
+        &self,
+        history: &SessionHistory,
+        locale: &Locale,
+        palette: Palette,
+    ) -> (List<'static>, ListState) {
+        let live = Line::from("● live output".fg(palette.c400).bold());
+        let recorded = history
+            .iterations()
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, it)| {
+                let change = match it.output_changed {
+                    true => Span::raw("Δ").fg(palette.c400),
+                    false => Span::raw("="),
+                };
+                let status = match (&it.status, it.exit_code) {
+                    (ExecStatus::Completed, Some(exit_code)) => format!("exit {exit_code}"),
+                    (status, _) => status.to_string(),
+                };
+                let mut line = Line::from(vec![
+                    Span::raw(format!("{:>6} ", locale.format_integer(it.iteration))),
+                    Span::raw(format!("{} ", it.finished_at.format("%H:%M:%S"))),
+                    Span::raw(format!("{:>7} ", locale.format_duration(it.run_duration))),
+                    change,
+                    Span::raw(format!(" {status}")),
+                ]);
+                // The cap is in the title of the pane, the list being narrow
+                if it.truncated_at.is_some() {
+                    line.push_span(Span::raw(" ✂ truncated").fg(tailwind::AMBER.c300));
+                }
+                // Only the metadata of the evicted iterations is left, they can not be loaded
+                match (history.is_evicted(index), it.is_success()) {
+                    (true, _) => line.fg(tailwind::SLATE.c600),
+                    (false, true) => line,
+                    (false, false) => line.fg(tailwind::RED.c400),
+                }
+            });
+
+        let selected = Self::entries(history)
+            .iter()
+            .position(|entry| *entry == self.selected)
+            .unwrap_or(0);
+        let mut title = format!(
+            "  Iterations │ {}",
+            locale.format_integer(history.iterations().len())
+        );
+        if let Some((first, last)) = history.retained_range() {
+            write!(
+                title,
+                " │ outputs of {}-{}",
+                locale.format_integer(first),
+                locale.format_integer(last)
+            )
+            .unwrap();
+        }
+        title.push_str("  ");
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(palette.c500))
+            .title(title.bold())
+            .title_bottom(" ▲ ▼ select │ Enter load │ Esc close ");
+        let list = List::new(iter::once(live).chain(recorded))
+            .block(block)
+            .highlight_style(Style::default().bg(palette.c900).bold())
+            .highlight_symbol("▶ ");
+        (list, ListState::default().with_selected(Some(selected)))
+    }
+}
@@ -0,0 +1,148 @@
+use std::{str::FromStr, time::Duration};
+
+use ratatui::{
+    layout::Constraint,
+    style::{
+        palette::tailwind::{self, Palette},
+        Style, Stylize,
+    },
+    symbols::Marker,
+    text::Span,
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType},
+};
+
+use super::format::Locale;
+
+/// Samples kept per pane, the older ones scroll off the left of the chart.
+const MAX_SAMPLES: usize = 600;
+
+/// Extracts a number from the output of each iteration, with a regex matching it
+/// or capturing it in its first group.
+#[derive(Clone)]
+pub struct MetricExtractor(regex::Regex);
+
+impl FromStr for MetricExtractor {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        regex::Regex::new(pattern)
+            .map(Self)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl MetricExtractor {
+    pub fn pattern(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// First value matched in the `output`, if it parses as a number.
+    pub fn extract(&self, output: &str) -> Option<f64> {
+        let captures = self.0.captures(output)?;
+        let metric = captures.get(1).or_else(|| captures.get(0))?;
+        metric.as_str().trim().parse().ok()
+    }
+}
+
+/// Values extracted from the iterations of a pane, along the time elapsed since the watch started.
+#[derive(Default)]
+pub struct MetricSeries {
+    samples: Vec<(f64, f64)>,
+}
+
+impl MetricSeries {
+    pub fn push(&mut self, elapsed: Duration, value: f64) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.remove(0);
+        }
+        self.samples.push((elapsed.as_secs_f64(), value));
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.samples.last().map(|&(_, value)| value)
+    }
+}
+
+/// Bounds of the `values`, widened when they are all equal for the chart to stay drawable.
+fn bounds(values: impl Iterator<Item = f64>, padding_ratio: f64) -> [f64; 2] {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if !min.is_finite() {
+        return [0.0, 1.0];
+    }
+
+    let range = max - min;
+    match range > 0.0 {
+        true => [min - range * padding_ratio, max + range * padding_ratio],
+        false => [min - 1.0, max + 1.0],
+    }
+}
+
+/// Time-series chart of the metric extracted from every pane, one line per pane.
+pub fn metric_chart<'a>(
+    extractor: &MetricExtractor,
+    series: &'a [(Option<&'a str>, &'a MetricSeries)],
+    palette: Palette,
+    locale: &Locale,
+) -> Chart<'a> {
+    let colors = [
+        palette.c400,
+        tailwind::SKY.c400,
+        tailwind::AMBER.c400,
+        tailwind::ROSE.c400,
+        tailwind::VIOLET.c400,
+    ];
+    let datasets = series
+        .iter()
+        .enumerate()
+        .map(|(index, (title, series))| {
+            let latest = series
+                .latest()
+                .map_or_else(|| "-".to_string(), |value| locale.format_decimal(value, 2));
+            let name = match title {
+                Some(title) => format!("{title}: {latest}"),
+                None => format!("latest: {latest}"),
+            };
+            Dataset::default()
+                .name(name)
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(colors[index % colors.len()]))
+                .data(&series.samples)
+        })
+        .collect::<Vec<_>>();
+
+    let samples = || series.iter().flat_map(|(_, series)| &series.samples);
+    let [x_min, x_max] = bounds(samples().map(|&(elapsed, _)| elapsed), 0.0);
+    let [y_min, y_max] = bounds(samples().map(|&(_, value)| value), 0.05);
+
+    let elapsed_label =
+        |secs: f64| humantime::format_duration(Duration::from_secs(secs as u64)).to_string();
+    let x_axis = Axis::default()
+        .style(Style::default().fg(tailwind::SLATE.c400))
+        .bounds([x_min, x_max])
+        .labels([
+            Span::raw(elapsed_label(x_min)),
+            Span::raw(elapsed_label(x_max)),
+        ]);
+    let y_axis = Axis::default()
+        .style(Style::default().fg(tailwind::SLATE.c400))
+        .bounds([y_min, y_max])
+        .labels([
+            Span::raw(locale.format_decimal(y_min, 2)),
+            Span::raw(locale.format_decimal((y_min + y_max) / 2.0, 2)),
+            Span::raw(locale.format_decimal(y_max, 2)),
+        ]);
+
+    let block = Block::default()
+        .borders(Borders::TOP)
+        .border_style(Style::default().fg(palette.c500))
+        .title(format!("  📈 {}  ", extractor.pattern()).fg(palette.c200));
+
+    Chart::new(datasets)
+        .block(block)
+        .x_axis(x_axis)
+        .y_axis(y_axis)
+        .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)))
+}
@@ -5,24 +5,37 @@ use ratatui::{
     style::{palette::tailwind, Color, Stylize},
     symbols,
     text::Line,
-    widgets::{Block, Padding, Paragraph, Tabs, Widget},
+    widgets::{Block, Clear, Padding, Paragraph, Tabs, Widget},
     Frame, Terminal,
 };
+use std::sync::atomic::Ordering;
 use std::time::Instant;
 use std::{io, time::Duration};
 use strum::{Display, EnumIter, FromRepr, IntoEnumIterator};
 use tui_textarea::TextArea;
 
-use super::TICK_RATE;
+use watch_rs::utils::parse_env_var;
 
+use super::{help::HelpPopup, strip_colors, TERMINATION_REQUESTED, TICK_RATE};
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Default, Clone, Copy, EnumIter, Display, FromRepr)]
 enum QueryEditTab {
+    #[strum(to_string = "Env Tab")]
+    ENV,
+
     #[strum(to_string = "Setup Tab")]
     SETUP,
 
     #[default]
     #[strum(to_string = "Main Tab")]
     MAIN,
+
+    #[strum(to_string = "Transform Tab")]
+    TRANSFORM,
+
+    #[strum(to_string = "Teardown Tab")]
+    TEARDOWN,
 }
 
 impl QueryEditTab {
@@ -57,12 +70,16 @@ impl QueryEditTab {
 
     const fn palette(self) -> tailwind::Palette {
         match self {
+            Self::ENV => tailwind::VIOLET,
             Self::SETUP => tailwind::BLUE,
             Self::MAIN => tailwind::EMERALD,
+            Self::TRANSFORM => tailwind::AMBER,
+            Self::TEARDOWN => tailwind::ROSE,
         }
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum QueryMode {
     NORMAL,
@@ -70,67 +87,138 @@ enum QueryMode {
     SUBMIT,
 }
 
+#[derive(Clone)]
 pub struct QueryState {
+    /// Environment variables exported in the shell before the setup commands.
+    pub env_vars: Vec<(String, String)>,
     pub setup_commands: String,
     pub main_commands: String,
+    /// Commands executed once in the shell after the last iteration, like deleting the
+    /// temporary resources of the setup commands.
+    pub teardown_commands: String,
+    /// Shell snippet (like `jq .items` or `grep -v DEBUG`) the output of each iteration
+    /// is piped through before display, no transformation when empty.
+    pub transform: String,
+    /// Commands run in the shell before and after the main commands of every iteration,
+    /// with `--before-each` and `--after-each`. Their outputs are only logged.
+    pub before_each: String,
+    pub after_each: String,
 }
 
 pub struct QueryTui {
     state: QueryState,
     editing_tab: QueryEditTab,
     running_mode: QueryMode,
+    /// Reason the last submit got rejected, shown in the footer.
+    submit_error: Option<String>,
+    is_monochrome: bool,
+    /// Cheat sheet of the key bindings, opened with `?` out of the editor.
+    show_help: bool,
 }
 
 impl QueryTui {
-    pub fn new(commands: Option<String>) -> Self {
+    pub fn new(state: QueryState) -> Self {
         Self {
-            state: QueryState {
-                main_commands: commands.unwrap_or_else(|| String::new()),
-                setup_commands: String::new(),
-            },
+            state,
             editing_tab: QueryEditTab::default(),
             running_mode: QueryMode::NORMAL,
+            submit_error: None,
+            is_monochrome: false,
+            show_help: false,
         }
     }
 
+    /// Parse the `KEY=VALUE` lines of the ENV tab, skipping blank and `#` comment lines.
+    fn parse_env_lines(lines: &[String]) -> Result<Vec<(String, String)>, String> {
+        lines
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_env_var)
+            .collect()
+    }
+
+    /// Render without colors, keeping the modifiers.
+    pub fn with_monochrome(mut self, is_monochrome: bool) -> Self {
+        self.is_monochrome = is_monochrome;
+        self
+    }
+
     pub fn run_app(
         mut self,
         terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     ) -> Result<Option<QueryState>, std::io::Error> {
         let mut last_tick = Instant::now();
+        let mut env_textarea = TextArea::from(
+            self.state
+                .env_vars
+                .iter()
+                .map(|(key, value)| format!("{key}={value}")),
+        );
         let mut setup_textarea = TextArea::from(self.state.setup_commands.lines());
         let mut main_textarea = TextArea::from(self.state.main_commands.lines());
+        let mut transform_textarea = TextArea::from(self.state.transform.lines());
+        let mut teardown_textarea = TextArea::from(self.state.teardown_commands.lines());
 
         loop {
             terminal.draw(|f| {
                 self.draw_ui(
                     f,
                     match self.editing_tab {
-                        QueryEditTab::MAIN => &mut main_textarea,
+                        QueryEditTab::ENV => &mut env_textarea,
                         QueryEditTab::SETUP => &mut setup_textarea,
+                        QueryEditTab::MAIN => &mut main_textarea,
+                        QueryEditTab::TRANSFORM => &mut transform_textarea,
+                        QueryEditTab::TEARDOWN => &mut teardown_textarea,
                     },
-                )
+                );
+                if self.is_monochrome {
+                    strip_colors(f.buffer_mut());
+                }
             })?;
 
+            if TERMINATION_REQUESTED.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+
             let timeout = TICK_RATE
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
             if term_event::poll(timeout)? {
-                match term_event::read()? {
-                    Event::Key(key) => match key {
+                if let Event::Key(key) = term_event::read()? {
+                    match key {
                         KeyEvent {
                             modifiers: KeyModifiers::CONTROL,
                             code: KeyCode::Char('c'),
                             ..
                         } => return Ok(None),
+                        _ if self.show_help => self.show_help = false,
+                        KeyEvent {
+                            modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                            code: KeyCode::Char('?'),
+                            ..
+                        } if self.running_mode != QueryMode::EDITOR => {
+                            self.show_help = true;
+                        }
                         KeyEvent {
                             modifiers: KeyModifiers::NONE,
                             code: KeyCode::Enter,
                             ..
                         } if self.running_mode == QueryMode::SUBMIT => {
+                            match Self::parse_env_lines(env_textarea.lines()) {
+                                Ok(env_vars) => self.state.env_vars = env_vars,
+                                Err(err) => {
+                                    self.submit_error = Some(err);
+                                    self.editing_tab = QueryEditTab::ENV;
+                                    self.running_mode = QueryMode::NORMAL;
+                                    continue;
+                                }
+                            }
                             self.state.main_commands = main_textarea.lines().join("\n");
+                            self.state.transform = transform_textarea.lines().join("\n");
                             self.state.setup_commands = setup_textarea.lines().join("\n");
+                            self.state.teardown_commands = teardown_textarea.lines().join("\n");
 
                             return Ok(Some(self.state));
                         }
@@ -148,6 +236,7 @@ impl QueryTui {
                             ..
                         } if self.running_mode != QueryMode::EDITOR => {
                             self.running_mode = QueryMode::EDITOR;
+                            self.submit_error = None;
                         }
                         KeyEvent {
                             modifiers: KeyModifiers::NONE,
@@ -174,13 +263,15 @@ impl QueryTui {
 
                         _ if self.running_mode == QueryMode::EDITOR => {
                             match self.editing_tab {
-                                QueryEditTab::MAIN => main_textarea.input(key),
+                                QueryEditTab::ENV => env_textarea.input(key),
                                 QueryEditTab::SETUP => setup_textarea.input(key),
+                                QueryEditTab::MAIN => main_textarea.input(key),
+                                QueryEditTab::TRANSFORM => transform_textarea.input(key),
+                                QueryEditTab::TEARDOWN => teardown_textarea.input(key),
                             };
                         }
                         _ => {}
-                    },
-                    _ => {}
+                    }
                 }
             }
 
@@ -195,11 +286,18 @@ impl QueryTui {
     }
 
     fn footer_widget(&self) -> impl Widget {
+        if let Some(submit_error) = &self.submit_error {
+            return Line::raw(format!("✘ {submit_error}"))
+                .fg(tailwind::RED.c400)
+                .centered();
+        }
+
         let mut components = Vec::new();
         match self.running_mode {
             QueryMode::NORMAL => {
                 components.push("◄ ► to change tab");
                 components.push("(I) to enter insert mode");
+                components.push("? for help");
             }
             QueryMode::EDITOR => {
                 components.push("↲ Esc to pause editor");
@@ -211,6 +309,31 @@ impl QueryTui {
         Line::raw(components.join(" │ ")).centered()
     }
 
+    /// Key bindings active in the current mode.
+    fn help_popup(&self) -> HelpPopup {
+        let mode = match self.running_mode {
+            QueryMode::NORMAL => "Normal",
+            QueryMode::EDITOR => "Editor",
+            QueryMode::SUBMIT => "Submit",
+        };
+        let mut help = HelpPopup::new(format!("{mode} │ {}", self.editing_tab));
+        match self.running_mode {
+            QueryMode::NORMAL => {
+                help.bind("◄ ►", "Change tab");
+                help.bind("i", "Edit the tab");
+                help.bind("↲", "Review the query to submit");
+            }
+            QueryMode::EDITOR => help.bind("Esc", "Pause the editor"),
+            QueryMode::SUBMIT => {
+                help.bind("↲", "Start watching");
+                help.bind("i", "Back to editing");
+            }
+        }
+        help.bind("?", "Toggle this help");
+        help.bind("Ctrl+C", "Quit");
+        help
+    }
+
     fn tabs_widget(&self) -> impl Widget {
         let titles = QueryEditTab::iter().map(QueryEditTab::title);
         let highlight_style = (Color::default(), self.editing_tab.palette().c700);
@@ -250,5 +373,12 @@ impl QueryTui {
         }
 
         f.render_widget(self.footer_widget(), footer_area);
+
+        if self.show_help {
+            let help = self.help_popup();
+            let help_area = help.area(area);
+            f.render_widget(Clear, help_area);
+            f.render_widget(help.widget(self.editing_tab.palette()), help_area);
+        }
     }
 }
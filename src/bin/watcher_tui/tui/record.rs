@@ -0,0 +1,81 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
+};
+use serde_json::json;
+use watch_rs::utils::OpenResult;
+
+/// Recording of the frames rendered by the `WatcherTui` into an asciicast v2 file, to be
+/// replayed with `asciinema play` or embedded with the asciinema player.
+pub struct Recorder {
+    file: BufWriter<File>,
+    started_at: Instant,
+    /// Last recorded frame, only the cells changed since are written.
+    previous: Option<Buffer>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> OpenResult<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            started_at: Instant::now(),
+            previous: None,
+        })
+    }
+
+    fn write_event(&mut self, kind: &str, data: &str) -> OpenResult<()> {
+        let time = self.started_at.elapsed().as_secs_f64();
+        writeln!(self.file, "{}", json!([time, kind, data]))?;
+        Ok(())
+    }
+
+    /// Record the frame drawn in the `buffer`, encoded like the terminal backend draws it.
+    pub fn record(&mut self, buffer: &Buffer) -> OpenResult<()> {
+        let area = buffer.area;
+        let mut data = Vec::new();
+
+        // The header comes with the first frame, for the size of the terminal
+        match &self.previous {
+            None => {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                let header = json!({
+                    "version": 2,
+                    "width": area.width,
+                    "height": area.height,
+                    "timestamp": timestamp,
+                    "env": { "TERM": std::env::var("TERM").unwrap_or_default() },
+                });
+                writeln!(self.file, "{header}")?;
+                data.extend_from_slice(b"\x1b[?25l\x1b[2J");
+            }
+            Some(previous) if previous.area != area => {
+                self.write_event("r", &format!("{}x{}", area.width, area.height))?;
+                data.extend_from_slice(b"\x1b[2J");
+            }
+            Some(_) => {}
+        }
+
+        // A resized frame is drawn whole, like the terminal clears on resize
+        let previous = match self.previous.take() {
+            Some(previous) if previous.area == area => previous,
+            _ => Buffer::empty(area),
+        };
+        let changes = previous.diff(buffer);
+        if changes.is_empty() && data.is_empty() {
+            self.previous = Some(previous);
+            return Ok(());
+        }
+        CrosstermBackend::new(&mut data).draw(changes.into_iter())?;
+
+        self.write_event("o", &String::from_utf8_lossy(&data))?;
+        self.previous = Some(buffer.clone());
+        Ok(())
+    }
+}
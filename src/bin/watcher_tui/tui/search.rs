@@ -0,0 +1,119 @@
+use ratatui::{
+    style::{palette::tailwind, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use regex::Regex;
+
+/// Regex search within the output, opened with `/` and navigated with `n` / `N`.
+#[derive(Default)]
+pub struct Search {
+    /// Pattern being typed in the search prompt.
+    pub prompt: Option<String>,
+    pub regex: Option<Regex>,
+    /// Reason the last submitted pattern got rejected.
+    pub error: Option<String>,
+    /// Index of the selected match, among the matches of the output.
+    pub selected_match: usize,
+}
+
+/// A regex match, as the line it is on and its byte range within that line.
+pub struct SearchMatch {
+    pub line_index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Search {
+    /// Compile the pattern of the prompt, closing it.
+    pub fn submit(&mut self) {
+        let Some(pattern) = self.prompt.take() else {
+            return;
+        };
+
+        self.selected_match = 0;
+        match Regex::new(&pattern) {
+            Ok(regex) => {
+                self.regex = Some(regex);
+                self.error = None;
+            }
+            Err(err) => {
+                self.regex = None;
+                self.error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// All the matches in the `output`, in order of appearance.
+    pub fn matches(&self, output: &str) -> Vec<SearchMatch> {
+        let Some(regex) = &self.regex else {
+            return Vec::new();
+        };
+
+        output
+            .lines()
+            .enumerate()
+            .flat_map(|(line_index, line)| {
+                regex
+                    .find_iter(line)
+                    .filter(|found| !found.is_empty())
+                    .map(move |found| SearchMatch {
+                        line_index,
+                        start: found.start(),
+                        end: found.end(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Move the selection to the next match, or the previous one when going `backwards`.
+    pub fn select_next(&mut self, match_count: usize, backwards: bool) {
+        if match_count == 0 {
+            return;
+        }
+        self.selected_match = match backwards {
+            true => (self.selected_match + match_count - 1) % match_count,
+            false => (self.selected_match + 1) % match_count,
+        };
+    }
+
+    /// The `output` with its matches highlighted, the selected match standing out.
+    pub fn highlighted_text<'a>(&self, output: &'a str) -> Text<'a> {
+        let match_style = Style::default()
+            .fg(tailwind::SLATE.c900)
+            .bg(tailwind::AMBER.c300);
+        let selected_style = Style::default()
+            .fg(tailwind::SLATE.c900)
+            .bg(tailwind::ORANGE.c500)
+            .add_modifier(Modifier::BOLD);
+
+        let matches = self.matches(output);
+        let mut matches = matches.iter().enumerate().peekable();
+
+        output
+            .lines()
+            .enumerate()
+            .map(|(line_index, line)| {
+                let mut spans = Vec::new();
+                let mut position = 0;
+                while let Some((match_index, found)) =
+                    matches.next_if(|(_, found)| found.line_index == line_index)
+                {
+                    let style = match match_index == self.selected_match {
+                        true => selected_style,
+                        false => match_style,
+                    };
+                    spans.push(Span::raw(&line[position..found.start]));
+                    spans.push(Span::styled(&line[found.start..found.end], style));
+                    position = found.end;
+                }
+                spans.push(Span::raw(&line[position..]));
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::{Modifier, Style},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Text selected by dragging the mouse over a pane, from its `anchor` to its `cursor` cell.
+/// The selection runs like in a text editor, over the full rows between its two ends.
+pub struct Selection {
+    /// Inner area of the pane the drag started in, the selection stays within it.
+    area: Rect,
+    anchor: Position,
+    cursor: Position,
+    /// The mouse got released, the selected text is to be copied on the next frame.
+    pub is_released: bool,
+}
+
+impl Selection {
+    pub fn new(area: Rect, at: Position) -> Self {
+        Self {
+            area,
+            anchor: at,
+            cursor: at,
+            is_released: false,
+        }
+    }
+
+    pub fn extend_to(&mut self, at: Position) {
+        self.cursor = Position::new(
+            at.x.clamp(self.area.left(), self.area.right().saturating_sub(1)),
+            at.y.clamp(self.area.top(), self.area.bottom().saturating_sub(1)),
+        );
+    }
+
+    /// Whether only a cell got clicked, without any drag.
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.cursor
+    }
+
+    /// First and last cells of the selection, in reading order.
+    fn ends(&self) -> (Position, Position) {
+        match (self.anchor.y, self.anchor.x) <= (self.cursor.y, self.cursor.x) {
+            true => (self.anchor, self.cursor),
+            false => (self.cursor, self.anchor),
+        }
+    }
+
+    /// Columns of the selection on the row `y`.
+    fn row_columns(&self, y: u16) -> std::ops::RangeInclusive<u16> {
+        let (start, end) = self.ends();
+        let first = match y == start.y {
+            true => start.x,
+            false => self.area.left(),
+        };
+        let last = match y == end.y {
+            true => end.x,
+            false => self.area.right().saturating_sub(1),
+        };
+        first..=last
+    }
+
+    fn rows(&self) -> std::ops::RangeInclusive<u16> {
+        let (start, end) = self.ends();
+        start.y..=end.y
+    }
+
+    /// Reverse the colors of the selected cells of the rendered `buffer`.
+    pub fn highlight(&self, buffer: &mut Buffer) {
+        let style = Style::default().add_modifier(Modifier::REVERSED);
+        for y in self.rows() {
+            for x in self.row_columns(y) {
+                if let Some(cell) = buffer.cell_mut((x, y)) {
+                    cell.set_style(style);
+                }
+            }
+        }
+    }
+
+    /// Text of the selected cells of the rendered `buffer`, with the trailing spaces of the rows trimmed.
+    pub fn text(&self, buffer: &Buffer) -> String {
+        let rows = self.rows().map(|y| {
+            let mut row = String::new();
+            let mut columns = self.row_columns(y);
+            while let Some(x) = columns.next() {
+                let Some(cell) = buffer.cell((x, y)) else {
+                    break;
+                };
+                row.push_str(cell.symbol());
+                // Wide characters span the next cells
+                for _ in 1..cell.symbol().width() {
+                    columns.next();
+                }
+            }
+            row.trim_end().to_string()
+        });
+        rows.collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Copy the `text` to the clipboard of the terminal, with an OSC 52 sequence.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
@@ -0,0 +1,108 @@
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{palette::tailwind::Palette, Modifier, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Row, Table},
+};
+use strum::{Display, EnumIter, FromRepr, IntoEnumIterator};
+use watch_rs::models::comparator::CompareMode;
+
+/// Runtime options of the `WatcherTui`, listed in the settings popup.
+#[derive(Default, Clone, Copy, PartialEq, Eq, EnumIter, FromRepr, Display)]
+pub enum Setting {
+    #[default]
+    #[strum(to_string = "Format")]
+    Format,
+    #[strum(to_string = "Compare mode")]
+    CompareMode,
+    #[strum(to_string = "Diff view")]
+    DiffView,
+    #[strum(to_string = "Diff granularity")]
+    DiffGranularity,
+    #[strum(to_string = "Elide unchanged")]
+    ElideUnchanged,
+    #[strum(to_string = "Filter")]
+    Filter,
+    #[strum(to_string = "Line numbers")]
+    LineNumbers,
+    #[strum(to_string = "Wrap lines")]
+    Wrap,
+    #[strum(to_string = "Summary table")]
+    Summary,
+    #[strum(to_string = "Follow output")]
+    Follow,
+    #[strum(to_string = "Theme")]
+    Theme,
+    #[strum(to_string = "Interval")]
+    Interval,
+}
+
+impl Setting {
+    /// Get the previous setting, if there is no previous setting return the current one.
+    pub fn previous(self) -> Self {
+        let previous_index = (self as usize).saturating_sub(1);
+        Self::from_repr(previous_index).unwrap_or(self)
+    }
+
+    /// Get the next setting, if there is no next setting return the current one.
+    pub fn next(self) -> Self {
+        let next_index = (self as usize).saturating_add(1);
+        Self::from_repr(next_index).unwrap_or(self)
+    }
+}
+
+/// Settings overlay of the `WatcherTui`, opened with `,`.
+#[derive(Default)]
+pub struct SettingsPopup {
+    pub selected: Setting,
+}
+
+impl SettingsPopup {
+    /// Form listing every setting with its current value, as given by `value_of`.
+    pub fn widget(&self, value_of: impl Fn(Setting) -> String, palette: Palette) -> Table<'static> {
+        let rows = Setting::iter().map(|setting| {
+            let value = format!("◄ {} ►", value_of(setting));
+            let row = Row::new([Cell::from(setting.to_string()), Cell::from(value)]);
+
+            match setting == self.selected {
+                true => row.style(
+                    Style::default()
+                        .fg(palette.c200)
+                        .bg(palette.c900)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                false => row,
+            }
+        });
+
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(palette.c500))
+            .title("  Settings  ".bold())
+            .title_bottom(" ▲ ▼ to select │ ◄ ► to change │ Esc to close ");
+
+        Table::new(rows, [Constraint::Length(20), Constraint::Fill(1)]).block(block)
+    }
+
+    /// Centered area of the popup within `area`.
+    pub fn area(area: Rect) -> Rect {
+        let height = Setting::iter().count() as u16 + 2;
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Length(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        area
+    }
+}
+
+/// Compare mode following `mode` when cycling through them, with their default parameters.
+pub fn next_compare_mode(mode: CompareMode) -> CompareMode {
+    match mode {
+        CompareMode::Exact => CompareMode::Normalized,
+        CompareMode::Normalized => CompareMode::NumericTolerance(0.01),
+        CompareMode::NumericTolerance(_) => CompareMode::Json,
+        CompareMode::Json => CompareMode::ImageHash(4),
+        CompareMode::ImageHash(_) => CompareMode::Exact,
+    }
+}
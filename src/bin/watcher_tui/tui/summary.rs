@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+use regex::Regex;
+use strum::Display;
+use watch_rs::models::watcher::ExecStatus;
+
+use super::WatcherIterationOutput;
+
+/// Reduces the output of a fan-out watcher into the single value listed in the summary table.
+#[derive(Clone)]
+pub enum SummaryExtractor {
+    /// Exit code of the command.
+    ExitCode,
+    /// First non-empty line of the output.
+    FirstLine,
+    /// First match of the regex in the output, or its first capture group if it has one.
+    Metric(Regex),
+}
+
+impl FromStr for SummaryExtractor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "exit-code" => Ok(Self::ExitCode),
+            "first-line" => Ok(Self::FirstLine),
+            _ => match value.strip_prefix("metric:") {
+                Some(pattern) => Regex::new(pattern)
+                    .map(Self::Metric)
+                    .map_err(|err| err.to_string()),
+                None => Err(format!(
+                    "unknown extractor `{value}`, expected `exit-code`, `first-line` or `metric:<regex>`"
+                )),
+            },
+        }
+    }
+}
+
+impl SummaryExtractor {
+    pub fn extract(&self, event: &WatcherIterationOutput) -> String {
+        match self {
+            Self::ExitCode => event
+                .exit_code
+                .map_or_else(|| "-".to_string(), |code| code.to_string()),
+            Self::FirstLine => event
+                .output
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or_default()
+                .to_string(),
+            Self::Metric(regex) => regex
+                .captures(&event.output)
+                .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+                .map_or_else(|| "-".to_string(), |metric| metric.as_str().to_string()),
+        }
+    }
+}
+
+/// Health of a watcher, ordered so that the unhealthy watchers come first.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Display)]
+pub enum SummaryStatus {
+    #[strum(to_string = "TIMED OUT")]
+    TimedOut,
+    #[strum(to_string = "FAILED")]
+    Failed,
+    #[strum(to_string = "CANCELLED")]
+    Cancelled,
+    #[strum(to_string = "PENDING")]
+    Pending,
+    #[strum(to_string = "OK")]
+    Ok,
+}
+
+impl SummaryStatus {
+    pub fn of(event: &WatcherIterationOutput) -> Self {
+        match (event.status, event.exit_code) {
+            (ExecStatus::TimedOut(_), _) => Self::TimedOut,
+            (ExecStatus::Cancelled, _) => Self::Cancelled,
+            (_, _) if event.iteration == 0 => Self::Pending,
+            (_, Some(0)) => Self::Ok,
+            (_, Some(_)) => Self::Failed,
+            (_, None) => Self::Pending,
+        }
+    }
+}
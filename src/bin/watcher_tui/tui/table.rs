@@ -0,0 +1,187 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use ratatui::{
+    layout::Constraint,
+    style::{
+        palette::tailwind::{self, Palette},
+        Modifier, Style,
+    },
+    widgets::{Cell, Row, Table},
+};
+
+/// Widest a column gets, the longer cells are truncated.
+const MAX_COLUMN_WIDTH: usize = 60;
+
+/// Column the table rows are ordered by, toggled with `o` / `O`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TableSort {
+    pub column: usize,
+    pub descending: bool,
+}
+
+/// Output laid out in columns under a header line, like the ones of `ps aux` or `kubectl get pods`.
+pub struct ColumnarOutput {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Split a whitespace aligned `line` in at most `columns` cells, the last one keeping the rest
+/// of the line, like the command lines of `ps aux`.
+fn split_whitespace(line: &str, columns: usize) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut rest = line.trim();
+    while cells.len() + 1 < columns && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        cells.push(rest[..end].to_string());
+        rest = rest[end..].trim_start();
+    }
+    if !rest.is_empty() {
+        cells.push(rest.to_string());
+    }
+    cells
+}
+
+/// Split a CSV `line`, with the quoted cells unquoted.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut is_quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, is_quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            ('"', _) => is_quoted = !is_quoted,
+            (',', false) => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// Numbers are ordered by value, the other cells alphabetically.
+fn compare_cells(cell: &str, other: &str) -> Ordering {
+    match (cell.parse::<f64>(), other.parse::<f64>()) {
+        (Ok(value), Ok(other_value)) => value.total_cmp(&other_value),
+        _ => cell.cmp(other),
+    }
+}
+
+impl ColumnarOutput {
+    /// Parse the tab, comma or whitespace separated `output`, with its first line as the header.
+    /// `None` when it does not have at least two columns.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+        let header_line = lines.next()?;
+
+        let split_line = |line: &str, columns: usize| -> Vec<String> {
+            if header_line.contains('\t') {
+                line.split('\t').map(str::to_string).collect()
+            } else if header_line.contains(',') {
+                split_csv(line)
+            } else {
+                split_whitespace(line, columns)
+            }
+        };
+        let header = split_line(header_line, usize::MAX);
+        if header.len() < 2 {
+            return None;
+        }
+
+        let rows = lines.map(|line| split_line(line, header.len())).collect();
+        Some(Self { header, rows })
+    }
+
+    /// Parse the `output` as CSV, with its first line as the header.
+    pub fn parse_csv(output: &str) -> Option<Self> {
+        let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+        let header = split_csv(lines.next()?);
+        let rows = lines.map(split_csv).collect();
+        Some(Self { header, rows })
+    }
+
+    pub fn sort(&mut self, sort: TableSort) {
+        let cell = |row: &[String]| row.get(sort.column).cloned().unwrap_or_default();
+        self.rows.sort_by(|row, other| {
+            let ordering = compare_cells(&cell(row), &cell(other));
+            match sort.descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        });
+    }
+
+    /// Table of the rows with a sticky header, highlighting the cells which changed from the
+    /// `previous` output and the rows which are new, matched by their first cell.
+    pub fn widget(
+        &self,
+        previous: Option<&ColumnarOutput>,
+        sort: Option<TableSort>,
+        palette: Palette,
+    ) -> Table<'_> {
+        let previous_rows = previous.map(|previous| {
+            previous
+                .rows
+                .iter()
+                .filter_map(|row| Some((row.first()?.as_str(), row)))
+                .collect::<HashMap<_, _>>()
+        });
+        let changed_style = Style::default()
+            .fg(tailwind::AMBER.c300)
+            .add_modifier(Modifier::BOLD);
+        let added_style = Style::default().fg(tailwind::GREEN.c400);
+
+        let rows = self.rows.iter().map(|row| {
+            let previous_row = previous_rows
+                .as_ref()
+                .map(|previous_rows| row.first().and_then(|key| previous_rows.get(key.as_str())));
+            let cells = row.iter().enumerate().map(|(column, cell)| {
+                let style = match previous_row {
+                    Some(None) => added_style,
+                    Some(Some(previous_row)) if previous_row.get(column) != Some(cell) => {
+                        changed_style
+                    }
+                    _ => Style::default(),
+                };
+                Cell::from(cell.as_str()).style(style)
+            });
+            Row::new(cells)
+        });
+
+        let header = Row::new(self.header.iter().enumerate().map(|(column, title)| {
+            match sort.filter(|sort| sort.column == column) {
+                Some(TableSort {
+                    descending: true, ..
+                }) => format!("{title} ▼"),
+                Some(_) => format!("{title} ▲"),
+                None => title.clone(),
+            }
+        }))
+        .style(
+            Style::default()
+                .fg(palette.c200)
+                .bg(palette.c900)
+                .add_modifier(Modifier::BOLD),
+        );
+
+        let widths = (0..self.header.len()).map(|column| {
+            let width = self
+                .rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .chain([&self.header[column]])
+                .map(|cell| cell.chars().count() + 2)
+                .max()
+                .unwrap_or_default();
+            match column + 1 == self.header.len() {
+                true => Constraint::Fill(1),
+                false => Constraint::Length(width.min(MAX_COLUMN_WIDTH) as u16),
+            }
+        });
+
+        Table::new(rows, widths).header(header)
+    }
+}
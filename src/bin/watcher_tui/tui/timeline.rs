@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use chrono::Local;
+use ratatui::{
+    style::{
+        palette::tailwind::{self, Palette},
+        Stylize,
+    },
+    text::{Line, Span},
+};
+
+use super::format::Locale;
+use crate::report::SessionHistory;
+
+/// Strip of the latest recorded iterations, one cell each: tall when the output changed, low
+/// when it stayed the same, red when the iteration failed. Ends with the age of the last change.
+pub fn timeline_line(
+    history: &SessionHistory,
+    width: u16,
+    locale: &Locale,
+    palette: Palette,
+) -> Line<'static> {
+    let iterations = history.iterations();
+    let last_change = iterations.iter().rev().find(|it| it.output_changed);
+    let label = match last_change {
+        Some(it) => {
+            let age = (Local::now() - it.finished_at).to_std().unwrap_or_default();
+            format!(
+                " Δ Itr {} │ {} ago ",
+                locale.format_integer(it.iteration),
+                humantime::format_duration(Duration::from_secs(age.as_secs()))
+            )
+        }
+        None => " waiting for the first run ".to_string(),
+    };
+
+    let cell_count = (width as usize).saturating_sub(label.chars().count());
+    let skipped = iterations.len().saturating_sub(cell_count);
+    let padding = cell_count.saturating_sub(iterations.len());
+    let mut spans = vec![Span::raw(" ".repeat(padding))];
+    spans.extend(iterations.iter().skip(skipped).map(|it| {
+        let cell = match it.output_changed {
+            true => "█",
+            false => "▁",
+        };
+        match (it.is_success(), it.output_changed) {
+            (true, true) => cell.fg(palette.c400),
+            (true, false) => cell.fg(tailwind::SLATE.c600),
+            (false, true) => cell.fg(tailwind::RED.c500),
+            (false, false) => cell.fg(tailwind::RED.c800),
+        }
+    }));
+    spans.push(label.fg(tailwind::SLATE.c400));
+    Line::from(spans)
+}
@@ -1,83 +1,877 @@
-use chrono::{DateTime, Local, Timelike};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
 use crossbeam_channel::Receiver;
-use crossterm::event::{self as term_event, Event, KeyCode, KeyModifiers};
+use crossterm::event::{
+    self as term_event, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use log::{info, warn};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{self, Alignment, Constraint, Direction, Flex, Layout, Rect},
     style::{
         palette::tailwind::{self, Palette},
         Modifier, Style, Stylize,
     },
-    text::{Span, Text},
-    widgets::{block::Position, Block, BorderType, Borders, Paragraph, Wrap},
+    text::{Line, Span, Text},
+    widgets::{
+        block::{Position, Title},
+        Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Tabs, Wrap,
+    },
     Frame, Terminal,
 };
-use std::{fmt::Write, time::Instant};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    path::{Path, PathBuf},
+    ptr,
+    time::Instant,
+};
 use std::{
     io,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
     time::Duration,
 };
-use watch_rs::utils::OpenResult;
+use watch_rs::{
+    models::{
+        comparator::{Comparator, CompareMode, SharedComparator},
+        json_diff::JsonPath,
+        line_diff::{compared_line_diff, elide_unchanged, DiffLine},
+        shell::CancelHandle,
+        watcher::ExecStatus,
+        watcher_loop::IterationResult,
+    },
+    utils::OpenResult,
+};
+
+use crate::{
+    bundle::{
+        default_bundle_name, write_debug_bundle, DebugSession, PaneIterations, BUNDLED_ITERATIONS,
+    },
+    control::{ControlRequest, IterationMark},
+    redact::redact,
+    report::{default_report_name, write_report, PaneHistory, SessionHistory},
+    snapshot::export_snapshot,
+    tui::{IDLE_REDRAW_INTERVAL, TERMINATION_REQUESTED, TICK_RATE},
+};
+
+use super::{
+    alert::{ActiveAlert, AlertRule},
+    format::{
+        filter_lines, hexdump, is_binary, json_diff_text, json_output_diff, line_diff_text,
+        local_timezone_name, with_line_numbers, DiffGranularity, Locale, OutputFormat,
+    },
+    help::HelpPopup,
+    highlight::{with_highlights, HighlightRule},
+    markdown::markdown_text,
+    picker::{IterationPicker, PICKER_WIDTH},
+    plot::{metric_chart, MetricExtractor, MetricSeries},
+    query::QueryState,
+    record::Recorder,
+    search::Search,
+    selection::{copy_to_clipboard, Selection},
+    settings::{next_compare_mode, Setting, SettingsPopup},
+    strip_colors,
+    summary::{SummaryExtractor, SummaryStatus},
+    table::{ColumnarOutput, TableSort},
+    timeline::timeline_line,
+    EventReceiver, WatcherIterationOutput, WatcherOutputEvent,
+};
+
+/// Lines scrolled by `PageUp` / `PageDown`.
+const PAGE_SCROLL: u16 = 20;
+/// Lines scrolled by a notch of the mouse wheel.
+const MOUSE_SCROLL: u16 = 3;
+/// Columns panned by `Left` / `Right`, when the lines are not wrapped.
+const HORIZONTAL_SCROLL: u16 = 8;
+/// Lines kept above a search match scrolled to.
+const SCROLL_MARGIN: usize = 2;
+
+/// How long the outcome of an action stays in the status bar.
+const NOTICE_DURATION: Duration = Duration::from_secs(5);
+/// How long the titles of the panes flash once their baselines got accepted with `a`.
+const BASELINE_FLASH_DURATION: Duration = Duration::from_millis(600);
+/// Characters of the watched command shown in the status bar, the longer ones are truncated.
+const STATUS_COMMAND_WIDTH: usize = 32;
+/// Characters of the commands shown in the tab bar.
+const TAB_TITLE_WIDTH: usize = 24;
+/// Width of the error screen over the panes of the failed watches.
+const ERROR_SCREEN_WIDTH: u16 = 64;
+/// Lines of output of the failed setup commands shown above the outputs, the last ones.
+const SETUP_PANEL_LINES: usize = 6;
+/// Intervals stepped through with `+` and `-`, the first one being the floor.
+const INTERVAL_STEPS: [Duration; 16] = [
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(15),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(2 * 60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(10 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(30 * 60),
+    Duration::from_secs(60 * 60),
+];
+
+/// Frames of the spinner shown while a command runs.
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME_DURATION: Duration = Duration::from_millis(80);
+
+/// What the watcher thread of a pane is doing, as reported by its timing events.
+#[derive(Clone, Copy)]
+enum RunState {
+    Idle,
+    Running { since: Instant },
+    Sleeping { until: Instant },
+    WaitingForChange,
+}
+
+/// Running statistics of the run durations of a pane's command.
+#[derive(Default)]
+struct RunStats {
+    last: Duration,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+    runs: u32,
+}
+
+impl RunStats {
+    fn record(&mut self, run_duration: Duration) {
+        self.min = match self.runs {
+            0 => run_duration,
+            _ => self.min.min(run_duration),
+        };
+        self.max = self.max.max(run_duration);
+        self.last = run_duration;
+        self.total += run_duration;
+        self.runs += 1;
+    }
+
+    fn average(&self) -> Duration {
+        self.total.checked_div(self.runs).unwrap_or_default()
+    }
+}
+
+/// Watch of one of the commands, in its own tab when several commands are watched.
+pub struct WatchTab {
+    pub query_state: QueryState,
+    /// One pane per watcher, a single one out of the fan-out mode.
+    pub panes: Vec<WatcherPane>,
+}
+
+/// Recorded iteration of a pane, rebuilt from its session history.
+struct PickedIteration {
+    event: WatcherIterationOutput,
+    /// Output of the iteration before it, for the diff view.
+    previous_output: Option<String>,
+}
+
+/// Output of a single watcher thread, rendered as one pane of the `WatcherTui`.
+pub struct WatcherPane {
+    title: Option<String>,
+    event_receiver: EventReceiver,
+    /// Events of the watcher thread dropped as its queue was full.
+    dropped_events: Arc<AtomicUsize>,
+    current_event: WatcherIterationOutput,
+    previous_output: Option<String>,
+    /// Recorded iteration loaded from the picker in place of the latest one.
+    picked: Option<PickedIteration>,
+    /// Iteration pinned with `p`, the outputs are diffed against it instead of the previous one.
+    baseline: Option<WatcherIterationOutput>,
+    /// Latest iterations, kept for the debug bundles.
+    recent_events: VecDeque<WatcherIterationOutput>,
+    /// Iterations since the watch started, kept for the session reports.
+    history: SessionHistory,
+    /// Output streamed so far by the running iteration.
+    partial_output: Option<String>,
+    run_state: RunState,
+    run_stats: RunStats,
+    /// Values extracted with `--plot`, charted below the panes.
+    metric_series: MetricSeries,
+    /// Alert raised by the latest value, until a value satisfies none of the rules.
+    alert: Option<ActiveAlert>,
+    started_at: Instant,
+    /// When the output last changed, and the runs since which were ignored with `--only-changes`.
+    changed_at: Instant,
+    unchanged_runs: usize,
+    has_ended: bool,
+    /// Cancels the running command of the pane, when its shell supports it.
+    cancel_handle: Option<CancelHandle>,
+    /// Set to restart the shell of the pane before its next iteration.
+    restart_shell: Option<Arc<AtomicBool>>,
+    /// Failure of the watch, shown over the output until it gets retried.
+    error: Option<String>,
+    /// Result of the setup commands when they failed, shown above the output.
+    setup_failure: Option<WatcherIterationOutput>,
+}
+
+impl WatcherPane {
+    pub fn new(title: Option<String>, event_receiver: EventReceiver) -> Self {
+        Self {
+            title,
+            event_receiver,
+            dropped_events: Arc::default(),
+            current_event: WatcherIterationOutput {
+                iteration: 0,
+                output: String::new(),
+                raw_output: None,
+                status: ExecStatus::Completed,
+                exit_code: None,
+                interval: Duration::ZERO,
+                run_duration: Duration::ZERO,
+                started_at: Local::now(),
+                finished_at: Local::now(),
+                output_bytes: 0,
+                truncated_at: None,
+                has_changed: false,
+            },
+            previous_output: None,
+            picked: None,
+            baseline: None,
+            recent_events: VecDeque::with_capacity(BUNDLED_ITERATIONS),
+            history: SessionHistory::default(),
+            partial_output: None,
+            run_state: RunState::Idle,
+            run_stats: RunStats::default(),
+            metric_series: MetricSeries::default(),
+            alert: None,
+            started_at: Instant::now(),
+            changed_at: Instant::now(),
+            unchanged_runs: 0,
+            has_ended: false,
+            cancel_handle: None,
+            restart_shell: None,
+            error: None,
+            setup_failure: None,
+        }
+    }
+
+    pub fn with_dropped_events(mut self, dropped_events: Arc<AtomicUsize>) -> Self {
+        self.dropped_events = dropped_events;
+        self
+    }
+
+    /// Evict the outputs of the oldest recorded iterations past `max_history_bytes`.
+    pub fn with_history_budget(mut self, max_history_bytes: Option<usize>) -> Self {
+        self.history = SessionHistory::new(max_history_bytes);
+        self
+    }
+
+    pub fn with_cancel_handle(mut self, cancel_handle: Option<CancelHandle>) -> Self {
+        self.cancel_handle = cancel_handle;
+        self
+    }
+
+    pub fn with_shell_restart(mut self, restart_shell: Arc<AtomicBool>) -> Self {
+        self.restart_shell = Some(restart_shell);
+        self
+    }
+
+    /// Iteration shown in the pane, the one loaded from the picker or the latest one.
+    fn shown_event(&self) -> &WatcherIterationOutput {
+        match &self.picked {
+            Some(picked) => &picked.event,
+            None => &self.current_event,
+        }
+    }
+
+    /// Bytes of the shown output as the command printed them.
+    fn shown_bytes(&self) -> &[u8] {
+        let event = self.shown_event();
+        event
+            .raw_output
+            .as_deref()
+            .unwrap_or(event.output.as_bytes())
+    }
+
+    /// Output the shown one is diffed against, the pinned baseline or the previous output.
+    fn compared_output(&self) -> Option<&str> {
+        match (&self.baseline, &self.picked) {
+            (Some(baseline), _) => Some(&baseline.output),
+            (None, Some(picked)) => picked.previous_output.as_deref(),
+            (None, None) => self.previous_output.as_deref(),
+        }
+    }
+
+    /// Load the recorded `iteration` in place of the latest one, or back the latest one with `None`.
+    fn pick_iteration(&mut self, iteration: Option<usize>) {
+        self.picked = iteration.and_then(|iteration| {
+            let index = self.history.position(iteration)?;
+            let recorded = &self.history.iterations()[index];
+            Some(PickedIteration {
+                event: WatcherIterationOutput {
+                    iteration,
+                    output: self.history.output_at(index)?.to_string(),
+                    raw_output: self.history.raw_output_at(index).map(<[u8]>::to_vec),
+                    status: recorded.status,
+                    exit_code: recorded.exit_code,
+                    interval: self.current_event.interval,
+                    run_duration: recorded.run_duration,
+                    started_at: recorded.started_at,
+                    finished_at: recorded.finished_at,
+                    output_bytes: recorded.output_bytes,
+                    truncated_at: recorded.truncated_at,
+                    has_changed: index > 0 && recorded.output_changed,
+                },
+                previous_output: index
+                    .checked_sub(1)
+                    .and_then(|previous| self.history.output_at(previous))
+                    .map(str::to_string),
+            })
+        });
+    }
 
-use crate::tui::TICK_RATE;
+    /// Receive the next event of the watcher thread, returning whether it raised an alert, or
+    /// `None` when no event was pending.
+    fn receive_event(
+        &mut self,
+        plot: Option<&MetricExtractor>,
+        alerts: &[AlertRule],
+        only_changes: bool,
+    ) -> Option<bool> {
+        use WatcherOutputEvent::*;
 
-use super::{WatcherIterationOutput, WatcherOutputEvent};
+        let event = self.event_receiver.try_recv().ok()?;
+        let mut is_alert_raised = false;
+        match event {
+            SetupResult(res) => {
+                self.setup_failure = Some(res.clone()).filter(|res| !res.is_success());
+                self.current_event = res;
+            }
+            ShellRestarted(res, cancel_handle) => {
+                self.setup_failure = Some(res).filter(|res| !res.is_success());
+                self.cancel_handle = cancel_handle;
+                self.partial_output = None;
+                self.error = None;
+            }
+            RunStarted(since) => {
+                self.run_state = RunState::Running { since };
+                self.partial_output = None;
+            }
+            Sleeping(until) => self.run_state = RunState::Sleeping { until },
+            WaitingForChange => self.run_state = RunState::WaitingForChange,
+            PartialOutput(new_lines) => self
+                .partial_output
+                .get_or_insert_with(String::new)
+                .push_str(&new_lines),
+            IterationResult(res) => {
+                self.partial_output = None;
+                self.run_stats.record(res.run_duration);
+                let is_unchanged = self.current_event.iteration > 0
+                    && res.output == self.current_event.output
+                    && (res.status, res.exit_code)
+                        == (self.current_event.status, self.current_event.exit_code);
+                if only_changes && is_unchanged {
+                    self.unchanged_runs += 1;
+                    return Some(false);
+                }
+                self.changed_at = Instant::now();
+                self.unchanged_runs = 0;
+                if self.recent_events.len() == BUNDLED_ITERATIONS {
+                    self.recent_events.pop_front();
+                }
+                self.recent_events.push_back(res.clone());
+                self.history.record(&res);
+                if let Some(value) = plot.and_then(|plot| plot.extract(&res.output)) {
+                    self.metric_series.push(self.started_at.elapsed(), value);
+                    is_alert_raised = self.check_alerts(alerts, value);
+                }
+                let previous_event = std::mem::replace(&mut self.current_event, res);
+                if previous_event.iteration > 0 {
+                    self.previous_output = Some(previous_event.output);
+                }
+            }
+            Error(error) => {
+                self.error = Some(error);
+                self.partial_output = None;
+                self.run_state = RunState::Idle;
+            }
+            End => {
+                self.has_ended = true;
+                self.run_state = RunState::Idle;
+            }
+        }
+        Some(is_alert_raised)
+    }
+
+    /// Raise or clear the alert of the pane for the latest `value`, returning whether it got raised.
+    fn check_alerts(&mut self, alerts: &[AlertRule], value: f64) -> bool {
+        let title = self.title.as_deref().unwrap_or("local");
+        let was_alerting = self.alert.is_some();
+        self.alert = alerts
+            .iter()
+            .find(|rule| rule.is_satisfied(value))
+            .map(|&rule| ActiveAlert { rule, value });
+
+        match (&self.alert, was_alerting) {
+            (Some(alert), false) => {
+                warn!("Alert `{}` raised on {title} : value {value}", alert.rule);
+                true
+            }
+            (None, true) => {
+                info!("Alert cleared on {title} : value {value}");
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Rendering options of the `WatcherTui`, set from the command line.
+pub struct WatcherTuiOptions {
+    /// Start on the summary table, reducing the panes with this extractor.
+    pub summary_extractor: Option<SummaryExtractor>,
+    /// Decides which outputs and lines differ, across the iterations and the panes.
+    pub compare_mode: CompareMode,
+    /// Comparator of the watcher threads, following the compare mode changed from the settings.
+    pub comparator: SharedComparator,
+    pub format: OutputFormat,
+    /// JSON paths left out of the structural diff display.
+    pub json_ignore: Vec<JsonPath>,
+    /// Start in diff view, showing the changes from the previous iteration.
+    pub diff_view: bool,
+    /// Lines of context kept around the changes in diff view, farther unchanged lines are elided.
+    pub diff_context: usize,
+    /// Unit of the changes highlighted in diff view.
+    pub diff_granularity: DiffGranularity,
+    pub unfocused_mode: UnfocusedMode,
+    /// Only the lines matching the filter are shown, while it is enabled.
+    pub filter: Option<Regex>,
+    /// Chart the number extracted from each iteration below the panes.
+    pub plot: Option<MetricExtractor>,
+    /// Thresholds on the plotted value, turning the borders red and beeping when satisfied.
+    pub alerts: Vec<AlertRule>,
+    /// Styles of the output lines matching their regex, the first matching rule applies.
+    pub highlights: Vec<HighlightRule>,
+    /// Prefix the output lines with their line number.
+    pub line_numbers: bool,
+    /// Soft-wrap the long lines, instead of truncating them with horizontal panning.
+    pub wrap: bool,
+    /// Keep the panes scrolled to the bottom of the output as new iterations arrive.
+    pub follow: bool,
+    pub theme: Theme,
+    /// Conventions for rendering the numbers and the clock.
+    pub locale: Locale,
+    /// Directory the snapshots exported with `s` are written to.
+    pub export_dir: PathBuf,
+    /// Outputs larger than this many bytes are exported as gzip-compressed chunks.
+    pub export_chunk_size: usize,
+    /// How the watch got started, packaged into the debug bundles written with `b`.
+    pub debug_session: DebugSession,
+    /// HTML report of the session written when the watch ends.
+    pub report_path: Option<PathBuf>,
+    /// Records the rendered frames into an asciicast file.
+    pub recorder: Option<Recorder>,
+    /// Keys remapped onto the key bindings.
+    pub keybindings: BTreeMap<char, char>,
+    /// Render without colors, keeping the modifiers.
+    pub monochrome: bool,
+    /// Control requests sent with `ctl`.
+    pub control_receiver: Option<Receiver<ControlRequest>>,
+    /// Flag suspending the watcher threads, shared with their schedule.
+    pub paused: Arc<AtomicBool>,
+    /// Flag running the next iteration right away, shared with the schedule of the watchers.
+    pub refresh: Arc<AtomicBool>,
+    /// Flag skipping the next scheduled iteration, shared with the schedule of the watchers.
+    pub skip_next: Arc<AtomicBool>,
+    /// Interval adjusted with `+` and `-`, shared with the schedule of the watchers.
+    pub live_interval: Arc<Mutex<Duration>>,
+    /// Cron expression of `--schedule`, shown instead of the interval.
+    pub cron: Option<String>,
+    /// Keep the outputs displayed once the watchers ended, until quitting with Ctrl+C.
+    pub keep_open: bool,
+    /// Directory the command runs in, shown in the header.
+    pub cwd: String,
+    /// Title of the header, instead of the directory the command runs in.
+    pub title: Option<String>,
+    /// Only the outputs are rendered, without the header, the status bar and the pane titles.
+    pub no_title: bool,
+    /// Layout of the panes, of the hosts in fan-out mode or of the commands with `--split`.
+    pub split: SplitDirection,
+    /// The diff view renders the changes from the output of the first pane to the one of the
+    /// second, rather than from their previous iterations.
+    pub ab_diff: bool,
+    /// Iterations identical to the previous one are ignored, and counted in the pane titles.
+    pub only_changes: bool,
+}
+
+/// Accent colors of the `WatcherTui`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Lime,
+    Sky,
+    Amber,
+    Rose,
+    Violet,
+    Slate,
+}
+
+impl Theme {
+    /// Theme following this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Lime => Self::Sky,
+            Self::Sky => Self::Amber,
+            Self::Amber => Self::Rose,
+            Self::Rose => Self::Violet,
+            Self::Violet => Self::Slate,
+            Self::Slate => Self::Lime,
+        }
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            Self::Lime => tailwind::LIME,
+            Self::Sky => tailwind::SKY,
+            Self::Amber => tailwind::AMBER,
+            Self::Rose => tailwind::ROSE,
+            Self::Violet => tailwind::VIOLET,
+            Self::Slate => tailwind::SLATE,
+        }
+    }
+}
+
+/// Behavior of the `WatcherTui` while the terminal is unfocused.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UnfocusedMode {
+    /// Keep rendering and executing as usual.
+    Keep,
+    /// Stop redrawing until the focus comes back.
+    PauseRender,
+    /// Stop redrawing, and suspend the command execution.
+    Pause,
+}
+
+/// How the panes are laid out, when several commands share the screen with `--split`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SplitDirection {
+    /// Side by side, in columns.
+    #[default]
+    Vertical,
+    /// One above the other, in rows.
+    Horizontal,
+}
+
+/// First line of the `commands`, truncated to `width` characters and redacted.
+pub fn summarize_command(commands: &str, width: usize) -> String {
+    let commands = redact(commands.trim());
+    let mut lines = commands.lines();
+    let mut command = lines.next().unwrap_or_default().to_string();
+    if lines.next().is_some() || command.chars().count() > width {
+        command = command.chars().take(width - 1).collect();
+        command.push('…');
+    }
+    command
+}
+
+/// How the `WatcherTui` was left.
+pub enum WatcherTuiExit {
+    /// All the watchers ended.
+    Ended,
+    /// The commands are to be edited, and the watch restarted with them.
+    EditQuery,
+}
 
 pub struct WatcherTui {
-    event_receiver: Receiver<WatcherOutputEvent>,
+    /// Panes of the active tab, moved out of it while active.
+    panes: Vec<WatcherPane>,
+    /// Watches of every command, switched between with `Tab` and `Shift+Tab`.
+    tabs: Vec<WatchTab>,
+    active_tab: usize,
     should_close_watcher: Arc<AtomicBool>,
-    current_event: WatcherIterationOutput,
+    summary_extractor: SummaryExtractor,
+    show_summary: bool,
+    compare_mode: CompareMode,
+    /// Comparator of the watcher threads as well, swapped from the settings.
+    comparator: SharedComparator,
+    format: OutputFormat,
+    json_ignore: Vec<JsonPath>,
+    diff_view: bool,
+    diff_context: usize,
+    diff_granularity: DiffGranularity,
+    elide_unchanged: bool,
+    line_numbers: bool,
+    theme: Theme,
+    locale: Locale,
+    wrap: bool,
+    /// Lines scrolled off the top of the panes.
+    scroll: u16,
+    /// Pinned to the bottom of the output, until scrolled up.
+    follow: bool,
+    /// Columns panned off the left of the panes, when the lines are not wrapped.
+    column_offset: u16,
+    search: Search,
+    filter: Option<Regex>,
+    filter_enabled: bool,
+    plot: Option<MetricExtractor>,
+    alerts: Vec<AlertRule>,
+    highlights: Vec<HighlightRule>,
+    /// Switched with `h`, the binary outputs are shown raw and the text ones as a hexdump.
+    is_hexdump_toggled: bool,
+    /// Order of the rows, in table format.
+    table_sort: Option<TableSort>,
+    /// Labels of the timeline, added with `m` or `ctl mark`.
+    marks: Vec<IterationMark>,
+    /// Label being typed in the mark prompt.
+    mark_prompt: Option<String>,
+    /// Directory being typed in the export prompt, opened with `S`.
+    export_prompt: Option<String>,
+    control_receiver: Option<Receiver<ControlRequest>>,
+    unfocused_mode: UnfocusedMode,
+    is_focused: bool,
+    paused: Arc<AtomicBool>,
+    refresh: Arc<AtomicBool>,
+    skip_next: Arc<AtomicBool>,
+    live_interval: Arc<Mutex<Duration>>,
+    cron: Option<String>,
+    keep_open: bool,
+    cwd: String,
+    title: Option<String>,
+    /// Fullscreen outputs, without the chrome, from `--no-title` and toggled with `z`.
+    no_title: bool,
+    split: SplitDirection,
+    /// A/B diff of two commands, from `--ab`.
+    ab_diff: bool,
+    only_changes: bool,
+    export_dir: PathBuf,
+    export_chunk_size: usize,
+    debug_session: DebugSession,
+    report_path: Option<PathBuf>,
+    recorder: Option<Recorder>,
+    keybindings: BTreeMap<char, char>,
+    monochrome: bool,
+    /// Outcome of the last action, shown in the status bar for a few seconds or until the next key press.
+    notice: Option<Result<String, String>>,
+    noticed_at: Instant,
+    /// When the outputs got accepted as the new baselines, to flash the titles of the panes.
+    baseline_accepted_at: Option<Instant>,
+    settings_popup: Option<SettingsPopup>,
+    /// Cheat sheet of the key bindings, opened with `?`.
+    show_help: bool,
+    /// Side panel of the recorded iterations, opened with `i`.
+    picker: Option<IterationPicker>,
+    /// Strip of the changed and failed iterations below the outputs, toggled with `T`.
+    show_timeline: bool,
+    /// Inner areas of the panes in the last frame, for the mouse selection.
+    pane_areas: Vec<Rect>,
+    /// Text selected with the mouse, until the next click or key press.
+    selection: Option<Selection>,
+    /// Selected text to copy once the frame is drawn.
+    clipboard: Option<String>,
 }
 
 impl WatcherTui {
     pub fn new(
-        event_receiver: Receiver<WatcherOutputEvent>,
+        mut tabs: Vec<WatchTab>,
         should_close_watcher: Arc<AtomicBool>,
+        options: WatcherTuiOptions,
     ) -> Self {
+        let mut debug_session = options.debug_session;
+        debug_session.query_state = tabs[0].query_state.clone();
         Self {
-            event_receiver,
+            panes: std::mem::take(&mut tabs[0].panes),
+            tabs,
+            active_tab: 0,
             should_close_watcher,
-            current_event: WatcherIterationOutput {
-                iteration: 0,
-                output: String::new(),
+            show_summary: options.summary_extractor.is_some(),
+            summary_extractor: options
+                .summary_extractor
+                .unwrap_or(SummaryExtractor::ExitCode),
+            compare_mode: options.compare_mode,
+            comparator: {
+                // Back in line with the compare mode, once restarted after a change of setting
+                options
+                    .comparator
+                    .set(options.compare_mode.comparator(&options.json_ignore));
+                options.comparator
             },
+            format: options.format,
+            json_ignore: options.json_ignore,
+            diff_view: options.diff_view,
+            diff_context: options.diff_context,
+            diff_granularity: options.diff_granularity,
+            elide_unchanged: true,
+            line_numbers: options.line_numbers,
+            theme: options.theme,
+            locale: options.locale,
+            wrap: options.wrap,
+            scroll: 0,
+            column_offset: 0,
+            follow: options.follow,
+            search: Search::default(),
+            filter_enabled: options.filter.is_some(),
+            filter: options.filter,
+            plot: options.plot,
+            alerts: options.alerts,
+            highlights: options.highlights,
+            is_hexdump_toggled: false,
+            table_sort: None,
+            marks: Vec::new(),
+            mark_prompt: None,
+            export_prompt: None,
+            control_receiver: options.control_receiver,
+            unfocused_mode: options.unfocused_mode,
+            is_focused: true,
+            paused: options.paused,
+            refresh: options.refresh,
+            skip_next: options.skip_next,
+            live_interval: options.live_interval,
+            cron: options.cron,
+            keep_open: options.keep_open,
+            cwd: options.cwd,
+            title: options.title,
+            no_title: options.no_title,
+            split: options.split,
+            ab_diff: options.ab_diff,
+            only_changes: options.only_changes,
+            export_dir: options.export_dir,
+            export_chunk_size: options.export_chunk_size,
+            debug_session,
+            report_path: options.report_path,
+            recorder: options.recorder,
+            keybindings: options.keybindings,
+            monochrome: options.monochrome,
+            notice: None,
+            noticed_at: Instant::now(),
+            baseline_accepted_at: None,
+            settings_popup: None,
+            show_help: false,
+            picker: None,
+            show_timeline: true,
+            pane_areas: Vec::new(),
+            selection: None,
+            clipboard: None,
+        }
+    }
+
+    /// Swap in the tabs of a restarted watch with the edited commands, keeping the runtime
+    /// settings and the active tab.
+    pub fn restart(&mut self, tabs: Vec<WatchTab>, should_close_watcher: Arc<AtomicBool>) {
+        self.tabs = tabs;
+        self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+        self.panes = std::mem::take(&mut self.tabs[self.active_tab].panes);
+        self.debug_session.query_state = self.tabs[self.active_tab].query_state.clone();
+        self.should_close_watcher = should_close_watcher;
+    }
+
+    /// Index of the tab shown, the one whose commands get edited with `e`.
+    pub fn active_tab(&self) -> usize {
+        self.active_tab
+    }
+
+    fn select_tab(&mut self, index: usize) {
+        if index == self.active_tab || index >= self.tabs.len() {
+            return;
+        }
+        std::mem::swap(&mut self.panes, &mut self.tabs[self.active_tab].panes);
+        std::mem::swap(&mut self.panes, &mut self.tabs[index].panes);
+        self.active_tab = index;
+        self.debug_session.query_state = self.tabs[index].query_state.clone();
+        self.scroll = 0;
+        self.column_offset = 0;
+        self.selection = None;
+    }
+
+    /// Panes of the tab at `index`, whether active or not.
+    fn tab_panes(&self, index: usize) -> &[WatcherPane] {
+        match index == self.active_tab {
+            true => &self.panes,
+            false => &self.tabs[index].panes,
         }
     }
 
     pub fn run_app(
         &mut self,
         terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    ) -> OpenResult<()> {
-        use WatcherOutputEvent::*;
-
+    ) -> OpenResult<WatcherTuiExit> {
         let mut last_tick = Instant::now();
+        let mut is_suspended_frame_drawn = false;
+        // Only redrawn on events, or on the idle interval for the clocks to keep moving
+        let mut needs_redraw = true;
+        let mut last_redraw = Instant::now();
 
         loop {
-            terminal.draw(|f| self.draw_ui(f))?;
+            // While suspended, the last frame drawn carries the paused indicator
+            let is_suspended = self.is_suspended();
+            let is_redraw_due = needs_redraw || last_redraw.elapsed() >= IDLE_REDRAW_INTERVAL;
+            if is_redraw_due && (!is_suspended || !is_suspended_frame_drawn) {
+                let frame = terminal.draw(|f| self.draw_ui(f))?;
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.record(frame.buffer)?;
+                }
+                needs_redraw = false;
+                last_redraw = Instant::now();
+                is_suspended_frame_drawn = is_suspended;
+            }
+            if let Some(text) = self.clipboard.take() {
+                copy_to_clipboard(&text)?;
+            }
 
-            if let Ok(event) = self.event_receiver.try_recv() {
-                self.current_event = match event {
-                    SetupResult(res) => res,
-                    IterationResult(res) => res,
-                    End => return Ok(()),
+            // The panes of the inactive tabs keep up with their watcher threads as well
+            let mut is_alert_raised = false;
+            let all_panes = self
+                .panes
+                .iter_mut()
+                .chain(self.tabs.iter_mut().flat_map(|tab| tab.panes.iter_mut()));
+            for pane in all_panes {
+                if let Some(is_raised) =
+                    pane.receive_event(self.plot.as_ref(), &self.alerts, self.only_changes)
+                {
+                    needs_redraw = true;
+                    is_alert_raised |= is_raised;
                 }
             }
+            if is_alert_raised {
+                beep()?;
+            }
+            needs_redraw |= self.receive_control_requests();
+            if TERMINATION_REQUESTED.load(Ordering::Acquire)
+                && !self.should_close_watcher.load(Ordering::Acquire)
+            {
+                self.close_watchers();
+            }
+            let has_ended = (0..self.tabs.len())
+                .all(|index| self.tab_panes(index).iter().all(|pane| pane.has_ended));
+            if has_ended && (!self.keep_open || self.should_close_watcher.load(Ordering::Acquire)) {
+                if let Some(report_path) = &self.report_path {
+                    self.write_report(report_path)?;
+                }
+                return Ok(WatcherTuiExit::Ended);
+            }
 
             let timeout = TICK_RATE
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
             if term_event::poll(timeout)? {
-                let ev = term_event::read()?;
-                if let Event::Key(key) = ev {
-                    if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') {
-                        self.should_close_watcher.store(true, Ordering::Release);
+                match term_event::read()? {
+                    Event::Key(key) => {
+                        if let Some(exit) = self.handle_key(key) {
+                            return Ok(exit);
+                        }
                     }
+                    Event::Mouse(mouse) => self.handle_mouse(mouse),
+                    Event::FocusLost => self.set_focused(false),
+                    Event::FocusGained => self.set_focused(true),
+                    _ => {}
                 }
+                // Resizes and pastes included, for the next frame to reflect them right away
+                needs_redraw = true;
             }
 
             if last_tick.elapsed() >= TICK_RATE {
@@ -86,8 +880,1018 @@ impl WatcherTui {
         }
     }
 
+    /// Handle the pending requests of the control socket, returning whether there were any.
+    fn receive_control_requests(&mut self) -> bool {
+        let Some(control_receiver) = &self.control_receiver else {
+            return false;
+        };
+
+        let requests = control_receiver.try_iter().collect::<Vec<_>>();
+        let has_requests = !requests.is_empty();
+        for request in requests {
+            match request {
+                ControlRequest::Mark(label) => self.add_mark(label),
+                ControlRequest::DebugBundle { path, redact } => {
+                    self.set_notice(self.write_debug_bundle(&path, redact))
+                }
+            }
+        }
+        has_requests
+    }
+
+    /// Label the latest iteration of the first pane.
+    fn add_mark(&mut self, label: String) {
+        let iteration = self
+            .panes
+            .first()
+            .map_or(0, |pane| pane.current_event.iteration);
+        self.marks.push(IterationMark {
+            label,
+            iteration,
+            marked_at: Local::now(),
+        });
+    }
+
+    fn handle_mark_prompt_key(&mut self, code: KeyCode) {
+        let Some(label) = &mut self.mark_prompt else {
+            return;
+        };
+
+        match code {
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::Backspace => {
+                label.pop();
+            }
+            KeyCode::Esc => self.mark_prompt = None,
+            KeyCode::Enter => {
+                let label = self.mark_prompt.take().unwrap_or_default();
+                if !label.trim().is_empty() {
+                    self.add_mark(label.trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_export_prompt_key(&mut self, code: KeyCode) {
+        let Some(dir) = &mut self.export_prompt else {
+            return;
+        };
+
+        match code {
+            KeyCode::Char(c) => dir.push(c),
+            KeyCode::Backspace => {
+                dir.pop();
+            }
+            KeyCode::Esc => self.export_prompt = None,
+            KeyCode::Enter => {
+                let dir = self.export_prompt.take().unwrap_or_default();
+                let dir = match dir.trim() {
+                    "" => self.export_dir.clone(),
+                    dir => PathBuf::from(dir),
+                };
+                self.set_notice(self.export_snapshots(&dir));
+            }
+            _ => {}
+        }
+    }
+
+    /// Open the iteration picker on the iteration shown, or close it.
+    fn toggle_picker(&mut self) {
+        self.picker = match self.picker {
+            Some(_) => None,
+            None => Some(IterationPicker {
+                selected: self.panes[0].picked.as_ref().map(|p| p.event.iteration),
+            }),
+        };
+    }
+
+    /// Load the nearest recorded iteration which changed the output, an older one going
+    /// `backwards`. Past the newest change, the latest iteration is shown back.
+    fn jump_to_change(&mut self, backwards: bool) {
+        let history = &self.panes[0].history;
+        let iterations = history.iterations();
+        // The latest iteration is the one shown live
+        let shown = match &self.panes[0].picked {
+            Some(picked) => history.position(picked.event.iteration),
+            None => iterations.len().checked_sub(1),
+        };
+        let Some(shown) = shown else {
+            return;
+        };
+
+        let mut changes = iterations
+            .iter()
+            .enumerate()
+            // The evicted changes can not be loaded anymore
+            .filter(|(_, it)| it.output_changed && it.output.is_some());
+        let target = match backwards {
+            true => changes.rfind(|(index, _)| *index < shown),
+            false => changes.find(|(index, _)| *index > shown),
+        }
+        .map(|(_, it)| it.iteration);
+
+        match (target, backwards) {
+            (None, true) => {
+                self.set_notice(Err("no older change recorded".to_string()));
+                return;
+            }
+            (None, false) if self.panes[0].picked.is_none() => {
+                self.set_notice(Err("already at the latest iteration".to_string()));
+                return;
+            }
+            _ => {}
+        }
+        for pane in &mut self.panes {
+            pane.pick_iteration(target);
+        }
+        if let Some(picker) = &mut self.picker {
+            picker.selected = target;
+        }
+    }
+
+    /// Move through the iteration picker and load its selection, returning whether the key
+    /// got used by the picker.
+    fn handle_picker_key(&mut self, code: KeyCode) -> bool {
+        let Some(picker) = &mut self.picker else {
+            return false;
+        };
+
+        let history = &self.panes[0].history;
+        match code {
+            KeyCode::Up => picker.move_selection(history, -1),
+            KeyCode::Down => picker.move_selection(history, 1),
+            KeyCode::PageUp => picker.move_selection(history, -(PAGE_SCROLL as isize)),
+            KeyCode::PageDown => picker.move_selection(history, PAGE_SCROLL as isize),
+            KeyCode::Home => picker.move_selection(history, isize::MIN),
+            KeyCode::End => picker.move_selection(history, isize::MAX),
+            KeyCode::Enter => {
+                let selected = picker.selected;
+                for pane in &mut self.panes {
+                    pane.pick_iteration(selected);
+                }
+                if let (Some(iteration), None) = (selected, &self.panes[0].picked) {
+                    self.set_notice(Err(format!(
+                        "the output of iteration {iteration} was evicted from the history"
+                    )));
+                }
+            }
+            KeyCode::Esc => self.picker = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Step the interval of the watch up, or down when `faster`, along the `INTERVAL_STEPS`.
+    /// The watcher threads wait for the new interval from the current wait.
+    fn adjust_interval(&mut self, faster: bool) {
+        if self.cron.is_some() {
+            self.set_notice(Err("the runs follow the cron schedule".to_string()));
+            return;
+        }
+
+        let mut live_interval = self.live_interval.lock().unwrap();
+        let adjusted = match faster {
+            true => INTERVAL_STEPS
+                .iter()
+                .rev()
+                .find(|step| **step < *live_interval),
+            false => INTERVAL_STEPS.iter().find(|step| **step > *live_interval),
+        };
+        let adjusted = adjusted.copied().unwrap_or(*live_interval);
+        *live_interval = adjusted;
+        drop(live_interval);
+
+        // The watcher threads of every pane share the interval. Shown right away, rather than
+        // from the next iterations
+        let tab_panes = self.tabs.iter_mut().flat_map(|tab| tab.panes.iter_mut());
+        for pane in self.panes.iter_mut().chain(tab_panes) {
+            pane.current_event.interval = adjusted;
+        }
+        self.set_notice(Ok(format!(
+            "interval set to {}",
+            humantime::format_duration(adjusted)
+        )));
+    }
+
+    /// Cancel the commands running in the panes of the tab, rather than waiting for them to
+    /// complete or time out.
+    fn cancel_running_commands(&mut self) {
+        let running_panes = self
+            .panes
+            .iter()
+            .filter(|pane| matches!(pane.run_state, RunState::Running { .. }))
+            .collect::<Vec<_>>();
+        if running_panes.is_empty() {
+            self.set_notice(Err("no command running".to_string()));
+            return;
+        }
+
+        let mut cancelled = 0;
+        for pane in running_panes {
+            let Some(cancel_handle) = &pane.cancel_handle else {
+                continue;
+            };
+            match cancel_handle.cancel() {
+                Ok(is_cancelled) => cancelled += is_cancelled as usize,
+                Err(err) => warn!("Cannot cancel the command : {err}"),
+            }
+        }
+        self.set_notice(match cancelled {
+            0 => Err("the running command cannot be cancelled".to_string()),
+            1 => Ok("command cancelled".to_string()),
+            _ => Ok(format!("{cancelled} commands cancelled")),
+        });
+    }
+
+    /// Recreate the shells of the panes of the tab, re-running their setup commands, before
+    /// running their next iteration right away. Their running commands get cancelled first.
+    fn restart_shells(&mut self) {
+        for pane in &self.panes {
+            let Some(restart_shell) = &pane.restart_shell else {
+                continue;
+            };
+            restart_shell.store(true, Ordering::Release);
+            if let (RunState::Running { .. }, Some(cancel_handle)) =
+                (pane.run_state, &pane.cancel_handle)
+            {
+                cancel_handle.cancel().ok();
+            }
+        }
+        self.set_notice(Ok("restarting the shell".to_string()));
+    }
+
+    /// Retry the failed watches of the tab, each in a fresh shell.
+    fn retry_failed_watches(&mut self) {
+        let failed_panes = self.panes.iter().filter(|pane| pane.error.is_some());
+        let mut retried = 0;
+        for restart_shell in failed_panes.filter_map(|pane| pane.restart_shell.as_ref()) {
+            restart_shell.store(true, Ordering::Release);
+            retried += 1;
+        }
+        self.set_notice(match retried {
+            0 => Err("the watch cannot be retried".to_string()),
+            _ => Ok("retrying the watch".to_string()),
+        });
+    }
+
+    /// Pin the outputs shown in the panes as the baselines of the diffs, or unpin them.
+    fn toggle_baseline(&mut self) {
+        if self.panes.iter().any(|pane| pane.baseline.is_some()) {
+            for pane in &mut self.panes {
+                pane.baseline = None;
+            }
+            self.set_notice(Ok("baseline unpinned".to_string()));
+            return;
+        }
+        if self
+            .panes
+            .iter()
+            .all(|pane| pane.shown_event().iteration == 0)
+        {
+            self.set_notice(Err("no iteration to pin as the baseline yet".to_string()));
+            return;
+        }
+        for pane in &mut self.panes {
+            pane.baseline = Some(pane.shown_event().clone());
+        }
+        self.diff_view = true;
+        let iteration = self.panes[0].shown_event().iteration;
+        self.set_notice(Ok(format!("baseline pinned at Itr {iteration}")));
+    }
+
+    /// Promote the outputs shown, the picked ones or else the latest ones, to the new baselines,
+    /// acknowledging the drift from the previous ones.
+    fn accept_baseline(&mut self) {
+        if self.panes.iter().all(|pane| pane.baseline.is_none()) {
+            self.set_notice(Err("no baseline pinned, pin one with p".to_string()));
+            return;
+        }
+        for pane in &mut self.panes {
+            pane.baseline = Some(pane.shown_event().clone());
+        }
+        self.baseline_accepted_at = Some(Instant::now());
+        let iteration = self.panes[0].shown_event().iteration;
+        self.set_notice(Ok(format!("accepted Itr {iteration} as the new baseline")));
+    }
+
+    fn set_notice(&mut self, notice: Result<String, String>) {
+        self.notice = Some(notice);
+        self.noticed_at = Instant::now();
+    }
+
+    fn set_focused(&mut self, is_focused: bool) {
+        self.is_focused = is_focused;
+        if self.unfocused_mode == UnfocusedMode::Pause {
+            self.paused.store(!is_focused, Ordering::Release);
+        }
+    }
+
+    /// Whether rendering is suspended, for the terminal lost focus.
+    fn is_suspended(&self) -> bool {
+        !self.is_focused && self.unfocused_mode != UnfocusedMode::Keep
+    }
+
+    /// Close the watchers of all the tabs, cancelling their running commands.
+    fn close_watchers(&mut self) {
+        self.should_close_watcher.store(true, Ordering::Release);
+        // Commands still running would otherwise hold the exit until they end
+        self.panes
+            .iter()
+            .chain(self.tabs.iter().flat_map(|tab| &tab.panes))
+            .filter_map(|pane| pane.cancel_handle.as_ref())
+            .for_each(|cancel_handle| {
+                cancel_handle.cancel().ok();
+            });
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<WatcherTuiExit> {
+        if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('c') {
+            self.close_watchers();
+            return None;
+        }
+        self.notice = None;
+        self.selection = None;
+        if self.show_help {
+            self.show_help = false;
+            return None;
+        }
+        if self.settings_popup.is_some() {
+            self.handle_settings_key(key.code);
+            return None;
+        }
+        if self.search.prompt.is_some() {
+            self.handle_search_prompt_key(key.code);
+            return None;
+        }
+        if self.mark_prompt.is_some() {
+            self.handle_mark_prompt_key(key.code);
+            return None;
+        }
+        if self.export_prompt.is_some() {
+            self.handle_export_prompt_key(key.code);
+            return None;
+        }
+        if self.handle_picker_key(key.code) {
+            return None;
+        }
+        if key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return None;
+        }
+
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(self.keybindings.get(&c).copied().unwrap_or(c)),
+            code => code,
+        };
+        match code {
+            KeyCode::Char('?') => self.show_help = true,
+            KeyCode::Char('t') => self.show_summary = !self.show_summary,
+            KeyCode::Char(',') => self.settings_popup = Some(SettingsPopup::default()),
+            KeyCode::Char('e') => return Some(WatcherTuiExit::EditQuery),
+            KeyCode::Char('d') => self.diff_view = !self.diff_view,
+            KeyCode::Char('g') => self.diff_granularity = self.diff_granularity.next(),
+            KeyCode::Char('p') => self.toggle_baseline(),
+            KeyCode::Char('i') => self.toggle_picker(),
+            KeyCode::Char('[') => self.jump_to_change(true),
+            KeyCode::Char(']') => self.jump_to_change(false),
+            KeyCode::Char('T') => self.show_timeline = !self.show_timeline,
+            KeyCode::Char('a') => self.accept_baseline(),
+            KeyCode::Char('x') => self.elide_unchanged = !self.elide_unchanged,
+            KeyCode::Char('l') => self.line_numbers = !self.line_numbers,
+            KeyCode::Char('f') if self.filter.is_some() => {
+                self.filter_enabled = !self.filter_enabled
+            }
+            KeyCode::Char('/') => self.search.prompt = Some(String::new()),
+            KeyCode::Char('m') => self.mark_prompt = Some(String::new()),
+            KeyCode::Char('s') => self.set_notice(self.export_snapshots(&self.export_dir)),
+            KeyCode::Char('S') => self.export_prompt = Some(self.export_dir.display().to_string()),
+            KeyCode::Char(key @ ('b' | 'B')) => {
+                let path = self.export_dir.join(default_bundle_name());
+                self.set_notice(self.write_debug_bundle(&path, key == 'B'));
+            }
+            KeyCode::Char('R') => {
+                let path = self.export_dir.join(default_report_name());
+                self.set_notice(
+                    self.write_report(&path)
+                        .map(|()| format!("report written to {}", path.display()))
+                        .map_err(|err| format!("report failed: {err}")),
+                );
+            }
+            KeyCode::Char('r') if self.panes.iter().any(|pane| pane.error.is_some()) => {
+                self.retry_failed_watches()
+            }
+            KeyCode::Char('r') => self.refresh.store(true, Ordering::Release),
+            KeyCode::Char('+' | '=') => self.adjust_interval(false),
+            KeyCode::Char('-') => self.adjust_interval(true),
+            KeyCode::Char('j') => {
+                self.skip_next.fetch_xor(true, Ordering::AcqRel);
+            }
+            KeyCode::Char('k') => self.cancel_running_commands(),
+            KeyCode::Char('X') => self.restart_shells(),
+            KeyCode::Char('n') => self.jump_to_match(false),
+            KeyCode::Char('N') => self.jump_to_match(true),
+            KeyCode::Esc => self.search.clear(),
+            KeyCode::Char('F') => self.follow = !self.follow,
+            KeyCode::Char('h') => self.is_hexdump_toggled = !self.is_hexdump_toggled,
+            KeyCode::Char('o') if self.format.is_tabular() => self.sort_next_column(),
+            KeyCode::Char('O') if self.format.is_tabular() => {
+                if let Some(sort) = &mut self.table_sort {
+                    sort.descending = !sort.descending;
+                }
+            }
+            KeyCode::Up => {
+                self.follow = false;
+                self.scroll = self.scroll.saturating_sub(1);
+            }
+            KeyCode::Down => self.scroll = self.scroll.saturating_add(1),
+            KeyCode::PageUp => {
+                self.follow = false;
+                self.scroll = self.scroll.saturating_sub(PAGE_SCROLL);
+            }
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(PAGE_SCROLL),
+            KeyCode::Left if !self.wrap => {
+                self.column_offset = self.column_offset.saturating_sub(HORIZONTAL_SCROLL)
+            }
+            KeyCode::Right if !self.wrap => {
+                self.column_offset = self.column_offset.saturating_add(HORIZONTAL_SCROLL)
+            }
+            KeyCode::Char('w') => self.wrap = !self.wrap,
+            KeyCode::Char('z') => self.no_title = !self.no_title,
+            KeyCode::Tab => self.select_tab((self.active_tab + 1) % self.tabs.len()),
+            KeyCode::BackTab => {
+                self.select_tab((self.active_tab + self.tabs.len() - 1) % self.tabs.len())
+            }
+            KeyCode::Home => {
+                self.follow = false;
+                self.scroll = 0;
+                self.column_offset = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Select the output text by dragging over a pane, copied to the clipboard on release.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let at = layout::Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.selection = self
+                    .pane_areas
+                    .iter()
+                    .find(|area| area.contains(at))
+                    .map(|&area| Selection::new(area, at));
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(selection) = &mut self.selection {
+                    selection.extend_to(at);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => match &mut self.selection {
+                Some(selection) if !selection.is_empty() => selection.is_released = true,
+                _ => self.selection = None,
+            },
+            MouseEventKind::ScrollUp => {
+                self.follow = false;
+                self.scroll = self.scroll.saturating_sub(MOUSE_SCROLL);
+            }
+            MouseEventKind::ScrollDown => self.scroll = self.scroll.saturating_add(MOUSE_SCROLL),
+            _ => {}
+        }
+    }
+
+    fn handle_search_prompt_key(&mut self, code: KeyCode) {
+        let Some(pattern) = &mut self.search.prompt else {
+            return;
+        };
+
+        match code {
+            KeyCode::Char(c) => pattern.push(c),
+            KeyCode::Backspace => {
+                pattern.pop();
+            }
+            KeyCode::Esc => self.search.prompt = None,
+            KeyCode::Enter => {
+                self.search.submit();
+                self.scroll_to_selected_match();
+            }
+            _ => {}
+        }
+    }
+
+    /// Select the next match of the search, or the previous one going `backwards`, and scroll to it.
+    /// Matches are searched in the output of the first pane.
+    fn jump_to_match(&mut self, backwards: bool) {
+        let Some(pane) = self.panes.first() else {
+            return;
+        };
+
+        let match_count = self
+            .search
+            .matches(&self.visible_output(&pane.shown_event().output))
+            .len();
+        self.search.select_next(match_count, backwards);
+        self.scroll_to_selected_match();
+    }
+
+    fn scroll_to_selected_match(&mut self) {
+        let Some(pane) = self.panes.first() else {
+            return;
+        };
+
+        let matches = self
+            .search
+            .matches(&self.visible_output(&pane.shown_event().output));
+        if let Some(selected) = matches.get(self.search.selected_match) {
+            self.follow = false;
+            self.scroll = selected.line_index.saturating_sub(SCROLL_MARGIN) as u16;
+        }
+    }
+
+    /// Order the table rows by the next column, back to the output order past the last one.
+    fn sort_next_column(&mut self) {
+        let column_count = self
+            .panes
+            .first()
+            .and_then(|pane| self.columnar_output(&pane.shown_event().output))
+            .map_or(0, |table| table.header.len());
+
+        self.table_sort = match self.table_sort {
+            None if column_count > 0 => Some(TableSort {
+                column: 0,
+                descending: false,
+            }),
+            Some(sort) if sort.column + 1 < column_count => Some(TableSort {
+                column: sort.column + 1,
+                ..sort
+            }),
+            _ => None,
+        };
+    }
+
+    /// Export the current output of each pane into `dir`, listing the written paths.
+    fn export_snapshots(&self, dir: &Path) -> Result<String, String> {
+        let taken_at = Local::now().format("%Y%m%d-%H%M%S");
+        let paths = self
+            .panes
+            .iter()
+            .map(|pane| {
+                let iteration = pane.current_event.iteration;
+                let mut name = format!("watch-{taken_at}-itr{iteration}");
+                if let Some(title) = &pane.title {
+                    name.push('-');
+                    name.extend(
+                        title
+                            .chars()
+                            .filter(|c| c.is_alphanumeric() || "-_.".contains(*c)),
+                    );
+                }
+
+                export_snapshot(
+                    dir,
+                    &name,
+                    iteration,
+                    &pane.current_event.output,
+                    self.export_chunk_size,
+                )
+                .map(|path| path.display().to_string())
+                .map_err(|err| format!("export failed: {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(format!("exported {}", paths.join(", ")))
+    }
+
+    /// Write the session report of the panes of all the tabs at `path`.
+    fn write_report(&self, path: &Path) -> OpenResult<()> {
+        let panes = (0..self.tabs.len())
+            .flat_map(|index| {
+                let command = self.tabs[index].query_state.main_commands.as_str();
+                self.tab_panes(index).iter().map(move |pane| PaneHistory {
+                    title: pane.title.as_deref(),
+                    command,
+                    history: &pane.history,
+                })
+            })
+            .collect::<Vec<_>>();
+        write_report(path, &self.debug_session.query_state, &panes)
+    }
+
+    /// Package the recent iterations of the panes into a debug bundle at `path`.
+    fn write_debug_bundle(&self, path: &Path, redact: bool) -> Result<String, String> {
+        let panes = self
+            .panes
+            .iter()
+            .map(|pane| PaneIterations {
+                title: pane.title.as_deref(),
+                iterations: pane.recent_events.iter().collect(),
+            })
+            .collect::<Vec<_>>();
+
+        write_debug_bundle(path, &self.debug_session, &panes, redact)
+            .map(|()| format!("debug bundle written to {}", path.display()))
+            .map_err(|err| format!("debug bundle failed: {err}"))
+    }
+
+    /// Spinner while any of the commands runs, else the countdown to the next run.
+    fn run_state_line(&self) -> String {
+        let now = Instant::now();
+        let running_since = self
+            .panes
+            .iter()
+            .filter_map(|pane| match pane.run_state {
+                RunState::Running { since } => Some(since),
+                _ => None,
+            })
+            .min();
+        if let Some(since) = running_since {
+            let elapsed = now.duration_since(since);
+            let frame = elapsed.as_millis() / SPINNER_FRAME_DURATION.as_millis();
+            return format!(
+                " {} running {}s",
+                SPINNER_FRAMES[frame as usize % SPINNER_FRAMES.len()],
+                self.locale.format_decimal(elapsed.as_secs_f64(), 1)
+            );
+        }
+        if self.skip_next.load(Ordering::Acquire) {
+            return " next run skipped".to_string();
+        }
+
+        let next_run = self
+            .panes
+            .iter()
+            .filter_map(|pane| match pane.run_state {
+                RunState::Sleeping { until } => Some(until),
+                _ => None,
+            })
+            .min();
+        match next_run {
+            // The scheduled runs are far apart, their time is more telling than the countdown
+            Some(until) if self.cron.is_some() => {
+                let remaining = until.saturating_duration_since(now);
+                let next_run = Local::now() + remaining;
+                format!(
+                    " next run at {} (in {})",
+                    self.locale.format_clock(&next_run, false),
+                    humantime::format_duration(Duration::from_secs(remaining.as_secs()))
+                )
+            }
+            Some(until) => format!(
+                " next run in {}s",
+                self.locale
+                    .format_decimal(until.saturating_duration_since(now).as_secs_f64(), 1)
+            ),
+            None if self
+                .panes
+                .iter()
+                .any(|pane| matches!(pane.run_state, RunState::WaitingForChange)) =>
+            {
+                " waiting for a file change".to_string()
+            }
+            None if self.keep_open && self.panes.iter().all(|pane| pane.has_ended) => {
+                " done, Ctrl+C to quit".to_string()
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Content of the status bar: the search prompt or its state, else the status of the watch.
+    fn status_line(&self) -> Line<'_> {
+        if let Some(pattern) = &self.search.prompt {
+            return Line::raw(format!("/{pattern}█"));
+        }
+        if let Some(label) = &self.mark_prompt {
+            return Line::raw(format!("⚑ Mark label: {label}█"));
+        }
+        if let Some(dir) = &self.export_prompt {
+            return Line::raw(format!("⤓ Export to: {dir}█"));
+        }
+        match self
+            .notice
+            .as_ref()
+            .filter(|_| self.noticed_at.elapsed() < NOTICE_DURATION)
+        {
+            Some(Ok(notice)) => return Line::raw(format!("✔ {notice}")).fg(tailwind::GREEN.c400),
+            Some(Err(err)) => return Line::raw(format!("✘ {err}")).fg(tailwind::RED.c400),
+            None => {}
+        }
+        if let Some(err) = &self.search.error {
+            return Line::raw(format!("✘ invalid search: {err}")).fg(tailwind::RED.c400);
+        }
+        if let Some(regex) = &self.search.regex {
+            let match_count = self.panes.first().map_or(0, |pane| {
+                self.search
+                    .matches(&self.visible_output(&pane.shown_event().output))
+                    .len()
+            });
+            let position = match match_count {
+                0 => "no matches".to_string(),
+                _ => format!(
+                    "{}/{match_count} matches",
+                    self.search.selected_match.min(match_count - 1) + 1
+                ),
+            };
+            return Line::raw(format!(
+                "🔍 {regex} │ {position} │ n / N to navigate │ Esc to clear"
+            ));
+        }
+
+        self.watch_status_line()
+    }
+
+    /// The watched command, its effective interval, the outcome of the last iteration and
+    /// whether the watch is paused. In fan-out mode, the outcomes of all the panes are counted.
+    fn watch_status_line(&self) -> Line<'_> {
+        let separator = || Span::raw(" │ ").fg(tailwind::SLATE.c500);
+        let mut spans = match self.paused.load(Ordering::Acquire) {
+            true => vec![Span::raw("⏸ paused").fg(tailwind::AMBER.c300)],
+            false => vec![Span::raw("▶ running").fg(self.palette().c400)],
+        };
+
+        let command = summarize_command(
+            &self.debug_session.query_state.main_commands,
+            STATUS_COMMAND_WIDTH,
+        );
+        spans.extend([separator(), Span::raw(format!("$ {command}"))]);
+
+        let Some(first_pane) = self.panes.first() else {
+            return Line::from(spans);
+        };
+        if let Some(cron) = &self.cron {
+            spans.extend([separator(), Span::raw(format!("at {cron}"))]);
+        } else if first_pane.current_event.interval > Duration::ZERO {
+            spans.extend([
+                separator(),
+                Span::raw(format!(
+                    "every {}",
+                    humantime::format_duration(first_pane.current_event.interval)
+                )),
+            ]);
+        }
+
+        let statuses = self
+            .panes
+            .iter()
+            .map(|pane| SummaryStatus::of(&pane.current_event))
+            .collect::<Vec<_>>();
+        let failures = statuses
+            .iter()
+            .filter(|status| matches!(status, SummaryStatus::Failed | SummaryStatus::TimedOut))
+            .count();
+        let outcome = match (self.panes.len(), statuses[0]) {
+            _ if statuses
+                .iter()
+                .all(|status| *status == SummaryStatus::Pending) =>
+            {
+                Span::raw("waiting for the first run").fg(tailwind::SLATE.c400)
+            }
+            (1, SummaryStatus::TimedOut) => Span::raw("timed out").fg(tailwind::RED.c400),
+            (1, status) => {
+                let exit_code = first_pane.current_event.exit_code.unwrap_or_default();
+                let color = match status {
+                    SummaryStatus::Ok => tailwind::GREEN.c400,
+                    _ => tailwind::RED.c400,
+                };
+                Span::raw(format!("exit {exit_code}")).fg(color)
+            }
+            (pane_count, _) if failures > 0 => {
+                Span::raw(format!("{failures} of {pane_count} failing")).fg(tailwind::RED.c400)
+            }
+            (pane_count, _) => {
+                let ok_count = statuses
+                    .iter()
+                    .filter(|status| **status == SummaryStatus::Ok)
+                    .count();
+                Span::raw(format!("{ok_count} of {pane_count} ok")).fg(tailwind::GREEN.c400)
+            }
+        };
+        spans.extend([separator(), outcome]);
+
+        let run_duration = self
+            .panes
+            .iter()
+            .filter(|pane| pane.current_event.iteration > 0)
+            .map(|pane| pane.current_event.run_duration)
+            .max();
+        if let Some(run_duration) = run_duration {
+            spans.extend([
+                separator(),
+                Span::raw(format!(
+                    "took {}",
+                    self.locale.format_duration(run_duration)
+                )),
+            ]);
+        }
+
+        // Events of the watcher threads lost while the TUI was not keeping up
+        let dropped_events = self
+            .panes
+            .iter()
+            .map(|pane| pane.dropped_events.load(Ordering::Relaxed))
+            .sum::<usize>();
+        if dropped_events > 0 {
+            spans.extend([
+                separator(),
+                Span::raw(format!(
+                    "⚠ dropped {} events",
+                    self.locale.format_integer(dropped_events)
+                ))
+                .fg(tailwind::AMBER.c300),
+            ]);
+        }
+
+        spans.extend([separator(), Span::raw("? help").fg(tailwind::SLATE.c400)]);
+        Line::from(spans)
+    }
+
+    /// Key bindings active in the current view, with the keys remapped onto them.
+    fn help_popup(&self) -> HelpPopup {
+        let view = match (self.show_summary, self.diff_view) {
+            (true, _) => "Summary table",
+            (false, true) => "Diff view",
+            (false, false) => "Output view",
+        };
+        let mut mode = format!("{view} │ {}", self.setting_value(Setting::Format));
+        if self.follow {
+            mode.push_str(" │ following");
+        }
+        if self.is_suspended() {
+            mode.push_str(" │ paused");
+        }
+
+        let mut help = HelpPopup::new(mode);
+        let mut bind =
+            |keys: &[char], action| help.bind_remappable(keys, action, &self.keybindings);
+        bind(&['?'], "Toggle this help");
+        bind(&['/'], "Search the output");
+        if self.search.regex.is_some() {
+            bind(&['n', 'N'], "Next / previous match");
+        }
+        bind(&['d'], "Toggle the diff view");
+        bind(&['g'], "Cycle the diff granularity");
+        bind(&['i'], "Toggle the iteration picker");
+        bind(&['[', ']'], "Previous / next change of the output");
+        bind(&['T'], "Toggle the change timeline");
+        bind(&['p'], "Pin / unpin the output as the diff baseline");
+        if self.panes.iter().any(|pane| pane.baseline.is_some()) {
+            bind(&['a'], "Accept the output as the new baseline");
+        }
+        bind(&['x'], "Toggle eliding the unchanged lines");
+        bind(&['l'], "Toggle the line numbers");
+        bind(&['w'], "Toggle wrapping the lines");
+        bind(&['r'], "Run the next iteration now / retry a failed watch");
+        bind(&['+', '-'], "Lengthen / shorten the interval");
+        bind(&['j'], "Skip the next run / undo");
+        bind(&['k'], "Cancel the running command");
+        bind(&['X'], "Restart the shell");
+        bind(&['F'], "Toggle following the output");
+        bind(&['z'], "Toggle the fullscreen output");
+        bind(&['h'], "Toggle the hexdump");
+        if self.filter.is_some() {
+            bind(&['f'], "Toggle the line filter");
+        }
+        if self.format.is_tabular() {
+            bind(&['o', 'O'], "Sort by the next column / reverse");
+        }
+        bind(&['t'], "Toggle the summary table");
+        bind(&[','], "Open the settings");
+        bind(&['m'], "Mark the current iteration");
+        bind(&['e'], "Edit the commands");
+        bind(&['s', 'S'], "Export snapshots / to a directory");
+        bind(&['R'], "Write a session report");
+        bind(&['b', 'B'], "Write a debug bundle / redacted");
+        help.bind(
+            match self.wrap {
+                true => "▲ ▼ PgUp PgDn",
+                false => "▲ ▼ ◄ ► PgUp PgDn",
+            },
+            "Scroll",
+        );
+        help.bind("Home", "Back to the top");
+        if self.tabs.len() > 1 {
+            help.bind("Tab / Shift+Tab", "Next / previous tab");
+        }
+        help.bind("Esc", "Clear the search");
+        help.bind("Mouse drag", "Copy the selected text");
+        help.bind("Ctrl+C", "Quit");
+        help
+    }
+
+    fn handle_settings_key(&mut self, code: KeyCode) {
+        let Some(popup) = &mut self.settings_popup else {
+            return;
+        };
+
+        match code {
+            KeyCode::Esc | KeyCode::Char(',') => self.settings_popup = None,
+            KeyCode::Up => popup.selected = popup.selected.previous(),
+            KeyCode::Down => popup.selected = popup.selected.next(),
+            KeyCode::Left => {
+                let selected = popup.selected;
+                self.change_setting(selected, false);
+            }
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char(' ') => {
+                let selected = popup.selected;
+                self.change_setting(selected, true);
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the `setting` to its next value. The interval is made longer, or shorter when not
+    /// `forward`, as with `+` and `-`.
+    fn change_setting(&mut self, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Format => self.format = self.format.next(),
+            Setting::CompareMode => {
+                self.compare_mode = next_compare_mode(self.compare_mode);
+                self.comparator
+                    .set(self.compare_mode.comparator(&self.json_ignore));
+            }
+            Setting::DiffView => self.diff_view = !self.diff_view,
+            Setting::DiffGranularity => self.diff_granularity = self.diff_granularity.next(),
+            Setting::ElideUnchanged => self.elide_unchanged = !self.elide_unchanged,
+            Setting::Filter if self.filter.is_some() => self.filter_enabled = !self.filter_enabled,
+            Setting::Filter => {}
+            Setting::LineNumbers => self.line_numbers = !self.line_numbers,
+            Setting::Wrap => self.wrap = !self.wrap,
+            Setting::Summary => self.show_summary = !self.show_summary,
+            Setting::Follow => self.follow = !self.follow,
+            Setting::Theme => self.theme = self.theme.next(),
+            Setting::Interval => self.adjust_interval(!forward),
+        }
+    }
+
+    fn setting_value(&self, setting: Setting) -> String {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+
+        match setting {
+            Setting::Format => match self.format {
+                OutputFormat::Text => "text".to_string(),
+                OutputFormat::Json => "json".to_string(),
+                OutputFormat::Auto => "auto (pretty JSON)".to_string(),
+                OutputFormat::Table => "table".to_string(),
+                OutputFormat::Csv => "csv".to_string(),
+                OutputFormat::Markdown => "markdown".to_string(),
+            },
+            Setting::CompareMode => self.compare_mode.to_string(),
+            Setting::DiffView => on_off(self.diff_view),
+            Setting::DiffGranularity => match self.diff_granularity {
+                DiffGranularity::Line => "line".to_string(),
+                DiffGranularity::Word => "word".to_string(),
+                DiffGranularity::Char => "char".to_string(),
+            },
+            Setting::ElideUnchanged => on_off(self.elide_unchanged),
+            Setting::Filter => match &self.filter {
+                Some(filter) if self.filter_enabled => filter.to_string(),
+                Some(filter) => format!("{filter} (off)"),
+                None => "none".to_string(),
+            },
+            Setting::LineNumbers => on_off(self.line_numbers),
+            Setting::Wrap => on_off(self.wrap),
+            Setting::Summary => on_off(self.show_summary),
+            Setting::Follow => on_off(self.follow),
+            Setting::Theme => self
+                .theme
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            Setting::Interval => self.interval_setting_value(),
+        }
+    }
+
+    /// Interval set for the panes of the tab, along with the ones of the panes running at
+    /// another, like while they back off from failures.
+    fn interval_setting_value(&self) -> String {
+        if let Some(cron) = &self.cron {
+            return format!("at {cron}");
+        }
+        let live_interval = *self.live_interval.lock().unwrap();
+        let mut pane_intervals = self
+            .panes
+            .iter()
+            .map(|pane| pane.current_event.interval)
+            .filter(|&interval| interval != live_interval && !interval.is_zero())
+            .collect::<Vec<_>>();
+        pane_intervals.sort();
+        pane_intervals.dedup();
+
+        let mut value = humantime::format_duration(live_interval).to_string();
+        if !pane_intervals.is_empty() {
+            let pane_intervals = pane_intervals
+                .into_iter()
+                .map(|interval| humantime::format_duration(interval).to_string())
+                .collect::<Vec<_>>();
+            write!(value, " (some panes at {})", pane_intervals.join(", ")).unwrap();
+        }
+        value
+    }
+
     fn palette(&self) -> Palette {
-        tailwind::LIME
+        self.theme.palette()
     }
 
     fn draw_ui(&mut self, f: &mut Frame) {
@@ -98,65 +1902,789 @@ impl WatcherTui {
         // with at least a margin of 1
         let area = f.area();
 
-        let time_string = format!(
-            "{}.{:0^2}",
-            render_time.format("%b %d %H:%M:%S"),
-            render_time.nanosecond() / 10u32.pow(7)
-        );
+        let time_string = self.locale.format_clock(&render_time, true);
+
+        let title = match &self.title {
+            Some(title) => format!("  {title}  "),
+            None => format!("  📁 {}  ", self.cwd),
+        };
 
         // Surrounding block
         let block = Block::default()
             .borders(Borders::TOP | Borders::RIGHT)
+            .title(Title::from(title.fg(self.palette().c400)).alignment(Alignment::Left))
             .title(format!("  {time_string}  ").fg(self.palette().c200))
             .title_alignment(Alignment::Right)
             .border_type(BorderType::Rounded);
-        f.render_widget(block, area);
+        // Latest mark, to follow the iterations since an external action
+        let block = match self.marks.last() {
+            Some(mark) => block.title(
+                Title::from(
+                    format!(
+                        "  ⚑ {} │ Itr: {} │ {}  ",
+                        mark.label,
+                        self.locale.format_integer(mark.iteration),
+                        self.locale.format_clock(&mark.marked_at, false)
+                    )
+                    .fg(tailwind::SKY.c300),
+                )
+                .alignment(Alignment::Left),
+            ),
+            None => block,
+        };
+        let block = match self.is_suspended() {
+            true => block.title(
+                Title::from("  ⏸ paused (unfocused)  ".fg(tailwind::AMBER.c300))
+                    .alignment(Alignment::Center),
+            ),
+            false => block,
+        };
+        let block = match self.panes.iter().any(|pane| pane.alert.is_some()) {
+            true => block.border_style(Style::default().fg(tailwind::RED.c500)),
+            false => block,
+        };
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(1)
-            .constraints([Constraint::Percentage(95), Constraint::Percentage(5)].as_ref())
-            .split(area);
+        // Without the chrome, the outputs take the whole screen
+        let (content_area, status_area) = match self.no_title {
+            true => (area, None),
+            false => {
+                f.render_widget(block, area);
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .margin(1)
+                    .constraints([Constraint::Percentage(95), Constraint::Percentage(5)].as_ref())
+                    .split(area);
+                (chunks[0], Some(chunks[1]))
+            }
+        };
 
-        // Top right inner block with styled title aligned to the right
-        let block = Block::default()
-            .title(Span::styled(
-                format!("  Itr: {}  ", self.current_event.iteration),
+        // Timeline of the iterations of the first pane, at the bottom of the content
+        let content_area = match self.show_timeline && !self.no_title {
+            true => {
+                let [content_area, timeline_area] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(1)])
+                        .areas(content_area);
+                f.render_widget(
+                    timeline_line(
+                        &self.panes[0].history,
+                        timeline_area.width,
+                        &self.locale,
+                        self.palette(),
+                    ),
+                    timeline_area,
+                );
+                content_area
+            }
+            false => content_area,
+        };
+
+        // Chart of the extracted metric below the outputs
+        let output_area = match &self.plot {
+            Some(plot) => {
+                let [output_area, chart_area] =
+                    Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)])
+                        .areas(content_area);
+                let series = self
+                    .panes
+                    .iter()
+                    .map(|pane| (pane.title.as_deref(), &pane.metric_series))
+                    .collect::<Vec<_>>();
+                f.render_widget(
+                    metric_chart(plot, &series, self.palette(), &self.locale),
+                    chart_area,
+                );
+                output_area
+            }
+            None => content_area,
+        };
+        let output_area = match self.tabs.len() {
+            1 => output_area,
+            _ => {
+                let [tabs_area, output_area] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Fill(1)])
+                        .areas(output_area);
+                f.render_widget(self.tab_bar(), tabs_area);
+                output_area
+            }
+        };
+
+        // Iteration picker on the right of the outputs
+        let output_area = match &self.picker {
+            Some(picker) if !self.show_summary => {
+                let [output_area, picker_area] =
+                    Layout::horizontal([Constraint::Fill(1), Constraint::Length(PICKER_WIDTH)])
+                        .areas(output_area);
+                let (list, mut state) =
+                    picker.widget(&self.panes[0].history, &self.locale, self.palette());
+                f.render_stateful_widget(list, picker_area, &mut state);
+                output_area
+            }
+            _ => output_area,
+        };
+
+        if self.show_summary {
+            f.render_widget(self.summary_table(), output_area);
+            self.pane_areas.clear();
+        } else if self.ab_diff && self.diff_view && self.panes.len() == 2 {
+            self.pane_areas = vec![self.draw_ab_diff(f, output_area)];
+        } else {
+            let (scroll, pane_areas) = self.draw_panes(f, output_area);
+            if self.follow {
+                self.scroll = scroll;
+            }
+            self.pane_areas = pane_areas;
+        }
+
+        // Selected cells are read back from the rendered frame, as they are shown
+        if let Some(selection) = &mut self.selection {
+            if std::mem::take(&mut selection.is_released) {
+                let text = selection.text(f.buffer_mut());
+                self.notice = Some(Ok(format!("copied {} characters", text.chars().count())));
+                self.noticed_at = Instant::now();
+                self.clipboard = Some(text);
+            }
+            selection.highlight(f.buffer_mut());
+        }
+
+        match status_area {
+            Some(status_area) => self.draw_status_area(f, status_area),
+            // Prompts and notices still show up on the last row
+            None if self.has_status_message() => {
+                let [_, last_row] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+                f.render_widget(Clear, last_row);
+                f.render_widget(self.status_line(), last_row);
+            }
+            None => {}
+        }
+
+        if let Some(popup) = &self.settings_popup {
+            let popup_area = SettingsPopup::area(area);
+            f.render_widget(Clear, popup_area);
+            f.render_widget(
+                popup.widget(|setting| self.setting_value(setting), self.palette()),
+                popup_area,
+            );
+        }
+        if self.show_help {
+            let help = self.help_popup();
+            let help_area = help.area(area);
+            f.render_widget(Clear, help_area);
+            f.render_widget(help.widget(self.palette()), help_area);
+        }
+        if self.monochrome {
+            strip_colors(f.buffer_mut());
+        }
+    }
+
+    /// Commands of the tabs, the failing ones in red.
+    fn tab_bar(&self) -> Tabs<'_> {
+        let titles = self.tabs.iter().enumerate().map(|(index, tab)| {
+            let title = summarize_command(&tab.query_state.main_commands, TAB_TITLE_WIDTH);
+            let is_failing = self.tab_panes(index).iter().any(|pane| {
+                matches!(
+                    SummaryStatus::of(&pane.current_event),
+                    SummaryStatus::Failed | SummaryStatus::TimedOut
+                )
+            });
+            let title = Line::raw(format!(" {} {title} ", index + 1));
+            match is_failing {
+                true => title.fg(tailwind::RED.c400),
+                false => title,
+            }
+        });
+        Tabs::new(titles)
+            .select(self.active_tab)
+            .highlight_style(
                 Style::default()
                     .fg(self.palette().c200)
                     .bg(self.palette().c900)
                     .add_modifier(Modifier::BOLD),
-            ))
-            .title_alignment(Alignment::Right);
-
-        let para = Paragraph::new(Text::raw(&self.current_event.output))
-            .block(block)
-            .wrap(Wrap { trim: false });
-        f.render_widget(para, chunks[0]);
+            )
+            .padding("", "")
+            .divider("│")
+    }
 
+    /// Status bar, next to the state of the runs.
+    fn draw_status_area(&self, f: &mut Frame, area: Rect) {
         // Bottom two inner blocks
         let bottom_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(85), Constraint::Percentage(15)])
-            .split(chunks[1]);
+            .constraints([Constraint::Percentage(75), Constraint::Percentage(25)])
+            .split(area);
 
-        // Bottom left block with all default borders
-        let block = Block::default().title("With borders").borders(Borders::ALL);
-        f.render_widget(block, bottom_chunks[0]);
+        // Bottom left status bar
+        let status_bar = Block::default()
+            .title(self.status_line())
+            .borders(Borders::ALL);
+        f.render_widget(status_bar, bottom_chunks[0]);
 
         let mut extra_info = String::new();
-        if let Ok(timezone) = iana_time_zone::get_timezone() {
+        if let Some(timezone) = local_timezone_name() {
             write!(&mut extra_info, " ⌛ {timezone}").unwrap();
         }
 
         // Bottom right block with styled left and right border
         let block = Block::default()
+            .title(Title::from(self.run_state_line()).alignment(Alignment::Left))
             .title(extra_info)
-            .title_alignment(Alignment::Center)
+            .title_alignment(Alignment::Right)
             .title_position(Position::Top)
             .border_style(Style::default().fg(self.palette().c500))
             .borders(Borders::LEFT | Borders::RIGHT)
             .border_type(BorderType::Thick);
         f.render_widget(block, bottom_chunks[1]);
     }
+
+    /// Whether a prompt or a notice replaces the status of the watch in the status bar.
+    fn has_status_message(&self) -> bool {
+        self.search.prompt.is_some()
+            || self.mark_prompt.is_some()
+            || self.export_prompt.is_some()
+            || (self.notice.is_some() && self.noticed_at.elapsed() < NOTICE_DURATION)
+    }
+
+    /// One row per pane, reduced by the summary extractor, with the unhealthy panes first.
+    fn summary_table(&self) -> Table<'_> {
+        let mut rows = self
+            .panes
+            .iter()
+            .map(|pane| {
+                let title = pane.title.clone().unwrap_or_else(|| "local".to_string());
+                let status = SummaryStatus::of(&pane.current_event);
+                (status, title, pane)
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|(status, title, _), (other_status, other_title, _)| {
+            status
+                .cmp(other_status)
+                .then_with(|| title.cmp(other_title))
+        });
+
+        let rows = rows.into_iter().map(|(status, title, pane)| {
+            let status_color = match status {
+                SummaryStatus::TimedOut | SummaryStatus::Failed => tailwind::RED.c400,
+                SummaryStatus::Cancelled => tailwind::AMBER.c400,
+                SummaryStatus::Pending => tailwind::SLATE.c400,
+                SummaryStatus::Ok => self.palette().c400,
+            };
+            Row::new([
+                Cell::from(title),
+                Cell::from(status.to_string()).style(Style::default().fg(status_color)),
+                Cell::from(self.summary_extractor.extract(&pane.current_event)),
+                Cell::from(self.locale.format_integer(pane.current_event.iteration)),
+            ])
+        });
+
+        let header = Row::new(["Host", "Status", "Value", "Itr"]).style(
+            Style::default()
+                .fg(self.palette().c200)
+                .bg(self.palette().c900)
+                .add_modifier(Modifier::BOLD),
+        );
+
+        Table::new(
+            rows,
+            [
+                Constraint::Percentage(25),
+                Constraint::Length(10),
+                Constraint::Fill(1),
+                Constraint::Length(8),
+            ],
+        )
+        .header(header)
+    }
+
+    /// Render the changes from the output of the first pane to the one of the second, structural
+    /// for the JSON outputs, returning the inner area of the diff.
+    fn draw_ab_diff(&self, f: &mut Frame, area: Rect) -> Rect {
+        let [pane_a, pane_b] = [&self.panes[0], &self.panes[1]];
+        let output_a = self.visible_output(&pane_a.shown_event().output);
+        let output_b = self.visible_output(&pane_b.shown_event().output);
+
+        let json_diff = json_output_diff(&output_a, &output_b, &self.json_ignore)
+            .filter(|_| self.format == OutputFormat::Json);
+        let (text, change_count) = match json_diff {
+            Some(diff) => (json_diff_text(&diff), diff.changes().len()),
+            None => {
+                let mut diff_lines = compared_line_diff(&output_a, &output_b, &self.comparator);
+                let change_count = diff_lines
+                    .iter()
+                    .filter(|line| matches!(line, DiffLine::Added(_) | DiffLine::Removed(_)))
+                    .count();
+                if self.elide_unchanged {
+                    diff_lines = elide_unchanged(diff_lines, self.diff_context);
+                }
+                (
+                    line_diff_text(&diff_lines, self.diff_granularity),
+                    change_count,
+                )
+            }
+        };
+
+        let side_title = |side: &str, pane: &WatcherPane| {
+            format!(
+                "  {side} {} │ Itr: {}  ",
+                pane.title.as_deref().unwrap_or_default(),
+                self.locale.format_integer(pane.shown_event().iteration)
+            )
+        };
+        let mut block = Block::default().title_alignment(Alignment::Right);
+        if !self.no_title {
+            block = block
+                .title(
+                    Title::from(side_title("−", pane_a).fg(tailwind::RED.c300))
+                        .alignment(Alignment::Left),
+                )
+                .title(side_title("+", pane_b).fg(tailwind::GREEN.c300))
+                .title_bottom(
+                    Line::raw(format!(" {change_count} differences "))
+                        .fg(tailwind::SLATE.c400)
+                        .right_aligned(),
+                );
+        }
+        let inner_area = block.inner(area);
+
+        let text = with_highlights(text, &self.highlights);
+        let text = match self.line_numbers {
+            true => with_line_numbers(text),
+            false => text,
+        };
+        let mut para = Paragraph::new(text).block(block);
+        if self.wrap {
+            para = para.wrap(Wrap { trim: false });
+        }
+        let column_offset = match self.wrap {
+            true => 0,
+            false => self.column_offset,
+        };
+        f.render_widget(para.scroll((self.scroll, column_offset)), area);
+        inner_area
+    }
+
+    /// Render the panes, returning their scroll when followed and their inner areas.
+    fn draw_panes(&self, f: &mut Frame, area: Rect) -> (u16, Vec<Rect>) {
+        // One column or row per pane, lines differing across the panes get highlighted
+        let direction = match self.split {
+            SplitDirection::Vertical => Direction::Horizontal,
+            SplitDirection::Horizontal => Direction::Vertical,
+        };
+        let pane_chunks = Layout::default()
+            .direction(direction)
+            .constraints(vec![
+                Constraint::Ratio(1, self.panes.len() as u32);
+                self.panes.len()
+            ])
+            .split(area);
+
+        let outputs = self
+            .panes
+            .iter()
+            .map(|pane| self.visible_output(self.streamed_output(pane)))
+            .collect::<Vec<_>>();
+        let pane_lines = outputs
+            .iter()
+            .map(|output| output.lines().collect())
+            .collect::<Vec<Vec<_>>>();
+
+        let mut scroll = 0;
+        let mut pane_areas = Vec::with_capacity(self.panes.len());
+        for (pane_index, pane) in self.panes.iter().enumerate() {
+            let mut pane_title = format!(
+                "Itr: {} │ ⟳ {}",
+                self.locale.format_integer(pane.shown_event().iteration),
+                humantime::format_duration(pane.shown_event().interval)
+            );
+            if let Some(title) = &pane.title {
+                pane_title = format!("{title} │ {pane_title}");
+            }
+            if pane.unchanged_runs > 0 {
+                write!(
+                    &mut pane_title,
+                    " │ ≡ unchanged for {} ({} runs)",
+                    humantime::format_duration(Duration::from_secs(
+                        pane.changed_at.elapsed().as_secs()
+                    )),
+                    self.locale.format_integer(pane.unchanged_runs),
+                )
+                .unwrap();
+            }
+            // The panes side by side tell apart their outcomes
+            if self.panes.len() > 1 {
+                match (
+                    SummaryStatus::of(pane.shown_event()),
+                    pane.shown_event().exit_code,
+                ) {
+                    (SummaryStatus::Ok, _) => pane_title.push_str(" │ ✔ exit 0"),
+                    (SummaryStatus::Failed, Some(exit_code)) => {
+                        write!(&mut pane_title, " │ ✘ exit {exit_code}").unwrap()
+                    }
+                    _ => {}
+                }
+            }
+            if !self.wrap {
+                write!(&mut pane_title, " │ ⇔ col {}", self.column_offset).unwrap();
+            }
+            if self.follow {
+                pane_title.push_str(" │ ⤓ follow");
+            }
+            if pane.picked.is_some() {
+                pane_title.push_str(" │ ⏪ history");
+            }
+            if let Some(baseline) = &pane.baseline {
+                pane_title.push_str(&format!(" │ ⚓ vs Itr {}", baseline.iteration));
+            }
+            if pane.partial_output.is_some() {
+                pane_title.push_str(" │ ⋯ running");
+            }
+            let is_hexdump =
+                self.is_hexdump_toggled != is_binary(output_bytes(pane, &outputs[pane_index]));
+            if is_hexdump {
+                pane_title.push_str(" │ ⬡ hex");
+            }
+            let pane_title = format!("  {pane_title}  ");
+
+            // Top right inner block with styled title aligned to the right
+            let mut block = Block::default().title_alignment(Alignment::Right);
+            if !self.no_title {
+                let is_flashing = self
+                    .baseline_accepted_at
+                    .is_some_and(|accepted_at| accepted_at.elapsed() < BASELINE_FLASH_DURATION);
+                let title_bg = match is_flashing {
+                    true => tailwind::GREEN.c700,
+                    false => self.palette().c900,
+                };
+                block = block.title(Span::styled(
+                    pane_title,
+                    Style::default()
+                        .fg(self.palette().c200)
+                        .bg(title_bg)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+
+            // Flag iterations which only captured a partial output
+            if pane.shown_event().status != ExecStatus::Completed && !self.no_title {
+                block = block.title(Span::styled(
+                    format!("  {}  ", pane.shown_event().status),
+                    Style::default()
+                        .fg(tailwind::RED.c200)
+                        .bg(tailwind::RED.c900)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            if let Some(truncated_at) = pane.shown_event().truncated_at.filter(|_| !self.no_title) {
+                block = block.title(Span::styled(
+                    format!(
+                        "  ✂ output truncated at {} bytes  ",
+                        self.locale.format_integer(truncated_at)
+                    ),
+                    Style::default()
+                        .fg(tailwind::AMBER.c200)
+                        .bg(tailwind::AMBER.c900)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+
+            if let Some(alert) = pane.alert.as_ref().filter(|_| !self.no_title) {
+                block = block
+                    .title(Span::styled(
+                        format!(
+                            "  🔔 {} │ {}  ",
+                            alert.rule,
+                            self.locale.format_decimal(alert.value, 2)
+                        ),
+                        Style::default()
+                            .fg(tailwind::RED.c200)
+                            .bg(tailwind::RED.c900)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                    .border_style(Style::default().fg(tailwind::RED.c500));
+            }
+
+            // Stats strip of the run durations, to spot the command getting slower
+            let stats = &pane.run_stats;
+            if stats.runs > 0 && !self.no_title {
+                let format_duration = |duration| self.locale.format_duration(duration);
+                block = block.title_bottom(
+                    Line::raw(format!(
+                        " ⏱ last {} │ min {} │ avg {} │ max {} ",
+                        format_duration(stats.last),
+                        format_duration(stats.min),
+                        format_duration(stats.average()),
+                        format_duration(stats.max),
+                    ))
+                    .fg(tailwind::SLATE.c400)
+                    .right_aligned(),
+                );
+            }
+
+            if pane_index + 1 < self.panes.len() {
+                block = block.borders(match self.split {
+                    SplitDirection::Vertical => Borders::RIGHT,
+                    SplitDirection::Horizontal => Borders::BOTTOM,
+                });
+            }
+
+            let output = &outputs[pane_index];
+            let previous_output = pane
+                .compared_output()
+                .map(|previous_output| self.visible_output(previous_output));
+            // Failed setup commands stay in sight, the watch running without what they set up
+            let pane_area = match &pane.setup_failure {
+                Some(setup_failure) => {
+                    let panel_lines = setup_failure.output.lines().count();
+                    let panel_height = panel_lines.clamp(1, SETUP_PANEL_LINES) as u16 + 2;
+                    let [panel_area, pane_area] =
+                        Layout::vertical([Constraint::Length(panel_height), Constraint::Fill(1)])
+                            .areas(pane_chunks[pane_index]);
+                    f.render_widget(setup_failure_panel(setup_failure), panel_area);
+                    pane_area
+                }
+                None => pane_chunks[pane_index],
+            };
+            pane_areas.push(block.inner(pane_area));
+
+            // Columnar outputs in the tabular formats, the others are rendered as text
+            if let Some(mut table) = self.columnar_output(output).filter(|_| !is_hexdump) {
+                if let Some(sort) = self.table_sort {
+                    table.sort(sort);
+                }
+                let previous_table = previous_output
+                    .as_deref()
+                    .and_then(|previous_output| self.columnar_output(previous_output));
+
+                // Rows scrolled under the sticky header
+                let visible_rows = block.inner(pane_area).height.saturating_sub(1) as usize;
+                let pane_scroll = match self.follow {
+                    true => table.rows.len().saturating_sub(visible_rows) as u16,
+                    false => self.scroll,
+                };
+                scroll = scroll.max(pane_scroll);
+
+                let widget = table
+                    .widget(previous_table.as_ref(), self.table_sort, self.palette())
+                    .block(block);
+                let mut state = TableState::default().with_offset(pane_scroll as usize);
+                f.render_stateful_widget(widget, pane_area, &mut state);
+                continue;
+            }
+
+            let text = if is_hexdump {
+                Text::raw(hexdump(output_bytes(pane, output)))
+            } else if let Some(json_text) = self.json_diff_text(pane) {
+                json_text
+            } else if let Some(diff_text) = self.line_diff_text(previous_output.as_deref(), output)
+            {
+                diff_text
+            } else if self.format == OutputFormat::Markdown {
+                markdown_text(output, self.palette())
+            } else if self.search.regex.is_some() {
+                self.search.highlighted_text(output)
+            } else if self.panes.len() > 1 {
+                cross_pane_highlighted_text(pane_index, &pane_lines, &self.comparator)
+            } else {
+                Text::raw(output.as_ref())
+            };
+            let text = with_highlights(text, &self.highlights);
+            let text = match self.line_numbers {
+                true => with_line_numbers(text),
+                false => text,
+            };
+
+            let inner_width = block.inner(pane_area).width;
+            let mut para = Paragraph::new(text).block(block);
+            if self.wrap {
+                para = para.wrap(Wrap { trim: false });
+            }
+
+            // Followed panes are scrolled to the bottom of their own output
+            let pane_scroll = match self.follow {
+                true => para
+                    .line_count(inner_width)
+                    .saturating_sub(pane_area.height as usize) as u16,
+                false => self.scroll,
+            };
+            scroll = scroll.max(pane_scroll);
+
+            let column_offset = match self.wrap {
+                true => 0,
+                false => self.column_offset,
+            };
+            f.render_widget(para.scroll((pane_scroll, column_offset)), pane_area);
+        }
+
+        for (pane, pane_area) in self.panes.iter().zip(&pane_areas) {
+            if let Some(error) = &pane.error {
+                draw_error_screen(f, *pane_area, error);
+            }
+        }
+        (scroll, pane_areas)
+    }
+
+    /// Output of the running iteration streamed so far, else the output of the last iteration.
+    /// Diffs are only made between complete outputs.
+    fn streamed_output<'a>(&self, pane: &'a WatcherPane) -> &'a str {
+        match &pane.partial_output {
+            Some(partial_output)
+                if !self.diff_view
+                    && self.format != OutputFormat::Json
+                    && pane.picked.is_none() =>
+            {
+                partial_output
+            }
+            _ => &pane.shown_event().output,
+        }
+    }
+
+    /// The lines of the `output` kept by the filter, when it is enabled.
+    fn visible_output<'a>(&self, output: &'a str) -> Cow<'a, str> {
+        match &self.filter {
+            Some(filter) if self.filter_enabled => filter_lines(output, filter).into(),
+            _ => output.into(),
+        }
+    }
+
+    /// The `output` parsed in columns, in the tabular formats.
+    fn columnar_output(&self, output: &str) -> Option<ColumnarOutput> {
+        match self.format {
+            OutputFormat::Table => ColumnarOutput::parse(output),
+            OutputFormat::Csv => ColumnarOutput::parse_csv(output),
+            _ => None,
+        }
+    }
+
+    /// Changes from the previous iteration, in diff view.
+    fn line_diff_text<'a>(
+        &self,
+        previous_output: Option<&'a str>,
+        current_output: &'a str,
+    ) -> Option<Text<'a>> {
+        if !self.diff_view {
+            return None;
+        }
+
+        let mut diff_lines = compared_line_diff(previous_output?, current_output, &self.comparator);
+        if self.elide_unchanged {
+            diff_lines = elide_unchanged(diff_lines, self.diff_context);
+        }
+        Some(line_diff_text(&diff_lines, self.diff_granularity))
+    }
+
+    /// Tree-style structural diff against the previous iteration, for JSON outputs.
+    fn json_diff_text(&self, pane: &WatcherPane) -> Option<Text<'static>> {
+        if self.format != OutputFormat::Json {
+            return None;
+        }
+
+        let current_output = &pane.shown_event().output;
+        let previous_output = pane.compared_output().unwrap_or(current_output);
+        json_output_diff(previous_output, current_output, &self.json_ignore)
+            .map(|diff| json_diff_text(&diff))
+    }
+}
+
+/// Bytes of the visible `output` of the `pane`, the ones printed by the command when it is the
+/// whole shown output rather than a streamed or filtered one.
+fn output_bytes<'a>(pane: &'a WatcherPane, output: &'a str) -> &'a [u8] {
+    match ptr::eq(output, pane.shown_event().output.as_str()) {
+        true => pane.shown_bytes(),
+        false => output.as_bytes(),
+    }
+}
+
+/// Text of a pane, highlighting the lines which differ from the same line of any other pane.
+fn cross_pane_highlighted_text<'a>(
+    pane_index: usize,
+    pane_lines: &[Vec<&'a str>],
+    comparator: &dyn Comparator,
+) -> Text<'a> {
+    let highlight_style = Style::default()
+        .fg(tailwind::AMBER.c300)
+        .add_modifier(Modifier::BOLD);
+
+    pane_lines[pane_index]
+        .iter()
+        .enumerate()
+        .map(|(line_index, &line)| {
+            let differs = pane_lines
+                .iter()
+                .enumerate()
+                .any(|(other_index, other_lines)| {
+                    other_index != pane_index
+                        && !other_lines
+                            .get(line_index)
+                            .is_some_and(|other_line| comparator.is_same(other_line, line))
+                });
+
+            match differs {
+                true => Line::styled(line, highlight_style),
+                false => Line::raw(line),
+            }
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Failure of a watch over the `area` of its pane, with the keys to retry or quit.
+fn draw_error_screen(f: &mut Frame, area: Rect, error: &str) {
+    let text = Text::from(vec![
+        Line::raw(error),
+        Line::raw(""),
+        Line::from(vec![
+            "r".bold(),
+            " retry in a fresh shell │ ".into(),
+            "Ctrl+C".bold(),
+            " quit".into(),
+        ])
+        .fg(tailwind::SLATE.c400),
+    ]);
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(tailwind::RED.c500))
+        .title(" ✘ watch failed ".fg(tailwind::RED.c200).bold());
+    let error_screen = Paragraph::new(text).wrap(Wrap { trim: false }).block(block);
+
+    let width = area.width.min(ERROR_SCREEN_WIDTH);
+    let height = error_screen.line_count(width.saturating_sub(2)) as u16;
+    let [error_area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [error_area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(error_area);
+    f.render_widget(Clear, error_area);
+    f.render_widget(error_screen, error_area);
+}
+
+/// Warning panel of the failed setup commands, with the last lines of their output.
+fn setup_failure_panel(setup_failure: &WatcherIterationOutput) -> Paragraph<'_> {
+    let outcome = match setup_failure.exit_code {
+        Some(exit_code) => format!("exit {exit_code}"),
+        None => setup_failure.status.to_string(),
+    };
+    let lines = setup_failure.output.lines().collect::<Vec<_>>();
+    let text = match lines.is_empty() {
+        true => Text::raw("no output").fg(tailwind::SLATE.c400),
+        false => Text::raw(lines[lines.len().saturating_sub(SETUP_PANEL_LINES)..].join("\n")),
+    };
+    Paragraph::new(text).block(
+        Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(tailwind::AMBER.c500))
+            .title(
+                format!(" ⚠ setup failed │ {outcome} ")
+                    .fg(tailwind::AMBER.c300)
+                    .bold(),
+            ),
+    )
+}
+
+/// Ring the terminal bell.
+fn beep() -> io::Result<()> {
+    use std::io::Write as _;
+
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
 }
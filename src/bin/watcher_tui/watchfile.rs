@@ -0,0 +1,89 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+use log::debug;
+use serde::{de::Error, Deserialize, Deserializer};
+use watch_rs::utils::{parse_duration, OpenResult};
+
+/// A shareable description of a watch, written in TOML.
+///
+/// ```toml
+/// command = "kubectl get pods"
+/// setup = "export KUBECONFIG=~/.kube/staging"
+/// interval = "2s"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchFile {
+    /// Main command to execute and watch on.
+    pub command: String,
+    /// Setup commands executed once in the shell, before the first iteration.
+    pub setup: Option<String>,
+    /// Teardown commands executed once in the shell, after the last iteration.
+    pub teardown: Option<String>,
+    /// Call interval between two command invocations.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub interval: Option<Duration>,
+    /// Individual command run timeout.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Total duration for the watcher process.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub watch_duration: Option<Duration>,
+}
+
+/// Durations are either a number of seconds, or a human-friendly string like `"500ms"`.
+pub fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDuration {
+        Seconds(f64),
+        Human(String),
+    }
+
+    let duration = match RawDuration::deserialize(deserializer)? {
+        RawDuration::Seconds(seconds) => parse_duration(&seconds.to_string()),
+        RawDuration::Human(value) => parse_duration(&value),
+    };
+    duration.map(Some).map_err(D::Error::custom)
+}
+
+impl WatchFile {
+    /// Whether the watchfile source has to be fetched over HTTP(S).
+    pub fn is_remote(source: &str) -> bool {
+        source.starts_with("http://") || source.starts_with("https://")
+    }
+
+    /// Read the raw watchfile contents from a local path or an HTTP(S) URL.
+    pub fn fetch(source: &str) -> OpenResult<String> {
+        if Self::is_remote(source) {
+            debug!("Fetching remote watchfile : {source}");
+            return Ok(ureq::get(source).call()?.into_string()?);
+        }
+
+        Ok(std::fs::read_to_string(source)?)
+    }
+
+    pub fn parse(contents: &str) -> OpenResult<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// Show the fetched watchfile to the user and ask whether it should be run.
+pub fn confirm_watchfile(source: &str, contents: &str) -> OpenResult<bool> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "==> Watchfile fetched from {source} <==")?;
+    writeln!(stdout, "{}", contents.trim_end())?;
+    writeln!(stdout)?;
+    write!(stdout, "Run this watchfile? [y/N] ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use log::debug;
+use regex::Regex;
+use serde::{de::Error, Deserialize, Deserializer};
+use watch_rs::{
+    models::{comparator::CompareMode, json_diff::JsonPath},
+    utils::OpenResult,
+};
+
+use crate::{
+    tui::{
+        format::{HourCycle, OutputFormat, TimeFormat, Timezone},
+        highlight::HighlightRule,
+        watcher::Theme,
+    },
+    watchfile::deserialize_duration,
+};
+
+/// Name of the workspace config file, looked up from the working directory to the root.
+pub const WATCHRC_FILE_NAME: &str = ".watchrc.toml";
+
+/// Path of the user config within the config directory, holding the defaults of every watch.
+const USER_CONFIG_PATH: &str = "watch-rs/config.toml";
+
+/// Watch presets of a project, carried alongside its code in a `.watchrc.toml`, or of the user
+/// in `~/.config/watch-rs/config.toml` with the same fields. The presets of the project take
+/// precedence over the ones of the user, and the command line flags over both.
+///
+/// ```toml
+/// command = "cargo test"
+/// interval = "5s"
+/// compare_mode = "normalized"
+/// theme = "sky"
+///
+/// [keybindings]
+/// D = "d"
+///
+/// [env]
+/// RUST_LOG = "warn"
+///
+/// [profiles.pods]
+/// command = "kubectl get pods -o json"
+/// format = "json"
+/// json_ignore = [".metadata.resourceVersion"]
+/// highlight = ["Pending=yellow", "CrashLoopBackOff|Error=red,bold"]
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchRc {
+    /// Main command to execute and watch on.
+    pub command: Option<String>,
+    /// Setup commands executed once in the shell, before the first iteration.
+    pub setup: Option<String>,
+    /// Environment variables exported in the watcher shell.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub interval: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub interval_on_error: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub watch_duration: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub compare_mode: Option<CompareMode>,
+    pub format: Option<OutputFormat>,
+    #[serde(default, deserialize_with = "deserialize_parsed_list")]
+    pub json_ignore: Vec<JsonPath>,
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub filter: Option<Regex>,
+    pub theme: Option<Theme>,
+    #[serde(default, deserialize_with = "deserialize_parsed_list")]
+    pub highlight: Vec<HighlightRule>,
+    pub hour_cycle: Option<HourCycle>,
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub time_format: Option<TimeFormat>,
+    #[serde(default, deserialize_with = "deserialize_parsed")]
+    pub timezone: Option<Timezone>,
+    /// Bash compatible shell the local commands run in.
+    pub shell: Option<PathBuf>,
+    /// Keys remapped onto the key bindings of the TUI, like `D = "d"` to toggle the diff view with `D` as well.
+    #[serde(default, deserialize_with = "deserialize_keybindings")]
+    pub keybindings: BTreeMap<char, char>,
+    /// Named presets selected with `--profile`, overriding the top level presets.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, WatchRc>,
+}
+
+/// Values parsed from their string form, like they are on the command line.
+fn deserialize_parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(D::Error::custom)
+}
+
+fn deserialize_parsed_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| value.parse().map_err(D::Error::custom))
+        .collect()
+}
+
+fn deserialize_keybindings<'de, D>(deserializer: D) -> Result<BTreeMap<char, char>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let as_char = |key: &str| {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(D::Error::custom(format!(
+                "invalid key `{key}`, expected a single character"
+            ))),
+        }
+    };
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .iter()
+        .map(|(key, bound_key)| Ok((as_char(key)?, as_char(bound_key)?)))
+        .collect()
+}
+
+impl WatchRc {
+    /// The user config, within `$XDG_CONFIG_HOME` or `~/.config`, when it exists.
+    pub fn user_config() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join(USER_CONFIG_PATH)).filter(|path| path.is_file())
+    }
+
+    /// Closest `.watchrc.toml` within `dir` or any of its ancestors.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(WATCHRC_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> OpenResult<Self> {
+        debug!("Loading presets : {path:?}");
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|err| format!("invalid {} : {err}", path.display()).into())
+    }
+
+    /// Presets of the `profile` laid over the top level presets.
+    pub fn with_profile(mut self, profile: &str) -> OpenResult<Self> {
+        let Some(overrides) = self.profiles.remove(profile) else {
+            let known_profiles = self.profiles.keys().cloned().collect::<Vec<_>>();
+            return Err(format!(
+                "unknown profile `{profile}`, the known profiles are: {}",
+                known_profiles.join(", ")
+            )
+            .into());
+        };
+
+        self.profiles.clear();
+        Ok(overrides.laid_over(self))
+    }
+
+    /// These presets laid over the `base` presets, like the ones of a project over the ones of the user.
+    /// The profiles of the same name get replaced.
+    pub fn laid_over(self, mut base: Self) -> Self {
+        base.env.extend(self.env);
+        base.keybindings.extend(self.keybindings);
+        base.profiles.extend(self.profiles);
+        if !self.json_ignore.is_empty() {
+            base.json_ignore = self.json_ignore;
+        }
+        if !self.highlight.is_empty() {
+            base.highlight = self.highlight;
+        }
+        Self {
+            command: self.command.or(base.command),
+            setup: self.setup.or(base.setup),
+            env: base.env,
+            interval: self.interval.or(base.interval),
+            interval_on_error: self.interval_on_error.or(base.interval_on_error),
+            timeout: self.timeout.or(base.timeout),
+            watch_duration: self.watch_duration.or(base.watch_duration),
+            compare_mode: self.compare_mode.or(base.compare_mode),
+            format: self.format.or(base.format),
+            json_ignore: base.json_ignore,
+            filter: self.filter.or(base.filter),
+            theme: self.theme.or(base.theme),
+            highlight: base.highlight,
+            hour_cycle: self.hour_cycle.or(base.hour_cycle),
+            time_format: self.time_format.or(base.time_format),
+            timezone: self.timezone.or(base.timezone),
+            shell: self.shell.or(base.shell),
+            keybindings: base.keybindings,
+            profiles: base.profiles,
+        }
+    }
+}
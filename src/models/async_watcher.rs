@@ -0,0 +1,367 @@
+use std::{error::Error, path::Path, process::Stdio, sync::atomic::Ordering, time::Duration};
+use log::debug;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc::UnboundedReceiver,
+    time::{self, Instant},
+};
+use crate::utils::OpenResult;
+use super::{
+    encoding::OutputEncoding,
+    session::{EndEvent, EndReason},
+    shell::{self, CancelHandle, CommandCapture},
+    watcher::{shell_quote, ExecOutput, ExecStatus, DEFAULT_SHELL},
+    watcher_loop::{
+        poll_triggers, triggers_poll_interval, IterationResult, LoopEvent, OutputHistory, WatcherSchedule,
+        ADJUSTMENT_TRIGGER,
+    },
+};
+
+
+/// Size of the reads of the shell output.
+const READ_BUFFER_SIZE: usize = 8192;
+
+
+/// Async counterpart of the `Watcher`, running its commands in a Bash shell spawned on tokio.
+/// The shell output is awaited rather than polled by a blocking reader, the command timeout
+/// being a deadline on the reads.
+///
+/// An additive API for the integrations running on tokio: the binaries watch on the blocking
+/// `Watcher` and `WatcherLoop`, along with the SSH shells, the `MockShell` and the `WatchSession`.
+pub struct AsyncWatcher {
+    shell: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    exec_count: usize,
+    command_timeout: Duration,
+    encoding: OutputEncoding,
+    max_output_bytes: Option<usize>,
+    cancel_handle: CancelHandle,
+}
+
+impl AsyncWatcher {
+    pub async fn new(command_timeout: u64, cwd: Option<&Path>) -> OpenResult<Self> {
+        Self::in_shell(Path::new(DEFAULT_SHELL), command_timeout, cwd).await
+    }
+
+    /// Watcher running its commands in the `shell` program, which has to be Bash compatible.
+    pub async fn in_shell(shell: &Path, command_timeout: u64, cwd: Option<&Path>) -> OpenResult<Self> {
+        let mut shell = Command::new(shell)
+            .env("LC_ALL", "C")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = shell.stdin.take().unwrap();
+        let stdout = shell.stdout.take().unwrap();
+        let cancel_handle = CancelHandle::for_shell(shell.id().ok_or("the shell exited right away")?);
+
+        let mut watcher = Self {
+            shell, stdin, stdout, exec_count: 0, command_timeout: Duration::from_millis(command_timeout),
+            encoding: OutputEncoding::default(), max_output_bytes: None, cancel_handle,
+        };
+        // The shell stderr is merged from within, like the `Watcher` one. Job control and the
+        // ignored interrupts make the commands cancellable, the shell outliving them
+        watcher.exec_cmd_and_fetch_output("
+            exec 2>&1;
+            set -m;
+            trap : INT;
+            shopt -s expand_aliases;
+            source ~/.bashrc;
+        ").await?;
+
+        if let Some(cwd) = cwd {
+            let cwd_output = watcher.exec_cmd_and_fetch_output(
+                &format!("cd -- {}", shell_quote(&cwd.to_string_lossy()))
+            ).await?;
+            if !cwd_output.is_success() {
+                let reason = cwd_output.output.trim();
+                return Err(format!("cannot change directory to {} : {reason}", cwd.display()).into());
+            }
+        }
+
+        Ok(watcher)
+    }
+
+    /// Decode the outputs of the commands from the `encoding`, rather than UTF-8.
+    pub fn with_encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Only keep the first `max_output_bytes` of the outputs, the rest being read and discarded.
+    pub fn with_max_output_bytes(mut self, max_output_bytes: Option<usize>) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+
+    pub async fn exec_cmd_and_fetch_output(&mut self, command: &str) -> OpenResult<ExecOutput> {
+        self.exec_cmd_streaming(command, |_| {}).await
+    }
+
+    /// Execute the `command`, handing each new complete line of output to `on_output` once
+    /// while it runs. Dropping the future leaves the command running in the shell.
+    pub async fn exec_cmd_streaming(&mut self, command: &str, on_output: impl FnMut(&str)) -> OpenResult<ExecOutput> {
+        self.cancel_handle.take_cancelled();
+        let mut exec_output = self.exec_uncancelled(command, on_output).await?;
+        // A timed out command is interrupted, for the shell to take the next ones
+        if let ExecStatus::TimedOut(_) = exec_output.status {
+            self.cancel_handle.interrupt_timed_out()?;
+        }
+        if self.cancel_handle.take_cancelled() {
+            exec_output.status = ExecStatus::Cancelled;
+            exec_output.exit_code = None;
+        }
+        Ok(exec_output)
+    }
+
+    async fn exec_uncancelled(&mut self, command: &str, mut on_output: impl FnMut(&str)) -> OpenResult<ExecOutput> {
+        self.exec_count += 1;
+        let mut capture = CommandCapture::new(self.exec_count, self.encoding, self.max_output_bytes);
+        self.stdin.write_all(capture.script(command).as_bytes()).await?;
+        self.stdin.flush().await?;
+
+        let deadline = Instant::now() + self.command_timeout;
+        let mut buffer = [0; READ_BUFFER_SIZE];
+        loop {
+            let read_len = match time::timeout_at(deadline, self.stdout.read(&mut buffer)).await {
+                Ok(read_len) => read_len?,
+                Err(_) => return Ok(capture.finish(ExecStatus::TimedOut(self.command_timeout), None)),
+            };
+            if read_len == 0 {
+                return Err("the shell exited while running the command".into());
+            }
+            // Every byte of the output is held as its own char, decoded once the scaffolding got stripped
+            capture.push(&buffer[..read_len].iter().map(|&byte| byte as char).collect::<String>());
+
+            if let Some(exit_code) = capture.exit_code() {
+                return Ok(capture.finish(ExecStatus::Completed, exit_code));
+            }
+            if let Some(new_lines) = capture.new_lines() {
+                on_output(&new_lines);
+            }
+        }
+    }
+
+    /// Whether the shell still runs, and can take more commands.
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.shell.try_wait(), Ok(None))
+    }
+
+    /// Export the `key` environment variable in the shell, visible to all the following commands.
+    pub async fn export_env(&mut self, key: &str, value: &str) -> OpenResult {
+        self.exec_cmd_and_fetch_output(&format!("export {}={}", key, shell_quote(value))).await?;
+        Ok(())
+    }
+
+    /// Handle cancelling the running command from another task or thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    /// Kill the shell along with its commands, running in process groups of their own.
+    pub async fn kill(&mut self) -> OpenResult {
+        if let Some(shell_pid) = self.shell.id() {
+            shell::kill_shell(shell_pid);
+        }
+        Ok(self.shell.kill().await?)
+    }
+}
+
+
+/// Messages controlling an `AsyncWatcherLoop` while it runs, handled as they arrive, even while
+/// the command runs or the loop sleeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// Suspend the watch, the next iteration waits for it to be resumed.
+    Pause,
+    Resume,
+    /// Run the next iteration right away, or right after the running one.
+    Refresh,
+    /// Interrupt the running command, which completes as `ExecStatus::Cancelled`.
+    Cancel,
+    /// End the watch, killing the shell when a command is running.
+    Stop,
+}
+
+/// Async counterpart of the `WatcherLoop`, running a command on its `WatcherSchedule` in an
+/// `AsyncWatcher`. The interval ticks, the command timeouts and the control messages are all
+/// awaited on the same task, without any thread of its own.
+///
+/// The control messages replace the `paused` flag of the schedule. Its `triggers`, `refresh` and
+/// `skip_next` flags run or skip the iterations like in the `WatcherLoop`, polled on the task.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use tokio::sync::mpsc;
+/// # use watch_rs::models::{
+/// #     async_watcher::{AsyncWatcher, AsyncWatcherLoop, ControlMessage},
+/// #     watcher_loop::{LoopEvent, WatcherSchedule},
+/// # };
+/// # async fn watch() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut watcher = AsyncWatcher::new(30_000, None).await?;
+/// let (control, control_receiver) = mpsc::unbounded_channel();
+/// tokio::spawn(async move {
+///     tokio::time::sleep(Duration::from_secs(60)).await;
+///     control.send(ControlMessage::Stop)
+/// });
+/// AsyncWatcherLoop::new(WatcherSchedule::every(Duration::from_secs(5)))
+///     .run(&mut watcher, "date", control_receiver, |event| {
+///         if let LoopEvent::Ran { result, .. } = event {
+///             print!("{}", result.output);
+///         }
+///         true
+///     })
+///     .await
+///     .map_err(|(_, err)| err)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncWatcherLoop {
+    schedule: WatcherSchedule,
+}
+
+impl AsyncWatcherLoop {
+    pub fn new(schedule: WatcherSchedule) -> Self {
+        Self { schedule }
+    }
+
+    /// Run the `command` in the `watcher` until the watch ends, `control` steering the loop and
+    /// `on_event` receiving its events, ending the watch when it returns false. An error running
+    /// the command ends the watch, returned along with the number of iterations which ran.
+    pub async fn run(
+        mut self,
+        watcher: &mut AsyncWatcher,
+        command: &str,
+        mut control: UnboundedReceiver<ControlMessage>,
+        mut on_event: impl FnMut(LoopEvent<ExecOutput>) -> bool,
+    ) -> Result<EndEvent, (usize, Box<dyn Error>)> {
+        let start_checkpoint = std::time::Instant::now();
+        let mut triggers = self.schedule.all_triggers();
+        let mut iteration = 0;
+        let mut outputs = OutputHistory::default();
+        let mut interval = self.schedule.interval;
+        let mut is_paused = false;
+        let mut is_control_open = true;
+        let cancel_handle = watcher.cancel_handle();
+
+        let reason = 'watch: loop {
+            // Once nobody can resume the watch anymore, it ends
+            while is_paused {
+                match control.recv().await {
+                    Some(ControlMessage::Resume | ControlMessage::Refresh) => is_paused = false,
+                    Some(ControlMessage::Pause | ControlMessage::Cancel) => {}
+                    Some(ControlMessage::Stop) | None => break 'watch EndReason::Stopped,
+                }
+            }
+
+            iteration += 1;
+            let mut is_refreshed = false;
+            let exec_result = {
+                let exec = watcher.exec_cmd_and_fetch_output(command);
+                tokio::pin!(exec);
+                loop {
+                    tokio::select! {
+                        result = &mut exec => break Some(result.map_err(|err| (iteration - 1, err))?),
+                        message = control.recv(), if is_control_open => match message {
+                            Some(ControlMessage::Pause) => is_paused = true,
+                            Some(ControlMessage::Resume) => is_paused = false,
+                            Some(ControlMessage::Refresh) => is_refreshed = true,
+                            Some(ControlMessage::Cancel) => {
+                                if let Err(err) = cancel_handle.cancel() {
+                                    debug!("Could not cancel iteration {iteration}: {err}");
+                                }
+                            }
+                            Some(ControlMessage::Stop) => break None,
+                            None => is_control_open = false,
+                        },
+                    }
+                }
+            };
+            let Some(result) = exec_result else {
+                debug!("Stopped while running iteration {iteration}, killing the shell");
+                let _ = watcher.kill().await;
+                iteration -= 1;
+                break EndReason::Stopped;
+            };
+
+            let next_interval = self.schedule.next_interval(interval, result.is_success());
+            if next_interval != interval {
+                debug!("Changing the interval from {interval:?} to {next_interval:?}");
+                interval = next_interval;
+            }
+
+            let changed_from = outputs.record(self.schedule.comparator.as_ref(), result.output());
+            if !on_event(LoopEvent::Ran { iteration, result, interval, changed_from }) {
+                break EndReason::Stopped;
+            }
+            if let Some(reason) = self.schedule.end_reason(iteration, &outputs, start_checkpoint) {
+                break reason;
+            }
+            if is_refreshed {
+                debug!("Iteration {} triggered by a refresh", iteration + 1);
+                continue;
+            }
+
+            // A skipped run waits for the one scheduled after it instead
+            loop {
+                // Picks up the interval adjusted while watching, like the `WatcherLoop`
+                let live_interval = *self.schedule.live_interval.lock().unwrap();
+                if live_interval != self.schedule.interval {
+                    debug!("Adjusting the interval from {interval:?} to {live_interval:?}");
+                    self.schedule.interval = live_interval;
+                    interval = live_interval;
+                }
+
+                let Some(wait) = self.schedule.wait_before_next_run(start_checkpoint, iteration, interval) else {
+                    debug!("No run left on the schedule, ending the watch.");
+                    break 'watch EndReason::ScheduleExhausted;
+                };
+                let waiting_event = match self.schedule.is_interval_triggered() {
+                    true => LoopEvent::Sleeping(std::time::Instant::now() + wait),
+                    false => LoopEvent::WaitingForTrigger,
+                };
+                if !on_event(waiting_event) {
+                    break 'watch EndReason::Stopped;
+                }
+                triggers.iter_mut().for_each(|trigger| trigger.arm(wait));
+
+                // A pause taking effect during the wait holds the next iteration back once it fired
+                let fired = loop {
+                    if let Some(fired) = poll_triggers(&mut triggers) {
+                        break fired;
+                    }
+                    tokio::select! {
+                        _ = time::sleep(triggers_poll_interval(&triggers)) => {}
+                        message = control.recv(), if is_control_open => match message {
+                            Some(ControlMessage::Pause) => is_paused = true,
+                            Some(ControlMessage::Resume) => is_paused = false,
+                            Some(ControlMessage::Refresh) => {
+                                is_paused = false;
+                                break "refresh";
+                            }
+                            Some(ControlMessage::Cancel) => {}
+                            Some(ControlMessage::Stop) => break 'watch EndReason::Stopped,
+                            None => is_control_open = false,
+                        },
+                    }
+                };
+                match fired {
+                    ADJUSTMENT_TRIGGER => {}
+                    trigger if trigger != "refresh" && self.schedule.skip_next.swap(false, Ordering::AcqRel) => {
+                        debug!("Skipped the run of iteration {}", iteration + 1);
+                    }
+                    trigger => {
+                        debug!("Iteration {} triggered by the {trigger}", iteration + 1);
+                        break;
+                    }
+                }
+            }
+        };
+
+        Ok(EndEvent { iterations: iteration, reason })
+    }
+}
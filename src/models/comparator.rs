@@ -0,0 +1,210 @@
+use std::{fmt, str::FromStr, sync::{Arc, RwLock}};
+use super::json_diff::{parse_without, JsonPath};
+
+
+/// Decides whether two outputs of the watched command are to be considered the same.
+/// Drives the change detection, exit-on-change and diff highlighting.
+pub trait Comparator: Send + Sync {
+    fn is_same(&self, previous: &str, current: &str) -> bool;
+}
+
+
+/// Comparator swapped while the watchers use it, like from the settings of a TUI. Clones share
+/// the comparator.
+#[derive(Clone)]
+pub struct SharedComparator(Arc<RwLock<Arc<dyn Comparator>>>);
+
+impl SharedComparator {
+    pub fn new(comparator: Arc<dyn Comparator>) -> Self {
+        Self(Arc::new(RwLock::new(comparator)))
+    }
+
+    /// Compare the next outputs with the `comparator`, for all the clones.
+    pub fn set(&self, comparator: Arc<dyn Comparator>) {
+        *self.0.write().unwrap() = comparator;
+    }
+}
+
+impl Comparator for SharedComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        self.0.read().unwrap().is_same(previous, current)
+    }
+}
+
+
+/// Byte-for-byte equality.
+pub struct ExactComparator;
+
+impl Comparator for ExactComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        previous == current
+    }
+}
+
+
+/// Equality ignoring whitespace differences and blank lines.
+pub struct NormalizedComparator;
+
+impl NormalizedComparator {
+    fn normalize(output: &str) -> impl Iterator<Item = String> + '_ {
+        output.lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+    }
+}
+
+impl Comparator for NormalizedComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        Self::normalize(previous).eq(Self::normalize(current))
+    }
+}
+
+
+/// Equality where numbers may differ by a relative `tolerance` (e.g. `0.05` for 5%).
+pub struct NumericToleranceComparator {
+    pub tolerance: f64,
+}
+
+impl NumericToleranceComparator {
+    fn is_same_token(&self, previous: &str, current: &str) -> bool {
+        match (previous.parse::<f64>(), current.parse::<f64>()) {
+            (Ok(previous), Ok(current)) => {
+                (previous - current).abs() <= self.tolerance * previous.abs().max(current.abs())
+            }
+            _ => previous == current,
+        }
+    }
+}
+
+impl Comparator for NumericToleranceComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        let previous_tokens = previous.split_whitespace().collect::<Vec<_>>();
+        let current_tokens = current.split_whitespace().collect::<Vec<_>>();
+
+        previous_tokens.len() == current_tokens.len()
+            && previous_tokens.iter().zip(&current_tokens)
+                .all(|(previous, current)| self.is_same_token(previous, current))
+    }
+}
+
+
+/// Structural equality of JSON documents, ignoring formatting, key order and the `ignored_paths`.
+/// Falls back on exact equality for outputs which are not valid JSON.
+pub struct JsonComparator {
+    pub ignored_paths: Vec<JsonPath>,
+}
+
+impl Comparator for JsonComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        match (parse_without(previous, &self.ignored_paths), parse_without(current, &self.ignored_paths)) {
+            (Some(previous), Some(current)) => previous == current,
+            _ => previous == current,
+        }
+    }
+}
+
+
+/// Perceptual comparison of outputs drawn as text (ASCII art, sparklines, bar graphs).
+/// Outputs get reduced to an average hash of their character density on an 8x8 grid,
+/// and are the same when the hashes differ by at most `max_distance` bits.
+pub struct ImageHashComparator {
+    pub max_distance: u32,
+}
+
+impl ImageHashComparator {
+    const GRID_SIZE: usize = 8;
+
+    fn average_hash(output: &str) -> u64 {
+        let lines = output.lines().collect::<Vec<_>>();
+        let height = lines.len().max(1);
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0).max(1);
+
+        // Density of non-blank characters for each cell of the grid
+        let mut density = [0f64; Self::GRID_SIZE * Self::GRID_SIZE];
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if !c.is_whitespace() {
+                    let cell = (y * Self::GRID_SIZE / height) * Self::GRID_SIZE + x * Self::GRID_SIZE / width;
+                    density[cell] += 1.0;
+                }
+            }
+        }
+
+        let average = density.iter().sum::<f64>() / density.len() as f64;
+        density.iter().enumerate()
+            .filter(|(_, &cell_density)| cell_density > average)
+            .fold(0, |hash, (cell, _)| hash | 1 << cell)
+    }
+}
+
+impl Comparator for ImageHashComparator {
+    fn is_same(&self, previous: &str, current: &str) -> bool {
+        (Self::average_hash(previous) ^ Self::average_hash(current)).count_ones() <= self.max_distance
+    }
+}
+
+
+/// Comparator selection, parsed from `exact`, `normalized`, `numeric[:TOLERANCE]`,
+/// `json` or `image-hash[:MAX_DISTANCE]`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum CompareMode {
+    #[default]
+    Exact,
+    Normalized,
+    NumericTolerance(f64),
+    Json,
+    ImageHash(u32),
+}
+
+impl CompareMode {
+    /// Build the comparator, with the `json_ignore` paths left out of JSON comparisons.
+    pub fn comparator(&self, json_ignore: &[JsonPath]) -> Arc<dyn Comparator> {
+        match *self {
+            CompareMode::Exact => Arc::new(ExactComparator),
+            CompareMode::Normalized => Arc::new(NormalizedComparator),
+            CompareMode::NumericTolerance(tolerance) => Arc::new(NumericToleranceComparator { tolerance }),
+            CompareMode::Json => Arc::new(JsonComparator { ignored_paths: json_ignore.to_vec() }),
+            CompareMode::ImageHash(max_distance) => Arc::new(ImageHashComparator { max_distance }),
+        }
+    }
+}
+
+impl FromStr for CompareMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (mode, parameter) = match value.split_once(':') {
+            Some((mode, parameter)) => (mode, Some(parameter)),
+            None => (value, None),
+        };
+
+        match (mode, parameter) {
+            ("exact", None) => Ok(CompareMode::Exact),
+            ("normalized", None) => Ok(CompareMode::Normalized),
+            ("numeric", None) => Ok(CompareMode::NumericTolerance(0.01)),
+            ("numeric", Some(tolerance)) => tolerance.parse()
+                .map(CompareMode::NumericTolerance)
+                .map_err(|err| format!("invalid numeric tolerance `{tolerance}`: {err}")),
+            ("json", None) => Ok(CompareMode::Json),
+            ("image-hash", None) => Ok(CompareMode::ImageHash(4)),
+            ("image-hash", Some(max_distance)) => max_distance.parse()
+                .map(CompareMode::ImageHash)
+                .map_err(|err| format!("invalid image hash distance `{max_distance}`: {err}")),
+            _ => Err(format!(
+                "unknown compare mode `{value}`, expected `exact`, `normalized`, `numeric[:TOLERANCE]`, `json` or `image-hash[:MAX_DISTANCE]`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CompareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareMode::Exact => write!(f, "exact"),
+            CompareMode::Normalized => write!(f, "normalized"),
+            CompareMode::NumericTolerance(tolerance) => write!(f, "numeric:{tolerance}"),
+            CompareMode::Json => write!(f, "json"),
+            CompareMode::ImageHash(max_distance) => write!(f, "image-hash:{max_distance}"),
+        }
+    }
+}
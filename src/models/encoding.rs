@@ -0,0 +1,65 @@
+use std::{fmt, str::FromStr};
+
+
+/// Characters of the `0x80..=0x9F` bytes in Windows-1252, the other bytes map to the same code points as Latin-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{FFFD}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{FFFD}', 'Ž', '\u{FFFD}',
+    '\u{FFFD}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{FFFD}', 'ž', 'Ÿ',
+];
+
+
+/// Character encoding of the output of the watched command, decoded to UTF-8 for display.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OutputEncoding {
+    /// Invalid sequences are replaced by `U+FFFD`, so that arbitrary bytes stay renderable.
+    #[default]
+    Utf8,
+    Latin1,
+    Windows1252,
+    /// Multibyte, decoded by `encoding_rs`. A character never spans a line break, so the lines
+    /// streamed while the command runs decode on their own.
+    ShiftJis,
+}
+
+impl OutputEncoding {
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            OutputEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            OutputEncoding::Latin1 => bytes.iter().map(|&byte| byte as char).collect(),
+            OutputEncoding::Windows1252 => bytes.iter()
+                .map(|&byte| match byte {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                    _ => byte as char,
+                })
+                .collect(),
+            OutputEncoding::ShiftJis => encoding_rs::SHIFT_JIS.decode_without_bom_handling(bytes).0.into_owned(),
+        }
+    }
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(OutputEncoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(OutputEncoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(OutputEncoding::Windows1252),
+            "shift-jis" | "sjis" | "cp932" => Ok(OutputEncoding::ShiftJis),
+            _ => Err(format!(
+                "unsupported encoding `{value}`, expected `utf-8`, `latin-1`, `windows-1252` or `shift-jis`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputEncoding::Utf8 => write!(f, "utf-8"),
+            OutputEncoding::Latin1 => write!(f, "latin-1"),
+            OutputEncoding::Windows1252 => write!(f, "windows-1252"),
+            OutputEncoding::ShiftJis => write!(f, "shift-jis"),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::{fmt, str::FromStr};
+use serde::Serialize;
+use serde_json::Value;
+
+
+/// Structural diff of two JSON documents, mirroring the shape of the documents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonDiffNode {
+    Unchanged(Value),
+    Added(Value),
+    Removed(Value),
+    Changed { old: Value, new: Value },
+    /// Object present on both sides, with the diff of each key in sorted order.
+    Object(Vec<(String, JsonDiffNode)>),
+    /// Array present on both sides, with the diff of each element by index.
+    Array(Vec<JsonDiffNode>),
+}
+
+/// A single change of a structural diff, addressed by its jq-like path (`.items[2].name`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JsonChange {
+    Added { path: String, value: Value },
+    Removed { path: String, value: Value },
+    Changed { path: String, old: Value, new: Value },
+}
+
+impl JsonChange {
+    pub fn path(&self) -> &str {
+        match self {
+            JsonChange::Added { path, .. }
+            | JsonChange::Removed { path, .. }
+            | JsonChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+
+pub fn diff(previous: &Value, current: &Value) -> JsonDiffNode {
+    match (previous, current) {
+        (Value::Object(previous_map), Value::Object(current_map)) => {
+            let mut keys = previous_map.keys().chain(current_map.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            JsonDiffNode::Object(keys.into_iter()
+                .map(|key| {
+                    let node = match (previous_map.get(key), current_map.get(key)) {
+                        (Some(previous), Some(current)) => diff(previous, current),
+                        (None, Some(current)) => JsonDiffNode::Added(current.clone()),
+                        (Some(previous), None) => JsonDiffNode::Removed(previous.clone()),
+                        (None, None) => unreachable!("key comes from one of the objects"),
+                    };
+                    (key.clone(), node)
+                })
+                .collect())
+        }
+        (Value::Array(previous_items), Value::Array(current_items)) => {
+            let length = previous_items.len().max(current_items.len());
+
+            JsonDiffNode::Array((0..length)
+                .map(|index| match (previous_items.get(index), current_items.get(index)) {
+                    (Some(previous), Some(current)) => diff(previous, current),
+                    (None, Some(current)) => JsonDiffNode::Added(current.clone()),
+                    (Some(previous), None) => JsonDiffNode::Removed(previous.clone()),
+                    (None, None) => unreachable!("index is within one of the arrays"),
+                })
+                .collect())
+        }
+        _ if previous == current => JsonDiffNode::Unchanged(current.clone()),
+        _ => JsonDiffNode::Changed { old: previous.clone(), new: current.clone() },
+    }
+}
+
+impl JsonDiffNode {
+    pub fn has_changes(&self) -> bool {
+        match self {
+            JsonDiffNode::Unchanged(_) => false,
+            JsonDiffNode::Added(_) | JsonDiffNode::Removed(_) | JsonDiffNode::Changed { .. } => true,
+            JsonDiffNode::Object(entries) => entries.iter().any(|(_, node)| node.has_changes()),
+            JsonDiffNode::Array(items) => items.iter().any(JsonDiffNode::has_changes),
+        }
+    }
+
+    /// Flatten the diff into the list of its changes.
+    pub fn changes(&self) -> Vec<JsonChange> {
+        let mut changes = Vec::new();
+        self.collect_changes(String::new(), &mut changes);
+        changes
+    }
+
+    fn collect_changes(&self, path: String, changes: &mut Vec<JsonChange>) {
+        let root_path = || if path.is_empty() { ".".to_string() } else { path.clone() };
+
+        match self {
+            JsonDiffNode::Unchanged(_) => {}
+            JsonDiffNode::Added(value) => {
+                changes.push(JsonChange::Added { path: root_path(), value: value.clone() })
+            }
+            JsonDiffNode::Removed(value) => {
+                changes.push(JsonChange::Removed { path: root_path(), value: value.clone() })
+            }
+            JsonDiffNode::Changed { old, new } => changes.push(JsonChange::Changed {
+                path: root_path(), old: old.clone(), new: new.clone(),
+            }),
+            JsonDiffNode::Object(entries) => entries.iter()
+                .for_each(|(key, node)| node.collect_changes(format!("{}.{}", path, key), changes)),
+            JsonDiffNode::Array(items) => items.iter().enumerate()
+                .for_each(|(index, node)| node.collect_changes(format!("{}[{}]", path, index), changes)),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+    /// `[]`, every element of an array.
+    AllItems,
+}
+
+/// A jq-like path to nodes of a JSON document, like `.metadata.resourceVersion` or `.items[].status`.
+/// The path `.` addresses the whole document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<JsonPathSegment>,
+}
+
+impl JsonPath {
+    /// Subtree of `value` addressed by the path, `null` where it is absent.
+    /// Through `[]`, the subtrees of every element are gathered in an array.
+    pub fn select(&self, value: &Value) -> Value {
+        Self::select_segments(&self.segments, value)
+    }
+
+    fn select_segments(segments: &[JsonPathSegment], value: &Value) -> Value {
+        let Some((segment, rest)) = segments.split_first() else { return value.clone() };
+
+        match (segment, value) {
+            (JsonPathSegment::Key(key), Value::Object(map)) => map.get(key)
+                .map_or(Value::Null, |child| Self::select_segments(rest, child)),
+            (JsonPathSegment::Index(index), Value::Array(items)) => items.get(*index)
+                .map_or(Value::Null, |child| Self::select_segments(rest, child)),
+            (JsonPathSegment::AllItems, Value::Array(items)) => Value::Array(items.iter()
+                .map(|child| Self::select_segments(rest, child)).collect()),
+            _ => Value::Null,
+        }
+    }
+
+    /// Remove the nodes addressed by the path from `value`, if present. The whole document
+    /// removed is left `null`.
+    pub fn remove_from(&self, value: &mut Value) {
+        match self.segments.is_empty() {
+            true => *value = Value::Null,
+            false => Self::remove_segments(&self.segments, value),
+        }
+    }
+
+    fn remove_segments(segments: &[JsonPathSegment], value: &mut Value) {
+        let Some((segment, rest)) = segments.split_first() else { return };
+
+        match (segment, value, rest.is_empty()) {
+            (JsonPathSegment::Key(key), Value::Object(map), true) => { map.remove(key); }
+            (JsonPathSegment::Key(key), Value::Object(map), false) => {
+                if let Some(child) = map.get_mut(key) {
+                    Self::remove_segments(rest, child);
+                }
+            }
+            (JsonPathSegment::Index(index), Value::Array(items), true) if *index < items.len() => {
+                items.remove(*index);
+            }
+            (JsonPathSegment::Index(index), Value::Array(items), false) => {
+                if let Some(child) = items.get_mut(*index) {
+                    Self::remove_segments(rest, child);
+                }
+            }
+            (JsonPathSegment::AllItems, Value::Array(items), true) => items.clear(),
+            (JsonPathSegment::AllItems, Value::Array(items), false) => items.iter_mut()
+                .for_each(|child| Self::remove_segments(rest, child)),
+            _ => {}
+        }
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid JSON path `{value}`, expected a path like `.metadata.name` or `.items[0]`");
+
+        let mut segments = Vec::new();
+        let mut rest = value.strip_prefix('.').ok_or_else(invalid)?;
+        while !rest.is_empty() {
+            if let Some(bracketed) = rest.strip_prefix('[') {
+                let (index, tail) = bracketed.split_once(']').ok_or_else(invalid)?;
+                segments.push(match index {
+                    "" => JsonPathSegment::AllItems,
+                    index => JsonPathSegment::Index(index.parse().map_err(|_| invalid())?),
+                });
+                rest = tail.strip_prefix('.').unwrap_or(tail);
+            } else {
+                let key_end = rest.find(['.', '[']).unwrap_or(rest.len());
+                if key_end == 0 {
+                    return Err(invalid());
+                }
+                segments.push(JsonPathSegment::Key(rest[..key_end].to_string()));
+                rest = rest[key_end..].strip_prefix('.').unwrap_or(&rest[key_end..]);
+            }
+        }
+
+        Ok(JsonPath { raw: value.to_string(), segments })
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Parse a JSON document, without the nodes at the `ignored_paths`.
+pub fn parse_without(document: &str, ignored_paths: &[JsonPath]) -> Option<Value> {
+    let mut value = serde_json::from_str::<Value>(document).ok()?;
+    ignored_paths.iter().for_each(|path| path.remove_from(&mut value));
+    Some(value)
+}
@@ -0,0 +1,197 @@
+use std::{iter::Copied, ops::Range, slice};
+use imara_diff::{diff, intern::{InternedInput, TokenSource}, Algorithm};
+use super::comparator::Comparator;
+
+
+/// A line of a diff between two outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Unchanged(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+    /// A run of this many unchanged lines, collapsed away from the changes.
+    Elided(usize),
+}
+
+impl DiffLine<'_> {
+    pub fn is_change(&self) -> bool {
+        matches!(self, DiffLine::Added(_) | DiffLine::Removed(_))
+    }
+}
+
+
+/// Line diff of two outputs, listing the removed lines of a change before the added ones.
+pub fn line_diff<'a>(previous: &'a str, current: &'a str) -> Vec<DiffLine<'a>> {
+    let input = InternedInput::new(previous, current);
+    let previous_lines = previous.lines().collect::<Vec<_>>();
+    let current_lines = current.lines().collect::<Vec<_>>();
+
+    let mut diff_lines = Vec::new();
+    let mut current_position = 0;
+    diff(Algorithm::Histogram, &input, |removed: Range<u32>, added: Range<u32>| {
+        // Lines up to the change are the same on both sides
+        let unchanged = &current_lines[current_position..added.start as usize];
+        let removed = &previous_lines[removed.start as usize..removed.end as usize];
+        let added = &current_lines[added.start as usize..added.end as usize];
+
+        diff_lines.extend(unchanged.iter().map(|&line| DiffLine::Unchanged(line)));
+        diff_lines.extend(removed.iter().map(|&line| DiffLine::Removed(line)));
+        diff_lines.extend(added.iter().map(|&line| DiffLine::Added(line)));
+        current_position += unchanged.len() + added.len();
+    });
+    diff_lines.extend(current_lines[current_position..].iter().map(|&line| DiffLine::Unchanged(line)));
+
+    diff_lines
+}
+
+/// Line diff of two outputs per the `comparator`. Outputs it deems the same have no changes, and
+/// the removed lines it deems the same as the added lines in their place are left unchanged.
+pub fn compared_line_diff<'a>(previous: &'a str, current: &'a str, comparator: &dyn Comparator) -> Vec<DiffLine<'a>> {
+    if comparator.is_same(previous, current) {
+        return current.lines().map(DiffLine::Unchanged).collect();
+    }
+
+    let mut diff_lines = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for line in line_diff(previous, current).into_iter().chain([DiffLine::Elided(0)]) {
+        match line {
+            DiffLine::Removed(line) => removed.push(line),
+            DiffLine::Added(line) => added.push(line),
+            line => {
+                diff_lines.extend(compare_change(&removed, &added, comparator));
+                removed.clear();
+                added.clear();
+                diff_lines.push(line);
+            }
+        }
+    }
+    // Drop the end sentinel
+    diff_lines.pop();
+
+    diff_lines
+}
+
+/// Lines of a change, the `removed` and `added` lines in the same place deemed the same by the
+/// `comparator` being unchanged.
+fn compare_change<'a>(removed: &[&'a str], added: &[&'a str], comparator: &dyn Comparator) -> Vec<DiffLine<'a>> {
+    let mut diff_lines = Vec::new();
+    let mut changed_since = 0;
+    for (index, (removed_line, added_line)) in removed.iter().zip(added).enumerate() {
+        if comparator.is_same(removed_line, added_line) {
+            diff_lines.extend(removed[changed_since..index].iter().map(|&line| DiffLine::Removed(line)));
+            diff_lines.extend(added[changed_since..index].iter().map(|&line| DiffLine::Added(line)));
+            diff_lines.push(DiffLine::Unchanged(added_line));
+            changed_since = index + 1;
+        }
+    }
+    diff_lines.extend(removed[changed_since..].iter().map(|&line| DiffLine::Removed(line)));
+    diff_lines.extend(added[changed_since..].iter().map(|&line| DiffLine::Added(line)));
+    diff_lines
+}
+
+/// A token of a changed line, from the diff of its words or characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffSpan<'a> {
+    Unchanged(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// Tokens of a split line, diffed as a sequence.
+struct Tokens<'b, 'a>(&'b [&'a str]);
+
+impl<'b, 'a> TokenSource for Tokens<'b, 'a> {
+    type Token = &'a str;
+    type Tokenizer = Copied<slice::Iter<'b, &'a str>>;
+
+    fn tokenize(&self) -> Self::Tokenizer {
+        self.0.iter().copied()
+    }
+
+    fn estimate_tokens(&self) -> u32 {
+        self.0.len() as u32
+    }
+}
+
+/// Split a line into its words, its runs of whitespace and its other characters one by one.
+pub fn split_words(line: &str) -> Vec<&str> {
+    let class = |c: char| match c {
+        c if c.is_alphanumeric() || c == '_' => 0,
+        c if c.is_whitespace() => 1,
+        _ => 2,
+    };
+
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let is_word_end = match chars.peek() {
+            Some(&(_, next)) => class(c) == 2 || class(next) != class(c),
+            None => true,
+        };
+        if is_word_end {
+            let end = index + c.len_utf8();
+            words.push(&line[start..end]);
+            start = end;
+        }
+    }
+    words
+}
+
+/// Split a line into its characters.
+pub fn split_chars(line: &str) -> Vec<&str> {
+    line.char_indices().map(|(index, c)| &line[index..index + c.len_utf8()]).collect()
+}
+
+/// Diff of two lines split into tokens, listing the removed tokens of a change before the added ones.
+pub fn token_diff<'a>(previous: &[&'a str], current: &[&'a str]) -> Vec<DiffSpan<'a>> {
+    let input = InternedInput::new(Tokens(previous), Tokens(current));
+
+    let mut spans = Vec::new();
+    let mut current_position = 0;
+    diff(Algorithm::Histogram, &input, |removed: Range<u32>, added: Range<u32>| {
+        let unchanged = &current[current_position..added.start as usize];
+        spans.extend(unchanged.iter().map(|&token| DiffSpan::Unchanged(token)));
+        spans.extend(previous[removed.start as usize..removed.end as usize].iter().map(|&token| DiffSpan::Removed(token)));
+        spans.extend(current[added.start as usize..added.end as usize].iter().map(|&token| DiffSpan::Added(token)));
+        current_position = added.end as usize;
+    });
+    spans.extend(current[current_position..].iter().map(|&token| DiffSpan::Unchanged(token)));
+
+    spans
+}
+
+/// Collapse the runs of unchanged lines further than `context` lines away from any change
+/// into `DiffLine::Elided` separators. Diffs without any change are left as they are.
+pub fn elide_unchanged(diff_lines: Vec<DiffLine<'_>>, context: usize) -> Vec<DiffLine<'_>> {
+    let change_indices = diff_lines.iter().enumerate()
+        .filter(|(_, line)| line.is_change())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if change_indices.is_empty() {
+        return diff_lines;
+    }
+
+    // Distance of each line to its closest change
+    let distance_to_change = |index: usize| {
+        let next_change = change_indices.partition_point(|&change_index| change_index < index);
+        let after = change_indices.get(next_change).map(|&change_index| change_index - index);
+        let before = next_change.checked_sub(1).map(|previous_change| index - change_indices[previous_change]);
+        after.into_iter().chain(before).min().unwrap_or(usize::MAX)
+    };
+
+    let mut elided_lines = Vec::new();
+    for (index, line) in diff_lines.into_iter().enumerate() {
+        if distance_to_change(index) <= context {
+            elided_lines.push(line);
+            continue;
+        }
+        match elided_lines.last_mut() {
+            Some(DiffLine::Elided(count)) => *count += 1,
+            _ => elided_lines.push(DiffLine::Elided(1)),
+        }
+    }
+
+    elided_lines
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use crate::utils::OpenResult;
+use super::{shell::ShellBackend, watcher::{ExecOutput, ExecStatus}};
+
+
+/// Scripted reply of the `MockShell` to a command.
+#[derive(Debug, Clone)]
+pub struct MockReply {
+    pub output: String,
+    pub status: ExecStatus,
+    pub exit_code: Option<i32>,
+    /// Time the command takes to run, slept before replying.
+    pub delay: Duration,
+    /// Fail to run the command, like a shell which can not be driven anymore.
+    pub error: Option<String>,
+}
+
+impl MockReply {
+    /// A command completing with the `output` and a zero exit code.
+    pub fn output(output: impl Into<String>) -> Self {
+        Self {
+            output: output.into(),
+            status: ExecStatus::Completed,
+            exit_code: Some(0),
+            delay: Duration::ZERO,
+            error: None,
+        }
+    }
+
+    /// A command outliving the command `timeout`, with only the `partial_output` captured.
+    pub fn timed_out(partial_output: impl Into<String>, timeout: Duration) -> Self {
+        Self { status: ExecStatus::TimedOut(timeout), exit_code: None, ..Self::output(partial_output) }
+    }
+
+    /// A command failing to run, with the `message` as error.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { error: Some(message.into()), ..Self::output("") }
+    }
+
+    pub fn with_exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = Some(exit_code);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// Shell backend replying to the commands from a script, without spawning any process. For
+/// the watch loop, the TUIs and the integrations built on the library to be tested on
+/// deterministic outputs, delays and failures.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use watch_rs::models::{mock_shell::{MockReply, MockShell}, watcher::Watcher};
+/// let shell = MockShell::default().script("date", [
+///     MockReply::output("Mon\n"),
+///     MockReply::output("Tue\n").with_delay(Duration::from_millis(200)),
+///     MockReply::output("").with_exit_code(1),
+/// ]);
+/// let history = shell.history();
+/// let mut watcher = Watcher::with_backend(Box::new(shell), None)?;
+/// assert_eq!(watcher.exec_cmd_and_fetch_output("date")?.output, "Mon\n");
+/// assert_eq!(history.lock().unwrap().as_slice(), ["date"]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Default)]
+pub struct MockShell {
+    /// Replies to each command in order, the last one repeating once the others ran out.
+    scripts: HashMap<String, VecDeque<MockReply>>,
+    history: Arc<Mutex<Vec<String>>>,
+    is_killed: bool,
+}
+
+impl MockShell {
+    /// Reply to the `command` with the `replies` in order, the last one repeating once the
+    /// others ran out. The commands without a script complete without any output.
+    pub fn script(mut self, command: impl Into<String>, replies: impl IntoIterator<Item = MockReply>) -> Self {
+        self.scripts.insert(command.into(), replies.into_iter().collect());
+        self
+    }
+
+    /// Commands executed so far, in order, still readable once the shell moved into a `Watcher`.
+    pub fn history(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.history)
+    }
+
+    fn next_reply(&mut self, command: &str) -> MockReply {
+        match self.scripts.get_mut(command) {
+            Some(replies) if replies.len() > 1 => replies.pop_front().unwrap(),
+            Some(replies) => replies.front().cloned().unwrap_or_else(|| MockReply::output("")),
+            None => MockReply::output(""),
+        }
+    }
+}
+
+impl ShellBackend for MockShell {
+    fn exec(&mut self, command: &str, on_output: &mut dyn FnMut(&str)) -> OpenResult<ExecOutput> {
+        if self.is_killed {
+            return Err("the mock shell was killed".into());
+        }
+        self.history.lock().unwrap().push(command.to_string());
+
+        let reply = self.next_reply(command);
+        thread::sleep(reply.delay);
+        if let Some(error) = reply.error {
+            return Err(error.into());
+        }
+
+        // Streamed like a real shell, as complete lines
+        let complete_len = reply.output.rfind('\n').map_or(0, |newline| newline + 1);
+        if complete_len > 0 {
+            on_output(&reply.output[..complete_len]);
+        }
+        Ok(ExecOutput {
+            raw_output: reply.output.clone().into_bytes(), output: reply.output, status: reply.status,
+            exit_code: reply.exit_code, truncated_at: None,
+        })
+    }
+
+    fn is_alive(&mut self) -> bool {
+        !self.is_killed
+    }
+
+    fn kill(&mut self) -> OpenResult {
+        self.is_killed = true;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, time::Instant};
+    use super::*;
+    use crate::models::{
+        session::{EndEvent, EndReason},
+        watcher::Watcher,
+        watcher_loop::{LoopEvent, WatcherLoop, WatcherSchedule},
+    };
+
+    const COMMAND: &str = "date";
+
+    /// Iterations of the watch which ran, with whether their output changed from the previous one.
+    type Iterations = Vec<(ExecOutput, bool)>;
+    type WatchEnd = Result<EndEvent, (usize, Box<dyn Error>)>;
+
+    /// Watch the `COMMAND` in the `shell` on the `schedule`, without waiting between the iterations.
+    fn watch(shell: MockShell, mut schedule: WatcherSchedule) -> (WatchEnd, Iterations) {
+        schedule.interval = Duration::ZERO;
+        schedule.live_interval = Arc::new(Mutex::new(Duration::ZERO));
+        if schedule.until_stable.is_none() && !schedule.exit_on_change {
+            schedule.max_iterations.get_or_insert(10);
+        }
+
+        let mut watcher = Watcher::with_backend(Box::new(shell), None).unwrap();
+        let mut iterations = Vec::new();
+        let end = WatcherLoop::new(schedule).run(
+            |_| watcher.exec_cmd_and_fetch_output(COMMAND),
+            |event| {
+                if let LoopEvent::Ran { result, changed_from, .. } = event {
+                    iterations.push((result, changed_from.is_some()));
+                }
+                true
+            },
+        );
+        (end, iterations)
+    }
+
+    fn outputs(iterations: &Iterations) -> Vec<&str> {
+        iterations.iter().map(|(result, _)| result.output.as_str()).collect()
+    }
+
+    #[test]
+    fn runs_the_scripted_outputs_in_order() {
+        let shell = MockShell::default().script(COMMAND, [
+            MockReply::output("Mon\n"), MockReply::output("Tue\n"), MockReply::output("").with_exit_code(1),
+        ]);
+        let history = shell.history();
+        let mut schedule = WatcherSchedule::every(Duration::ZERO);
+        schedule.max_iterations = Some(4);
+
+        let (end, iterations) = watch(shell, schedule);
+        let end = end.unwrap();
+        assert_eq!((end.iterations, end.reason), (4, EndReason::IterationCount));
+        // The last reply repeats once the others ran out
+        assert_eq!(outputs(&iterations), ["Mon\n", "Tue\n", "", ""]);
+        assert_eq!(iterations.iter().map(|(result, _)| result.exit_code).collect::<Vec<_>>(), [
+            Some(0), Some(0), Some(1), Some(1),
+        ]);
+        // The first output has nothing to change from
+        assert_eq!(iterations.iter().map(|(_, changed)| *changed).collect::<Vec<_>>(), [false, true, true, false]);
+        assert_eq!(history.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn ends_the_watch_on_a_shell_failure() {
+        let shell = MockShell::default().script(COMMAND, [
+            MockReply::output("Mon\n"), MockReply::error("broken pipe"), MockReply::output("Tue\n"),
+        ]);
+
+        let (end, iterations) = watch(shell, WatcherSchedule::every(Duration::ZERO));
+        let (ran, err) = end.unwrap_err();
+        assert_eq!(ran, 1);
+        assert_eq!(err.to_string(), "broken pipe");
+        assert_eq!(outputs(&iterations), ["Mon\n"]);
+    }
+
+    #[test]
+    fn reports_the_commands_outliving_the_timeout() {
+        let timeout = Duration::from_millis(50);
+        let shell = MockShell::default().script(COMMAND, [
+            MockReply::timed_out("partial\n", timeout).with_delay(timeout), MockReply::output("done\n"),
+        ]);
+        let mut schedule = WatcherSchedule::every(Duration::ZERO);
+        schedule.max_iterations = Some(2);
+        schedule.interval_on_error = Some(Duration::ZERO);
+
+        let start = Instant::now();
+        let (end, iterations) = watch(shell, schedule);
+        assert_eq!(end.unwrap().iterations, 2);
+        assert!(start.elapsed() >= timeout);
+        let (timed_out, _) = &iterations[0];
+        assert_eq!(timed_out.status, ExecStatus::TimedOut(timeout));
+        assert_eq!((timed_out.output.as_str(), timed_out.exit_code), ("partial\n", None));
+        assert!(!timed_out.is_success());
+        assert!(iterations[1].0.is_success());
+    }
+
+    #[test]
+    fn ends_the_watch_once_the_output_is_stable() {
+        let shell = MockShell::default().script(COMMAND, [
+            MockReply::output("1\n"), MockReply::output("2\n"), MockReply::output("2\n"), MockReply::output("3\n"),
+        ]);
+        let mut schedule = WatcherSchedule::every(Duration::ZERO);
+        schedule.until_stable = Some(2);
+
+        let (end, iterations) = watch(shell, schedule);
+        let end = end.unwrap();
+        assert_eq!((end.iterations, end.reason), (3, EndReason::Stable));
+        assert_eq!(outputs(&iterations), ["1\n", "2\n", "2\n"]);
+    }
+
+    #[test]
+    fn ends_the_watch_once_the_output_changes() {
+        let shell = MockShell::default().script(COMMAND, [
+            MockReply::output("same\n"), MockReply::output("same\n"), MockReply::output("other\n"),
+        ]);
+        let mut schedule = WatcherSchedule::every(Duration::ZERO);
+        schedule.exit_on_change = true;
+
+        let (end, iterations) = watch(shell, schedule);
+        let end = end.unwrap();
+        assert_eq!((end.iterations, end.reason), (3, EndReason::Changed));
+        assert_eq!(iterations.iter().map(|(_, changed)| *changed).collect::<Vec<_>>(), [false, false, true]);
+    }
+}
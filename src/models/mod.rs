@@ -1 +1,11 @@
-pub mod watcher;
\ No newline at end of file
+#[cfg(feature = "async")]
+pub mod async_watcher;
+pub mod comparator;
+pub mod encoding;
+pub mod json_diff;
+pub mod line_diff;
+pub mod mock_shell;
+pub mod session;
+pub mod shell;
+pub mod watcher;
+pub mod watcher_loop;
\ No newline at end of file
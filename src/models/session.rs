@@ -0,0 +1,210 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
+use crate::utils::OpenResult;
+use super::{
+    comparator::{Comparator, ExactComparator},
+    watcher::{ExecStatus, Watcher},
+    watcher_loop::{LoopEvent, WatcherLoop, WatcherSchedule},
+};
+
+
+/// Result of one run of the watched command.
+#[derive(Debug, Clone)]
+pub struct IterationEvent {
+    pub iteration: usize,
+    pub output: String,
+    pub status: ExecStatus,
+    /// Exit code of the command, unknown when it did not complete.
+    pub exit_code: Option<i32>,
+    /// Time elapsed since the start of the session.
+    pub elapsed: Duration,
+}
+
+/// The output of an iteration differs from the output of the previous iteration.
+#[derive(Debug, Clone)]
+pub struct ChangeEvent {
+    pub iteration: usize,
+    pub previous_output: String,
+    pub current_output: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum ErrorEvent {
+    /// The command completed with a non-zero exit code.
+    CommandFailed { iteration: usize, exit_code: Option<i32>, output: String },
+    /// The command did not complete within the command timeout.
+    TimedOut { iteration: usize, timeout: Duration, partial_output: String },
+    /// The shell could not be driven anymore, the session ends right after.
+    Shell(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// Stopped through the `stop_handle`.
+    Stopped,
+    WatchDurationElapsed,
+    ShellError,
+    /// The output changed, when watching until it does.
+    Changed,
+    /// The output stayed the same for the requested number of iterations.
+    Stable,
+    /// The requested number of iterations ran.
+    IterationCount,
+    /// The cron schedule has no run left.
+    ScheduleExhausted,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EndEvent {
+    pub iterations: usize,
+    pub reason: EndReason,
+}
+
+
+type Callback<T> = Box<dyn FnMut(&T) + Send>;
+
+/// A watch of a command driven by the library, notifying the registered callbacks of its events.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use watch_rs::models::{session::WatchSession, watcher::Watcher};
+/// let mut session = WatchSession::new(Watcher::new(30_000, None)?, "date");
+/// session.interval = Duration::from_secs(5);
+/// session
+///     .on_change(|change| println!("changed on iteration {}", change.iteration))
+///     .on_error(|error| eprintln!("{error:?}"));
+/// session.run()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct WatchSession {
+    watcher: Watcher,
+    pub command: String,
+    /// Commands executed once in the shell, before the first iteration.
+    pub setup_commands: Option<String>,
+    pub interval: Duration,
+    /// Total duration of the session, runs forever when not set.
+    pub watch_duration: Option<Duration>,
+    /// Decides whether the outputs of two iterations are the same.
+    pub comparator: Arc<dyn Comparator>,
+    should_stop: Arc<AtomicBool>,
+    on_iteration: Vec<Callback<IterationEvent>>,
+    on_change: Vec<Callback<ChangeEvent>>,
+    on_error: Vec<Callback<ErrorEvent>>,
+    on_end: Vec<Callback<EndEvent>>,
+}
+
+impl WatchSession {
+    pub fn new(watcher: Watcher, command: impl Into<String>) -> Self {
+        Self {
+            watcher,
+            command: command.into(),
+            setup_commands: None,
+            interval: Duration::from_secs(1),
+            watch_duration: None,
+            comparator: Arc::new(ExactComparator),
+            should_stop: Arc::new(AtomicBool::new(false)),
+            on_iteration: Vec::new(),
+            on_change: Vec::new(),
+            on_error: Vec::new(),
+            on_end: Vec::new(),
+        }
+    }
+
+    /// Flag ending the session after the running iteration once set, usable from any thread.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.should_stop)
+    }
+
+    pub fn on_iteration(&mut self, callback: impl FnMut(&IterationEvent) + Send + 'static) -> &mut Self {
+        self.on_iteration.push(Box::new(callback));
+        self
+    }
+
+    pub fn on_change(&mut self, callback: impl FnMut(&ChangeEvent) + Send + 'static) -> &mut Self {
+        self.on_change.push(Box::new(callback));
+        self
+    }
+
+    pub fn on_error(&mut self, callback: impl FnMut(&ErrorEvent) + Send + 'static) -> &mut Self {
+        self.on_error.push(Box::new(callback));
+        self
+    }
+
+    pub fn on_end(&mut self, callback: impl FnMut(&EndEvent) + Send + 'static) -> &mut Self {
+        self.on_end.push(Box::new(callback));
+        self
+    }
+
+    /// Run the session on the current thread until it ends, notifying the callbacks along the way.
+    pub fn run(mut self) -> OpenResult<EndEvent> {
+        let result = self.run_iterations();
+
+        let end = match &result {
+            Ok(end) => *end,
+            Err((iterations, err)) => {
+                notify(&mut self.on_error, &ErrorEvent::Shell(err.to_string()));
+                EndEvent { iterations: *iterations, reason: EndReason::ShellError }
+            }
+        };
+        notify(&mut self.on_end, &end);
+        self.watcher.kill()?;
+
+        match result {
+            Ok(_) => Ok(end),
+            Err((_, err)) => Err(err),
+        }
+    }
+
+    /// Run the iterations, returning how the session ended along with any shell error.
+    fn run_iterations(&mut self) -> Result<EndEvent, (usize, Box<dyn std::error::Error>)> {
+        if let Some(setup_commands) = &self.setup_commands {
+            self.watcher.exec_cmd_and_fetch_output(setup_commands).map_err(|err| (0, err))?;
+        }
+
+        let mut schedule = WatcherSchedule::every(self.interval);
+        schedule.watch_duration = self.watch_duration;
+        schedule.comparator = Arc::clone(&self.comparator);
+
+        let start_checkpoint = Instant::now();
+        let watcher = &mut self.watcher;
+        let command = &self.command;
+        let (on_iteration, on_change, on_error) = (&mut self.on_iteration, &mut self.on_change, &mut self.on_error);
+        WatcherLoop::new(schedule).with_stop_handle(Arc::clone(&self.should_stop)).run(
+            |_| watcher.exec_cmd_and_fetch_output(command),
+            |event| {
+                let LoopEvent::Ran { iteration, result, changed_from, .. } = event else {
+                    return true;
+                };
+                let event = IterationEvent {
+                    iteration,
+                    output: result.output,
+                    status: result.status,
+                    exit_code: result.exit_code,
+                    elapsed: start_checkpoint.elapsed(),
+                };
+                notify(on_iteration, &event);
+
+                match event.status {
+                    ExecStatus::TimedOut(timeout) => notify(on_error, &ErrorEvent::TimedOut {
+                        iteration, timeout, partial_output: event.output.clone(),
+                    }),
+                    ExecStatus::Completed if event.exit_code != Some(0) => notify(on_error, &ErrorEvent::CommandFailed {
+                        iteration, exit_code: event.exit_code, output: event.output.clone(),
+                    }),
+                    ExecStatus::Completed | ExecStatus::Cancelled => {}
+                }
+
+                if let Some(previous_output) = changed_from {
+                    notify(on_change, &ChangeEvent { iteration, previous_output, current_output: event.output });
+                }
+                true
+            },
+        )
+    }
+}
+
+fn notify<T>(callbacks: &mut [Callback<T>], event: &T) {
+    callbacks.iter_mut().for_each(|callback| callback(event));
+}
@@ -0,0 +1,529 @@
+use once_cell::sync::Lazy;
+use rand::{Rng, SeedableRng};
+use std::{
+    collections::HashSet,
+    io::Write,
+    process::{Command, Stdio},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use subprocess::{Popen, PopenConfig, Redirection};
+use rexpect::errors::{Error as ReadError, ErrorKind as ReadErrorKind};
+use rexpect::reader::{NBReader, ReadUntil, Regex};
+use rand::{prelude::StdRng, distributions::Alphanumeric};
+use crate::utils::OpenResult;
+use super::{encoding::OutputEncoding, watcher::{ExecOutput, ExecStatus}};
+
+
+pub(super) static CMD_MARKER: Lazy<String> = Lazy::new(|| {
+    let rng = StdRng::seed_from_u64(5);
+    rng.sample_iter(Alphanumeric).map(|u| u as char).take(100).collect()
+});
+
+/// Shells spawned by the process which still live, killed along with their commands on exit.
+static LIVE_SHELLS: Lazy<Mutex<HashSet<u32>>> = Lazy::new(Default::default);
+
+
+/// Shell statement printing the `marker`, without the marker appearing verbatim in the statement.
+/// Keeps shell echoes of the statement (`set -v`, `set -x`) from matching as the marker itself.
+pub(super) fn print_marker_statement(marker: &str) -> String {
+    let (head, tail) = marker.split_at(marker.len() / 2);
+    format!("printf '%s%s' '{}' '{}'", head, tail)
+}
+
+/// Same as `print_marker_statement`, with the exit code of the previous command
+/// printed right after the marker and terminated by `EXIT_CODE_TERMINATOR`.
+pub(super) fn print_end_marker_statement(marker: &str) -> String {
+    let (head, tail) = marker.split_at(marker.len() / 2);
+    format!("printf '%s%s%s{}' '{}' '{}' \"$?\"", EXIT_CODE_TERMINATOR, head, tail)
+}
+
+pub(super) const EXIT_CODE_TERMINATOR: char = ';';
+
+/// How long a read of the shell output blocks at most, before checking the command timeout
+/// and forwarding the output captured so far.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time given to the cancelled commands to exit on SIGINT, before they get killed.
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Matches everything available in the reader buffer at once.
+static AVAILABLE_OUTPUT: Lazy<Regex> = Lazy::new(|| Regex::new("(?s).+").unwrap());
+
+/// Strip the injected scaffolding from a command's captured output,
+/// i.e. any echo of the end marker statement printed before the marker itself.
+pub(super) fn scrub_output(captured_stdout: String, end_marker: &str) -> String {
+    let (head, tail) = end_marker.split_at(end_marker.len() / 2);
+    if !captured_stdout.contains(head) {
+        return captured_stdout;
+    }
+
+    captured_stdout
+        .split_inclusive('\n')
+        .filter(|line| !(line.contains(head) && line.contains(tail)))
+        .collect()
+}
+
+/// Byte index of the last `count` chars of `text`.
+fn tail_start(text: &str, count: usize) -> usize {
+    text.char_indices().rev().nth(count.saturating_sub(1)).map_or(0, |(index, _)| index)
+}
+
+/// Output of a command read from the shell, one char per byte, kept up to the output size cap.
+/// Past the cap, the output is only read through for the end marker, of which a beginning split
+/// across reads is kept along.
+pub(super) struct CapturedOutput {
+    kept: String,
+    kept_len: usize,
+    /// Output read past the cap, trimmed down to what might be the beginning of the end marker.
+    overflow: String,
+    max_len: Option<usize>,
+    truncated_at: Option<usize>,
+}
+
+impl CapturedOutput {
+    pub(super) fn new(max_len: Option<usize>) -> Self {
+        Self { kept: String::new(), kept_len: 0, overflow: String::new(), max_len, truncated_at: None }
+    }
+
+    /// Append the `chunk` read from the shell, returning what follows the `end_marker` once it
+    /// got read, the marker being cut from the output.
+    pub(super) fn push(&mut self, chunk: &str, end_marker: &str) -> Option<String> {
+        if self.truncated_at.is_none() {
+            // Only the chunk is searched, along with what might be the beginning of the marker
+            let search_start = tail_start(&self.kept, end_marker.len() - 1);
+            self.kept.push_str(chunk);
+            if let Some(marker_position) = self.kept[search_start..].find(end_marker).map(|found| search_start + found) {
+                let after_end_marker = self.kept.split_off(marker_position).split_off(end_marker.len());
+                if let Some(cut) = self.cut_index() {
+                    self.kept.truncate(cut);
+                    self.truncated_at = self.max_len;
+                }
+                return Some(after_end_marker);
+            }
+            self.kept_len += chunk.chars().count();
+            if self.max_len.is_none_or(|max_len| self.kept_len <= max_len) {
+                return None;
+            }
+            let cut = self.cut_index()?;
+            self.overflow = self.kept.split_off(cut);
+            self.truncated_at = self.max_len;
+        } else {
+            self.overflow.push_str(chunk);
+        }
+
+        // The marker might have started ahead of the cut
+        let kept_tail = tail_start(&self.kept, end_marker.len() - 1);
+        let searched = format!("{}{}", &self.kept[kept_tail..], self.overflow);
+        if let Some(marker_position) = searched.find(end_marker) {
+            if kept_tail + marker_position < self.kept.len() {
+                self.kept.truncate(kept_tail + marker_position);
+                self.truncated_at = None;
+            }
+            return Some(searched[marker_position + end_marker.len()..].to_string());
+        }
+        self.overflow.drain(..tail_start(&self.overflow, end_marker.len() - 1));
+        None
+    }
+
+    /// Byte index of the kept output past the cap, if it outgrew it.
+    fn cut_index(&self) -> Option<usize> {
+        self.kept.char_indices().nth(self.max_len?).map(|(index, _)| index)
+    }
+
+    /// Output kept so far.
+    pub(super) fn kept(&self) -> &str {
+        &self.kept
+    }
+
+}
+
+/// Bytes of the output read from the shell, which hands each of them over as its own char.
+fn raw_bytes(captured: &str) -> Vec<u8> {
+    captured.chars().map(|c| c as u32 as u8).collect()
+}
+
+
+/// Capture of a command run in a shell, from what the shell prints around it: the noise ahead of
+/// the start marker, the output up to the end marker, then the exit code. Shared by the shells
+/// reading their output their own way, the `PopenShell` and the `AsyncWatcher`.
+pub(super) struct CommandCapture {
+    start_marker: String,
+    end_marker: String,
+    encoding: OutputEncoding,
+    /// Printed ahead of the start marker, until it got read.
+    preamble: Option<String>,
+    captured: CapturedOutput,
+    /// Printed after the end marker, once it got read.
+    after_end_marker: Option<String>,
+    /// Length of the captured output already handed over as complete lines.
+    forwarded_len: usize,
+}
+
+impl CommandCapture {
+    /// Capture of the `exec_count`-th command of a shell, the markers being unique per command so
+    /// that leftovers of a previous command can never match.
+    pub(super) fn new(exec_count: usize, encoding: OutputEncoding, max_output_bytes: Option<usize>) -> Self {
+        Self {
+            start_marker: format!("{}S{}", *CMD_MARKER, exec_count),
+            end_marker: format!("{}E{}", *CMD_MARKER, exec_count),
+            encoding,
+            preamble: Some(String::new()),
+            captured: CapturedOutput::new(max_output_bytes),
+            after_end_marker: None,
+            forwarded_len: 0,
+        }
+    }
+
+    /// Statements running the `command` in the shell between the markers.
+    pub(super) fn script(&self, command: &str) -> String {
+        // Grouped, for a cancellation to abort the whole command rather than its running line. The
+        // no-op keeps the group valid for the empty commands
+        format!(
+            "{}\n{{ :\n{}\n}}\n{}\n",
+            print_marker_statement(&self.start_marker), command, print_end_marker_statement(&self.end_marker)
+        )
+    }
+
+    /// Append the `chunk` printed by the shell, one char per byte.
+    pub(super) fn push(&mut self, chunk: &str) {
+        let chunk = match &mut self.preamble {
+            // Drop the noise emitted before the command started (job-control notices, echoes)
+            Some(preamble) => {
+                preamble.push_str(chunk);
+                let Some(marker_position) = preamble.find(&self.start_marker) else { return };
+                let chunk = preamble.split_off(marker_position + self.start_marker.len());
+                self.preamble = None;
+                chunk
+            }
+            None => chunk.to_string(),
+        };
+        match &mut self.after_end_marker {
+            Some(after_end_marker) => after_end_marker.push_str(&chunk),
+            None => self.after_end_marker = self.captured.push(&chunk, &self.end_marker),
+        }
+    }
+
+    /// Exit code of the command once it got printed entirely, unknown when it can not be parsed.
+    pub(super) fn exit_code(&self) -> Option<Option<i32>> {
+        // The exit code is printed right after the end marker, it might not be read yet
+        let (exit_code, _) = self.after_end_marker.as_ref()?.split_once(EXIT_CODE_TERMINATOR)?;
+        Some(exit_code.trim().parse().ok())
+    }
+
+    /// Complete lines of output captured since the previous call, decoded. Partial lines are held
+    /// back, they might be the beginning of the end marker.
+    pub(super) fn new_lines(&mut self) -> Option<String> {
+        let kept = self.captured.kept();
+        let complete_len = kept.rfind('\n').map_or(0, |newline| newline + 1);
+        if complete_len <= self.forwarded_len {
+            return None;
+        }
+        let new_lines = kept[self.forwarded_len..complete_len].to_string();
+        self.forwarded_len = complete_len;
+        Some(self.encoding.decode(&raw_bytes(&scrub_output(new_lines, &self.end_marker))))
+    }
+
+    /// Output of the command once it ended with the `status`, scrubbed of the end marker and
+    /// decoded.
+    pub(super) fn finish(self, status: ExecStatus, exit_code: Option<i32>) -> ExecOutput {
+        let truncated_at = self.captured.truncated_at;
+        let raw_output = raw_bytes(&scrub_output(self.captured.kept, &self.end_marker));
+        ExecOutput { output: self.encoding.decode(&raw_output), raw_output, status, exit_code, truncated_at }
+    }
+}
+
+
+/// Process the commands of a `Watcher` run in, one after the other and sharing its state, like
+/// the working directory and the exported variables. The bash shell spawned with `PopenShell`
+/// is the default one, other backends (a PTY, a Windows shell, a mock of the commands in tests)
+/// plug into the `Watcher` with `Watcher::with_backend`.
+pub trait ShellBackend: Send {
+    /// Run the `command`, handing each new complete line of output to `on_output` once while it
+    /// runs, to be appended to the lines handed before. A command outliving the command timeout is reported in its `ExecStatus`.
+    fn exec(&mut self, command: &str, on_output: &mut dyn FnMut(&str)) -> OpenResult<ExecOutput>;
+
+    /// Decode the outputs of the commands from the `encoding`, rather than UTF-8.
+    fn set_encoding(&mut self, _encoding: OutputEncoding) {}
+
+    /// Only keep the first `max_output_bytes` of the outputs, the rest being read and discarded.
+    fn set_max_output_bytes(&mut self, _max_output_bytes: Option<usize>) {}
+
+    /// Whether the shell still runs, and can take more commands.
+    fn is_alive(&mut self) -> bool;
+
+    fn kill(&mut self) -> OpenResult;
+
+    /// Handle cancelling the running command from another thread, None when the backend can not.
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        None
+    }
+}
+
+
+/// Cancels the command running in a local shell from any thread, while the shell itself lives
+/// on. The shell runs its commands as jobs in process groups of their own, which get interrupted
+/// along with all their processes, the rest of the command being aborted like on a Ctrl+C in a
+/// terminal. The cancelled command completes as `ExecStatus::Cancelled`.
+#[derive(Clone)]
+pub struct CancelHandle {
+    shell_pid: u32,
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Handle of the local shell of `shell_pid`, which gets killed along with its commands by
+    /// `kill_all_shells` until killed on its own.
+    pub(super) fn for_shell(shell_pid: u32) -> Self {
+        LIVE_SHELLS.lock().unwrap().insert(shell_pid);
+        Self { shell_pid, is_cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Whether the running command got cancelled, clearing the request.
+    pub(super) fn take_cancelled(&self) -> bool {
+        self.is_cancelled.swap(false, Ordering::AcqRel)
+    }
+
+    /// Interrupt the commands still running once one timed out, for the shell to take the next ones.
+    pub(super) fn interrupt_timed_out(&self) -> OpenResult {
+        interrupt_jobs(self.shell_pid, running_jobs(self.shell_pid)?);
+        Ok(())
+    }
+
+    /// Interrupt the running command, returning whether there was one. Commands still running
+    /// after a grace period get killed.
+    pub fn cancel(&self) -> OpenResult<bool> {
+        let jobs = running_jobs(self.shell_pid)?;
+        if jobs.is_empty() {
+            return Ok(false);
+        }
+
+        self.is_cancelled.store(true, Ordering::Release);
+        interrupt_jobs(self.shell_pid, jobs);
+        Ok(true)
+    }
+}
+
+/// Kill the shells still alive along with all their commands, for none of them to outlive the
+/// process, like when it exits on a signal.
+pub fn kill_all_shells() {
+    let shell_pids = std::mem::take(&mut *LIVE_SHELLS.lock().unwrap());
+    shell_pids.into_iter().for_each(kill_process_tree);
+}
+
+/// Processes the shell of `shell_pid` runs its commands in, as jobs leading their process group.
+fn running_jobs(shell_pid: u32) -> OpenResult<Vec<String>> {
+    let children = Command::new("pgrep").args(["-P", &shell_pid.to_string()]).output()?;
+    Ok(String::from_utf8_lossy(&children.stdout)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Interrupt the `jobs` of the shell of `shell_pid`, killing them once the grace period elapsed.
+fn interrupt_jobs(shell_pid: u32, mut jobs: Vec<String>) {
+    signal_jobs(&jobs, "INT");
+    thread::spawn(move || {
+        thread::sleep(CANCEL_GRACE_PERIOD);
+        // Only the jobs still running, the IDs of the ones which exited might have been reused
+        let running = running_jobs(shell_pid).unwrap_or_default();
+        jobs.retain(|job| running.contains(job));
+        signal_jobs(&jobs, "KILL");
+    });
+}
+
+/// Kill the live shell of `shell_pid` along with its commands, for good.
+pub(super) fn kill_shell(shell_pid: u32) {
+    LIVE_SHELLS.lock().unwrap().remove(&shell_pid);
+    kill_process_tree(shell_pid);
+}
+
+/// Kill the shell of `shell_pid` along with its commands, whether they run in the process groups
+/// of their jobs or in the one of the shell.
+fn kill_process_tree(shell_pid: u32) {
+    // Listed ahead, the jobs are not children of the shell anymore once it got killed
+    let jobs = running_jobs(shell_pid).unwrap_or_default();
+    signal_jobs(&[shell_pid.to_string()], "KILL");
+    signal_jobs(&jobs, "KILL");
+}
+
+/// Send the `signal` to the process groups of the `jobs`, or to the processes themselves when
+/// they lead no group, like the command substitutions.
+fn signal_jobs(jobs: &[String], signal: &str) {
+    let signal = format!("-{signal}");
+    let send = |target: &str| Command::new("kill")
+        .args([signal.as_str(), "--", target])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+
+    for job in jobs {
+        if !send(&format!("-{job}")) {
+            send(job);
+        }
+    }
+}
+
+
+/// Bash shell spawned as a subprocess, driven through its stdin and stdout. Every command is
+/// wrapped between unique markers printed by the shell, delimiting its output and its exit code.
+pub struct PopenShell {
+    shell: Popen,
+    stdout_reader: NBReader,
+    exec_count: usize,
+    command_timeout: Duration,
+    encoding: OutputEncoding,
+    max_output_bytes: Option<usize>,
+    cancel_handle: Option<CancelHandle>,
+}
+
+impl PopenShell {
+    /// Spawn the `shell_argv` shell, which has to be Bash compatible, with the commands timing
+    /// out after `command_timeout` milliseconds.
+    pub fn spawn(shell_argv: &[&str], command_timeout: u64) -> OpenResult<Self> {
+        let mut shell_envs = PopenConfig::current_env();
+        shell_envs.push(("LC_ALL".into(), "C".into()));
+
+        // Setup Bash Shell subprocess
+        let mut shell = Popen::create(
+            shell_argv,
+            PopenConfig {
+                stdout: Redirection::Pipe,
+                stderr: Redirection::Merge,
+                stdin: Redirection::Pipe,
+                env: Some(shell_envs.clone()),
+                detached: true,
+                // Leading a process group of its own, so that all of it can be killed at once
+                setpgid: true,
+                ..Default::default()
+            },
+        )?;
+        // Reads wake up regularly, the command timeout is enforced across them
+        let stdout_reader = NBReader::new(
+            shell.stdout.take().unwrap(), Some(READ_POLL_INTERVAL.as_millis() as u64)
+        );
+
+        let cancel_handle = shell.pid().map(CancelHandle::for_shell);
+
+        // Init and execute shell setup commands. Job control and the ignored interrupts make the
+        // commands cancellable, the shell outliving them
+        let mut popen_shell = Self {
+            shell, stdout_reader, exec_count: 0, command_timeout: Duration::from_millis(command_timeout),
+            encoding: OutputEncoding::default(), max_output_bytes: None, cancel_handle,
+        };
+        popen_shell.exec("
+            set -m;
+            trap : INT;
+            shopt -s expand_aliases;
+            source ~/.bashrc;
+        ", &mut |_| {})?;
+
+        Ok(popen_shell)
+
+    }
+
+    /// The same shell, its commands not being cancellable. For the shells running their commands
+    /// on another host, like over SSH, whose processes are out of reach.
+    pub fn without_cancel(mut self) -> Self {
+        self.cancel_handle = None;
+        self
+    }
+
+    /// Whether the running command got cancelled, clearing the request.
+    fn take_cancelled(&self) -> bool {
+        self.cancel_handle.as_ref().is_some_and(CancelHandle::take_cancelled)
+    }
+
+    /// Read the shell output until the `needle`, for as long as the command started at `start` is
+    /// within the command timeout. A timeout is reported as an `ExecStatus` rather than an error.
+    fn read_until(&mut self, needle: &ReadUntil, start: Instant) -> OpenResult<Result<(String, String), ExecStatus>> {
+        loop {
+            match self.stdout_reader.read_until(needle) {
+                Ok(found) => return Ok(Ok(found)),
+                Err(ReadError(ReadErrorKind::Timeout(..), _)) if start.elapsed() < self.command_timeout => {}
+                Err(ReadError(ReadErrorKind::Timeout(..), _)) => return Ok(Err(ExecStatus::TimedOut(self.command_timeout))),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl ShellBackend for PopenShell {
+    fn exec(&mut self, command: &str, on_output: &mut dyn FnMut(&str)) -> OpenResult<ExecOutput> {
+        self.take_cancelled();
+        let mut exec_output = self.exec_uncancelled(command, on_output)?;
+        // A timed out command is interrupted, for the shell to take the next ones
+        if let (ExecStatus::TimedOut(_), Some(cancel_handle)) = (exec_output.status, &self.cancel_handle) {
+            cancel_handle.interrupt_timed_out()?;
+        }
+        if self.take_cancelled() {
+            exec_output.status = ExecStatus::Cancelled;
+            exec_output.exit_code = None;
+        }
+        Ok(exec_output)
+    }
+
+    fn set_encoding(&mut self, encoding: OutputEncoding) {
+        self.encoding = encoding;
+    }
+
+    fn set_max_output_bytes(&mut self, max_output_bytes: Option<usize>) {
+        self.max_output_bytes = max_output_bytes;
+    }
+
+    fn is_alive(&mut self) -> bool {
+        self.shell.poll().is_none()
+    }
+
+    fn kill(&mut self) -> OpenResult {
+        if let Some(shell_pid) = self.shell.pid() {
+            kill_shell(shell_pid);
+        }
+        self.shell.kill()?;
+        self.shell.wait()?;
+        Ok(())
+    }
+
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        self.cancel_handle.clone()
+    }
+}
+
+/// Shells dropped without being killed, like on an error, take their commands down with them.
+impl Drop for PopenShell {
+    fn drop(&mut self) {
+        if self.is_alive() {
+            self.kill().ok();
+        }
+    }
+}
+
+impl PopenShell {
+    fn exec_uncancelled(&mut self, command: &str, on_output: &mut dyn FnMut(&str)) -> OpenResult<ExecOutput> {
+        self.exec_count += 1;
+        let mut capture = CommandCapture::new(self.exec_count, self.encoding, self.max_output_bytes);
+        write!(self.shell.stdin.as_mut().unwrap(), "{}", capture.script(command))?;
+
+        let start = Instant::now();
+        loop {
+            let read = self.read_until(&ReadUntil::Regex(AVAILABLE_OUTPUT.clone()), start)?;
+            // Commands flooding their output never leave the reads idle long enough to time out
+            let read = read.and_then(|read| match start.elapsed() < self.command_timeout {
+                true => Ok(read),
+                false => Err(ExecStatus::TimedOut(self.command_timeout)),
+            });
+            match read {
+                Ok((_, available_output)) => capture.push(&available_output),
+                Err(timed_out) => return Ok(capture.finish(timed_out, None)),
+            }
+
+            if let Some(exit_code) = capture.exit_code() {
+                return Ok(capture.finish(ExecStatus::Completed, exit_code));
+            }
+            if let Some(new_lines) = capture.new_lines() {
+                on_output(&new_lines);
+            }
+        }
+    }
+}
@@ -1,65 +1,141 @@
-
-use once_cell::sync::Lazy;
-use rand::{Rng, SeedableRng};
-use std::io::Write;
-use subprocess::{Popen, PopenConfig, Redirection};
-use rexpect::reader::{NBReader, ReadUntil};
-use rand::{prelude::StdRng, distributions::Alphanumeric};
+use std::{fmt, path::Path, time::Duration};
 use crate::utils::OpenResult;
+use super::{encoding::OutputEncoding, shell::{CancelHandle, PopenShell, ShellBackend}};
+
+
+/// Single-quote `value` for the shell, so that it is passed on as one literal word.
+pub(super) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Shell the commands run in, unless configured otherwise.
+pub const DEFAULT_SHELL: &str = "/bin/bash";
+
+
+/// How a single command execution in the watcher shell ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExecStatus {
+    Completed,
+    /// The command did not finish within the command timeout, only a partial output was captured.
+    TimedOut(Duration),
+    /// The command was cancelled while it ran, only a partial output was captured.
+    Cancelled,
+}
 
+impl fmt::Display for ExecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecStatus::Completed => write!(f, "completed"),
+            ExecStatus::TimedOut(timeout) => write!(
+                f, "TIMED OUT after {}, partial output", humantime::format_duration(*timeout)
+            ),
+            ExecStatus::Cancelled => write!(f, "CANCELLED, partial output"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub output: String,
+    /// Bytes of the output as the command printed them, ahead of their decoding.
+    pub raw_output: Vec<u8>,
+    pub status: ExecStatus,
+    /// Exit code of the command, unknown when it did not complete.
+    pub exit_code: Option<i32>,
+    /// Bytes the output got cut at when it outgrew the output size cap, the rest being discarded.
+    pub truncated_at: Option<usize>,
+}
 
-static CMD_END_MARKER: Lazy<String> = Lazy::new(|| {
-    let rng = StdRng::seed_from_u64(5);
-    rng.sample_iter(Alphanumeric).map(|u| u as char).take(100).collect()
-});
+impl ExecOutput {
+    /// Whether the command completed with a zero exit code.
+    pub fn is_success(&self) -> bool {
+        self.status == ExecStatus::Completed && self.exit_code == Some(0)
+    }
+}
 
 
 pub struct Watcher {
-    shell: Popen,
-    stdout_reader: NBReader,
+    backend: Box<dyn ShellBackend>,
 }
 
 impl Watcher {
-    pub fn new(command_timeout: u64) -> OpenResult<Self> {
-        let mut shell_envs = PopenConfig::current_env();
-        shell_envs.push(("LC_ALL".into(), "C".into()));
-
-        // Setup Bash Shell subprocess
-        let mut shell = Popen::create(
-            &["/bin/bash"],
-            PopenConfig {
-                stdout: Redirection::Pipe,
-                stderr: Redirection::Merge,
-                stdin: Redirection::Pipe,
-                env: Some(shell_envs.clone()),
-                detached: true,
-                ..Default::default()
-            },
-        )?;
-        let stdout_reader = NBReader::new(shell.stdout.take().unwrap(),Some(command_timeout));
-
-        // Init and execute shell setup commands
-        let mut watcher = Self { shell, stdout_reader };
-        watcher.exec_cmd_and_fetch_output("
-            shopt -s expand_aliases;
-            source ~/.bashrc;
-        ")?;
+    pub fn new(command_timeout: u64, cwd: Option<&Path>) -> OpenResult<Self> {
+        Self::in_shell(Path::new(DEFAULT_SHELL), command_timeout, cwd)
+    }
+
+    /// Watcher running its commands in the `shell` program, which has to be Bash compatible,
+    /// like a Bash installed elsewhere than `/bin/bash`.
+    pub fn in_shell(shell: &Path, command_timeout: u64, cwd: Option<&Path>) -> OpenResult<Self> {
+        let shell = PopenShell::spawn(&[&shell.to_string_lossy()], command_timeout)?;
+        Self::with_backend(Box::new(shell), cwd)
+    }
+
+    /// Watcher running its commands in a Bash shell on a remote `host`, through SSH.
+    /// Authentication has to be non-interactive (keys or agent), as stdin is owned by the watcher.
+    pub fn over_ssh(host: &str, command_timeout: u64, cwd: Option<&Path>) -> OpenResult<Self> {
+        let shell = PopenShell::spawn(&["ssh", "-T", "-o", "BatchMode=yes", host, "/bin/bash"], command_timeout)?
+            .without_cancel();
+        Self::with_backend(Box::new(shell), cwd)
+    }
+
+    /// Watcher running its commands in the `backend`, moved into the `cwd` directory when provided.
+    pub fn with_backend(backend: Box<dyn ShellBackend>, cwd: Option<&Path>) -> OpenResult<Self> {
+        let mut watcher = Self { backend };
+
+        // Changing directory from within the shell, so that it applies to remote shells as well
+        if let Some(cwd) = cwd {
+            let cwd_output = watcher.exec_cmd_and_fetch_output(
+                &format!("cd -- {}", shell_quote(&cwd.to_string_lossy()))
+            )?;
+            if !cwd_output.is_success() {
+                let reason = cwd_output.output.trim();
+                return Err(format!("cannot change directory to {} : {reason}", cwd.display()).into());
+            }
+        }
 
         Ok(watcher)
     }
 
-    pub fn exec_cmd_and_fetch_output(&mut self, command: &str) -> OpenResult<String> {
-        let stdin = self.shell.stdin.as_mut().unwrap();
+    /// Decode the outputs of the commands from the `encoding`, rather than UTF-8.
+    pub fn with_encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.backend.set_encoding(encoding);
+        self
+    }
+
+    /// Only keep the first `max_output_bytes` of the outputs, for commands flooding their output
+    /// not to exhaust the memory. The outputs cut short report it in `ExecOutput::truncated_at`.
+    pub fn with_max_output_bytes(mut self, max_output_bytes: Option<usize>) -> Self {
+        self.backend.set_max_output_bytes(max_output_bytes);
+        self
+    }
+
+    pub fn exec_cmd_and_fetch_output(&mut self, command: &str) -> OpenResult<ExecOutput> {
+        self.exec_cmd_streaming(command, |_| {})
+    }
+
+    /// Execute the `command`, handing each new complete line of output to `on_output` once
+    /// while it runs, so that long-running commands can be rendered as they progress.
+    pub fn exec_cmd_streaming(&mut self, command: &str, mut on_output: impl FnMut(&str)) -> OpenResult<ExecOutput> {
+        self.backend.exec(command, &mut on_output)
+    }
 
-        writeln!(stdin, "{}", command)?;
-        writeln!(stdin, "printf '{}'", CMD_END_MARKER.clone())?;
+    /// Whether the shell still runs, and can take more commands.
+    pub fn is_alive(&mut self) -> bool {
+        self.backend.is_alive()
+    }
 
-        let (captured_stdout, _) = self.stdout_reader
-            .read_until(&ReadUntil::String(CMD_END_MARKER.clone()))?;
-        Ok(captured_stdout)
+    /// Export the `key` environment variable in the shell, visible to all the following commands.
+    pub fn export_env(&mut self, key: &str, value: &str) -> OpenResult {
+        self.exec_cmd_and_fetch_output(&format!("export {}={}", key, shell_quote(value)))?;
+        Ok(())
     }
 
     pub fn kill(&mut self) -> OpenResult {
-        Ok(self.shell.kill()?)
+        self.backend.kill()
+    }
+
+    /// Handle cancelling the running command from another thread, None when the shell can not.
+    pub fn cancel_handle(&self) -> Option<CancelHandle> {
+        self.backend.cancel_handle()
     }
-}
\ No newline at end of file
+}
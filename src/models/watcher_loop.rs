@@ -0,0 +1,539 @@
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Local};
+use log::{debug, trace};
+use super::{
+    comparator::{Comparator, ExactComparator},
+    session::{EndEvent, EndReason},
+    watcher::ExecOutput,
+};
+
+
+/// How often a paused loop checks whether it got resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest wait between two polls of the triggers, for the loop to stop promptly.
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Name of the `AdjustmentTrigger`, which does not run an iteration when fired.
+pub(super) const ADJUSTMENT_TRIGGER: &str = "interval adjustment";
+
+
+/// Source of the events running the next iteration of a `WatcherLoop`. The triggers of a
+/// watch are combined, the next iteration runs on whichever fires first.
+pub trait Trigger: Send + TriggerClone {
+    /// Name of the trigger, in the logs.
+    fn name(&self) -> &'static str;
+
+    /// Arm the trigger once an iteration ran, `wait` being the interval before the next one.
+    fn arm(&mut self, _wait: Duration) {}
+
+    /// Whether the trigger fired since it got armed.
+    fn poll(&mut self) -> bool;
+
+    /// Longest time to wait before polling the trigger again.
+    fn poll_interval(&self) -> Duration {
+        MAX_POLL_INTERVAL
+    }
+}
+
+/// Copies of the triggers, for each loop of a schedule to poll its own.
+pub trait TriggerClone {
+    fn clone_box(&self) -> Box<dyn Trigger>;
+}
+
+impl<T: Trigger + Clone + 'static> TriggerClone for T {
+    fn clone_box(&self) -> Box<dyn Trigger> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Trigger> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Wait for the first of the `triggers` to fire, returning its name. Gives up with None once
+/// `should_stop` gets set.
+pub fn wait_for_trigger(triggers: &mut [Box<dyn Trigger>], should_stop: &AtomicBool) -> Option<&'static str> {
+    loop {
+        if should_stop.load(Ordering::Acquire) {
+            return None;
+        }
+        let fired = poll_triggers(triggers);
+        if fired.is_some() {
+            return fired;
+        }
+        thread::sleep(triggers_poll_interval(triggers));
+    }
+}
+
+/// Name of the first of the `triggers` which fired, if any.
+pub(super) fn poll_triggers(triggers: &mut [Box<dyn Trigger>]) -> Option<&'static str> {
+    triggers.iter_mut().find_map(|trigger| trigger.poll().then(|| trigger.name()))
+}
+
+/// Longest time to wait before polling the `triggers` again.
+pub(super) fn triggers_poll_interval(triggers: &[Box<dyn Trigger>]) -> Duration {
+    let poll_interval = triggers.iter().map(|trigger| trigger.poll_interval()).min();
+    poll_interval.map_or(MAX_POLL_INTERVAL, |poll_interval| poll_interval.min(MAX_POLL_INTERVAL))
+}
+
+/// Fires once the interval following the previous iteration elapsed.
+#[derive(Clone)]
+pub struct IntervalTrigger {
+    deadline: Instant,
+}
+
+impl Default for IntervalTrigger {
+    fn default() -> Self {
+        Self { deadline: Instant::now() }
+    }
+}
+
+impl Trigger for IntervalTrigger {
+    fn name(&self) -> &'static str {
+        "interval"
+    }
+
+    fn arm(&mut self, wait: Duration) {
+        self.deadline = Instant::now() + wait;
+    }
+
+    fn poll(&mut self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Fires on a manual refresh, once the flag gets set. A refresh requested while the command
+/// runs fires right after it.
+#[derive(Clone)]
+pub struct RefreshTrigger(pub Arc<AtomicBool>);
+
+impl Trigger for RefreshTrigger {
+    fn name(&self) -> &'static str {
+        "refresh"
+    }
+
+    fn poll(&mut self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Fires once the live interval got adjusted, for the wait to restart with the new interval
+/// rather than to run an iteration.
+#[derive(Clone)]
+pub struct AdjustmentTrigger {
+    live_interval: Arc<Mutex<Duration>>,
+    armed_with: Duration,
+}
+
+impl AdjustmentTrigger {
+    pub fn new(live_interval: Arc<Mutex<Duration>>) -> Self {
+        let armed_with = *live_interval.lock().unwrap();
+        Self { live_interval, armed_with }
+    }
+}
+
+impl Trigger for AdjustmentTrigger {
+    fn name(&self) -> &'static str {
+        ADJUSTMENT_TRIGGER
+    }
+
+    fn arm(&mut self, _wait: Duration) {
+        self.armed_with = *self.live_interval.lock().unwrap();
+    }
+
+    fn poll(&mut self) -> bool {
+        *self.live_interval.lock().unwrap() != self.armed_with
+    }
+}
+
+
+/// Cron expression running the iterations at specific times rather than every interval.
+/// Takes the 5 fields of crontab, `min hour day month weekday`, or 6 and 7 fields starting
+/// with the seconds and ending with the years.
+#[derive(Clone)]
+pub struct CronSchedule {
+    expression: String,
+    schedule: cron::Schedule,
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = expression.trim();
+        // The crontab expressions run on the minute
+        let schedule = match expression.split_whitespace().count() {
+            5 => cron::Schedule::from_str(&format!("0 {expression}")),
+            _ => cron::Schedule::from_str(expression),
+        };
+        Ok(Self {
+            expression: expression.to_string(),
+            schedule: schedule.map_err(|err| format!("invalid cron expression: {err}"))?,
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl CronSchedule {
+    /// Next time on the schedule, None once it has no run left.
+    pub fn next_run(&self) -> Option<DateTime<Local>> {
+        self.schedule.upcoming(Local).next()
+    }
+}
+
+
+/// Growth of the interval while the command keeps failing, reset on the next success.
+#[derive(Clone, Copy)]
+pub struct Backoff {
+    pub multiplier: f64,
+    pub max_interval: Duration,
+}
+
+/// Parameters driving the iterations of a `WatcherLoop`.
+#[derive(Clone)]
+pub struct WatcherSchedule {
+    pub interval: Duration,
+    /// Run the iterations at the times of this cron schedule, rather than every `interval`.
+    pub cron: Option<CronSchedule>,
+    /// Interval used while the command fails, for a faster feedback on failures.
+    pub interval_on_error: Option<Duration>,
+    pub watch_duration: Option<Duration>,
+    /// Schedule iterations on a fixed cadence from the start checkpoint, rather than sleeping
+    /// `interval` after each run. Overrunning iterations skip the missed slots.
+    pub precise: bool,
+    /// End the watch once the output stayed identical for this many consecutive iterations.
+    pub until_stable: Option<usize>,
+    /// End the watch after this many iterations.
+    pub max_iterations: Option<usize>,
+    pub backoff: Option<Backoff>,
+    /// End the watch as soon as the output changes from the previous iteration.
+    pub exit_on_change: bool,
+    /// Decides whether the outputs of two iterations are the same.
+    pub comparator: Arc<dyn Comparator>,
+    /// Set while the watch is suspended, the next iteration waits for it to be cleared.
+    pub paused: Arc<AtomicBool>,
+    /// Set to run the next iteration right away, cleared once it starts.
+    pub refresh: Arc<AtomicBool>,
+    /// Set to skip the next scheduled iteration, cleared once skipped. A refresh still runs.
+    pub skip_next: Arc<AtomicBool>,
+    /// Interval adjusted while watching, taking over `interval` from the wait during which it changed.
+    pub live_interval: Arc<Mutex<Duration>>,
+    /// Triggers running the iterations along with the interval, like the changes of files.
+    pub triggers: Vec<Box<dyn Trigger>>,
+    /// Only the `triggers` run the iterations, rather than the interval as well.
+    pub triggers_only: bool,
+}
+
+impl WatcherSchedule {
+    /// Iterations every `interval`, until stopped.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            cron: None,
+            interval_on_error: None,
+            watch_duration: None,
+            precise: false,
+            until_stable: None,
+            max_iterations: None,
+            backoff: None,
+            exit_on_change: false,
+            comparator: Arc::new(ExactComparator),
+            paused: Arc::new(AtomicBool::new(false)),
+            refresh: Arc::new(AtomicBool::new(false)),
+            skip_next: Arc::new(AtomicBool::new(false)),
+            live_interval: Arc::new(Mutex::new(interval)),
+            triggers: Vec::new(),
+            triggers_only: false,
+        }
+    }
+
+    /// Whether the iterations run every interval, rather than on the `triggers` only.
+    pub fn is_interval_triggered(&self) -> bool {
+        !self.triggers_only || self.triggers.is_empty()
+    }
+
+    /// Triggers of the iterations, the refresh along with the interval and the added ones.
+    pub(super) fn all_triggers(&self) -> Vec<Box<dyn Trigger>> {
+        let mut triggers: Vec<Box<dyn Trigger>> = vec![Box::new(RefreshTrigger(Arc::clone(&self.refresh)))];
+        if self.is_interval_triggered() {
+            triggers.push(Box::new(IntervalTrigger::default()));
+            triggers.push(Box::new(AdjustmentTrigger::new(Arc::clone(&self.live_interval))));
+        }
+        triggers.extend(self.triggers.iter().cloned());
+        triggers
+    }
+
+    /// Interval following an iteration, which switches to the `interval_on_error` or grows
+    /// when backing off on failures.
+    pub(super) fn next_interval(&self, current_interval: Duration, succeeded: bool) -> Duration {
+        if succeeded {
+            return self.interval;
+        }
+        match (self.interval_on_error, self.backoff) {
+            (Some(interval_on_error), _) => interval_on_error,
+            (None, Some(backoff)) => current_interval
+                .mul_f64(backoff.multiplier)
+                .clamp(self.interval, backoff.max_interval.max(self.interval)),
+            (None, None) => self.interval,
+        }
+    }
+
+    /// Time to wait before running the next iteration, `iteration` runs after the `start` checkpoint.
+    /// None once the cron schedule has no run left.
+    pub(super) fn wait_before_next_run(&self, start: Instant, iteration: usize, interval: Duration) -> Option<Duration> {
+        if let Some(cron) = &self.cron {
+            let next_run = cron.next_run()?;
+            return Some((next_run - Local::now()).to_std().unwrap_or_default());
+        }
+
+        Some(self.wait_before_next_interval(start, iteration, interval))
+    }
+
+    fn wait_before_next_interval(&self, start: Instant, iteration: usize, interval: Duration) -> Duration {
+        // Backed off intervals are not aligned on the cadence
+        if !self.precise || self.interval.is_zero() || interval != self.interval {
+            return interval;
+        }
+
+        // Align on the next slot of the fixed cadence, skipping the slots missed by an overrun
+        let elapsed = start.elapsed().as_nanos();
+        let interval = self.interval.as_nanos();
+        let next_slot = elapsed / interval + 1;
+        if next_slot > iteration as u128 {
+            trace!("Iteration {iteration} overran its slot, coalescing into slot {next_slot}");
+        }
+
+        // Whatever is left of the current slot, in nanoseconds for the slot count not to overflow
+        // on long watches of a short interval
+        let remaining = interval - elapsed % interval;
+        u64::try_from(remaining).map_or(self.interval, Duration::from_nanos)
+    }
+
+    /// Why the watch ends after the `iteration` ran, None when it goes on.
+    pub(super) fn end_reason(&self, iteration: usize, outputs: &OutputHistory, start: Instant) -> Option<EndReason> {
+        // Break if the output changed and we are to exit on changes
+        if self.exit_on_change && outputs.has_changed {
+            debug!("Output changed on iteration {iteration}, ending the watch.");
+            return Some(EndReason::Changed);
+        }
+
+        // Break if the output has stabilized for the requested number of iterations
+        if self.until_stable.is_some_and(|until_stable| outputs.stable_iterations >= until_stable) {
+            debug!("Output stable for {} iterations, ending the watch.", outputs.stable_iterations);
+            return Some(EndReason::Stable);
+        }
+
+        // Break once the requested number of iterations ran
+        if self.max_iterations.is_some_and(|count| iteration >= count) {
+            debug!("Ran {iteration} iterations, ending the watch.");
+            return Some(EndReason::IterationCount);
+        }
+
+        // Break if a we have exceeded a 'watch duration' specified
+        if self.watch_duration.is_some_and(|duration| duration < start.elapsed()) {
+            return Some(EndReason::WatchDurationElapsed);
+        }
+
+        None
+    }
+}
+
+
+/// Outputs of the iterations so far, as far as the changes and the stability of the watch go.
+#[derive(Default)]
+pub(super) struct OutputHistory {
+    previous_output: Option<String>,
+    stable_iterations: usize,
+    has_changed: bool,
+}
+
+impl OutputHistory {
+    /// Record the `output` of an iteration, returning the previous output when it changed from it.
+    pub(super) fn record(&mut self, comparator: &dyn Comparator, output: &str) -> Option<String> {
+        let changed_from = match &self.previous_output {
+            Some(previous) if comparator.is_same(previous, output) => {
+                self.stable_iterations += 1;
+                None
+            }
+            _ => {
+                self.stable_iterations = 1;
+                self.previous_output.replace(output.to_string())
+            }
+        };
+        self.has_changed = changed_from.is_some();
+        changed_from
+    }
+}
+
+
+/// Result of an iteration, compared with the previous one and scheduled on by the `WatcherLoop`.
+pub trait IterationResult {
+    fn output(&self) -> &str;
+    fn is_success(&self) -> bool;
+}
+
+impl IterationResult for ExecOutput {
+    fn output(&self) -> &str {
+        &self.output
+    }
+
+    fn is_success(&self) -> bool {
+        ExecOutput::is_success(self)
+    }
+}
+
+/// Events of a `WatcherLoop`, reported to its event sink.
+#[derive(Debug, Clone)]
+pub enum LoopEvent<R> {
+    /// An iteration ran, with the interval following it and the output of the previous
+    /// iteration when the output changed from it.
+    Ran { iteration: usize, result: R, interval: Duration, changed_from: Option<String> },
+    /// The loop sleeps until this instant before the next iteration, unless another trigger fires first.
+    Sleeping(Instant),
+    /// The loop waits for the triggers to fire, without any interval.
+    WaitingForTrigger,
+}
+
+/// Engine running the iterations of a watch on its `WatcherSchedule`, shared by the binaries
+/// and the `WatchSession`. The iterations themselves are run by the caller, which gets the
+/// events of the loop in return.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use watch_rs::models::{watcher::Watcher, watcher_loop::{LoopEvent, WatcherLoop, WatcherSchedule}};
+/// let mut watcher = Watcher::new(30_000, None)?;
+/// let mut schedule = WatcherSchedule::every(Duration::from_secs(5));
+/// schedule.max_iterations = Some(3);
+/// WatcherLoop::new(schedule).run(
+///     |_| watcher.exec_cmd_and_fetch_output("date"),
+///     |event| {
+///         if let LoopEvent::Ran { result, .. } = event {
+///             print!("{}", result.output);
+///         }
+///         true
+///     },
+/// ).map_err(|(_, err)| err)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct WatcherLoop {
+    schedule: WatcherSchedule,
+    should_stop: Arc<AtomicBool>,
+}
+
+impl WatcherLoop {
+    pub fn new(schedule: WatcherSchedule) -> Self {
+        Self { schedule, should_stop: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// The same loop, ending once the `should_stop` flag gets set rather than its own.
+    pub fn with_stop_handle(mut self, should_stop: Arc<AtomicBool>) -> Self {
+        self.should_stop = should_stop;
+        self
+    }
+
+    /// Flag ending the loop after the running iteration once set, usable from any thread.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.should_stop)
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.should_stop.load(Ordering::Acquire)
+    }
+
+    /// Run the iterations on the current thread until the watch ends. `run_iteration` runs the
+    /// iteration of the given number, counted from 1, and `on_event` receives the events of the
+    /// loop, ending the watch when it returns false. An error of `run_iteration` ends the watch,
+    /// returned along with the number of iterations which ran.
+    pub fn run<R: IterationResult>(
+        mut self,
+        mut run_iteration: impl FnMut(usize) -> Result<R, Box<dyn Error>>,
+        mut on_event: impl FnMut(LoopEvent<R>) -> bool,
+    ) -> Result<EndEvent, (usize, Box<dyn Error>)> {
+        let start_checkpoint = Instant::now();
+        let mut triggers = self.schedule.all_triggers();
+        let mut iteration = 0;
+        let mut outputs = OutputHistory::default();
+        let mut interval = self.schedule.interval;
+
+        let reason = 'watch: loop {
+            while self.schedule.paused.load(Ordering::Acquire) && !self.is_stopped() {
+                thread::sleep(PAUSE_POLL_INTERVAL);
+            }
+            if self.is_stopped() {
+                break EndReason::Stopped;
+            }
+
+            iteration += 1;
+            let result = run_iteration(iteration).map_err(|err| (iteration - 1, err))?;
+
+            let next_interval = self.schedule.next_interval(interval, result.is_success());
+            if next_interval != interval {
+                debug!("Changing the interval from {interval:?} to {next_interval:?}");
+                interval = next_interval;
+            }
+
+            let changed_from = outputs.record(self.schedule.comparator.as_ref(), result.output());
+
+            if !on_event(LoopEvent::Ran { iteration, result, interval, changed_from }) || self.is_stopped() {
+                break EndReason::Stopped;
+            }
+            if let Some(reason) = self.schedule.end_reason(iteration, &outputs, start_checkpoint) {
+                break reason;
+            }
+
+            // A skipped run waits for the one scheduled after it instead
+            loop {
+                let live_interval = *self.schedule.live_interval.lock().unwrap();
+                if live_interval != self.schedule.interval {
+                    debug!("Adjusting the interval from {interval:?} to {live_interval:?}");
+                    self.schedule.interval = live_interval;
+                    interval = live_interval;
+                }
+
+                let Some(wait) = self.schedule.wait_before_next_run(start_checkpoint, iteration, interval) else {
+                    debug!("No run left on the schedule, ending the watch.");
+                    break 'watch EndReason::ScheduleExhausted;
+                };
+                let waiting_event = match self.schedule.is_interval_triggered() {
+                    true => LoopEvent::Sleeping(Instant::now() + wait),
+                    false => LoopEvent::WaitingForTrigger,
+                };
+                if !on_event(waiting_event) {
+                    break 'watch EndReason::Stopped;
+                }
+                triggers.iter_mut().for_each(|trigger| trigger.arm(wait));
+
+                match wait_for_trigger(&mut triggers, &self.should_stop) {
+                    Some(ADJUSTMENT_TRIGGER) => {}
+                    Some(trigger) if trigger != "refresh" && self.schedule.skip_next.swap(false, Ordering::AcqRel) => {
+                        debug!("Skipped the run of iteration {}", iteration + 1);
+                    }
+                    Some(trigger) => {
+                        debug!("Iteration {} triggered by the {trigger}", iteration + 1);
+                        break;
+                    }
+                    None => break 'watch EndReason::Stopped,
+                }
+            }
+        };
+
+        Ok(EndEvent { iterations: iteration, reason })
+    }
+}
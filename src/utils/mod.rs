@@ -1,3 +1,65 @@
-use std::error::Error;
+use std::{env, error::Error, path::Path, process::Command, time::Duration};
 
-pub type OpenResult<T=()> = Result<T, Box<dyn Error>>;
\ No newline at end of file
+pub type OpenResult<T=()> = Result<T, Box<dyn Error>>;
+
+/// Parse a duration from either plain (fractional) seconds like `2.5`,
+/// or a human-friendly form like `500ms`, `5m` or `1h 30m`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    if let Ok(seconds) = value.trim().parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string());
+    }
+    humantime::parse_duration(value).map_err(|err| err.to_string())
+}
+
+/// Parse a `KEY=VALUE` environment variable assignment, where `KEY` is a valid shell identifier.
+pub fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value.split_once('=')
+        .ok_or_else(|| format!("invalid environment variable `{value}`, expected `KEY=VALUE`"))?;
+
+    let is_identifier = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return Err(format!("invalid environment variable name `{key}`"));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+
+/// Editors tried in order when neither `$VISUAL` nor `$EDITOR` point to an available editor.
+const FALLBACK_EDITORS: [&str; 3] = ["vim", "vi", "nano"];
+
+/// Whether `program` is a path to an existing file, or can be found in one of the `$PATH` directories.
+fn is_available(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// The user's editor command line, from `$VISUAL`, `$EDITOR` or else the first available fallback editor.
+/// Editor variables may carry arguments, like `code --wait`.
+pub fn find_editor() -> Option<Vec<String>> {
+    let configured_editors = ["VISUAL", "EDITOR"].into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .map(|editor| editor.split_whitespace().map(str::to_string).collect::<Vec<_>>());
+    let fallback_editors = FALLBACK_EDITORS.into_iter().map(|editor| vec![editor.to_string()]);
+
+    configured_editors.chain(fallback_editors)
+        .find(|editor| editor.first().is_some_and(|program| is_available(program)))
+}
+
+/// Open the file at `path` in the user's editor, and wait for the editor to be closed.
+pub fn edit_file(path: &Path) -> OpenResult {
+    let editor = find_editor().ok_or_else(|| format!(
+        "no editor found, set $VISUAL or $EDITOR to your editor (none of {} are installed)",
+        FALLBACK_EDITORS.join(", ")
+    ))?;
+
+    let status = Command::new(&editor[0]).args(&editor[1..]).arg(path).status()?;
+    if !status.success() {
+        return Err(format!("editor `{}` exited with {status}", editor.join(" ")).into());
+    }
+    Ok(())
+}